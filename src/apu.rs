@@ -0,0 +1,607 @@
+use crate::nes::Nes;
+use crate::pulse::Pulse;
+use crate::triangle::Triangle;
+use crate::noise::Noise;
+use crate::dmc::{Dmc,IRQ_DMC};
+use crate::mixer::{self,Mixer};
+use std::cell::Cell;
+
+// Number of timer ticks a channel keeps sounding for, indexed by the top 5 bits written to its length register.
+const LENGTH_TABLE : [u8;32] = [
+    10, 254, 20, 2, 40, 4, 80, 6, 160, 8, 60, 10, 14, 12, 26, 14,
+    12, 16, 24, 18, 48, 20, 96, 22, 192, 24, 72, 26, 16, 28, 32, 30
+];
+
+pub const STATUS_PULSE_1 : u8 = 0b1;
+pub const STATUS_PULSE_2 : u8 = 0b10;
+pub const STATUS_TRIANGLE : u8 = 0b100;
+pub const STATUS_NOISE : u8 = 0b1000;
+pub const STATUS_DMC : u8 = 0b10000;
+pub const STATUS_FRAME_IRQ : u8 = 0b100_0000;
+pub const STATUS_DMC_IRQ : u8 = 0b1000_0000;
+
+pub const IRQ_FRAME_COUNTER : u8 = 0b1; // Bit of Nes::irq_sources, which doubles as the frame interrupt flag.
+const FRAME_COUNTER_FIVE_STEP : u8 = 0b1000_0000;
+const FRAME_COUNTER_IRQ_INHIBIT : u8 = 0b100_0000;
+
+// Volume of a channel, either constant or a sawtooth decaying from 15 at a rate set by the same 4 bits.
+pub struct Envelope {
+    start : Cell<bool>, // Set by writes to the channel's length register, restarts the decay at the next clock.
+    divider : Cell<u8>,
+    decay : Cell<u8>,
+    volume : Cell<u8>, // Constant volume, or the divider period when decaying.
+    looping : Cell<bool>, // Decay wraps back to 15 instead of staying at 0. Shares its bit with the length counter halt.
+    constant : Cell<bool>
+}
+
+impl Envelope {
+    pub fn new() -> Self {
+        Envelope { start: Cell::new(false), divider: Cell::new(0), decay: Cell::new(0), volume: Cell::new(0), looping: Cell::new(false), constant: Cell::new(false) }
+    }
+
+    // --LC VVVV of the channel's first register.
+    pub fn write(&self, value : u8) {
+        self.looping.set(value & 0x20 != 0);
+        self.constant.set(value & 0x10 != 0);
+        self.volume.set(value & 0x0f);
+    }
+
+    pub fn restart(&self) {
+        self.start.set(true);
+    }
+
+    // Clocked by the frame counter every quarter frame.
+    pub fn clock(&self) {
+        if self.start.get() {
+            self.start.set(false);
+            self.decay.set(15);
+            self.divider.set(self.volume.get());
+        }
+        else if self.divider.get() == 0 {
+            self.divider.set(self.volume.get());
+            if self.decay.get() > 0 {
+                self.decay.set(self.decay.get() - 1);
+            }
+            else if self.looping.get() {
+                self.decay.set(15);
+            }
+        }
+        else {
+            self.divider.set(self.divider.get() - 1);
+        }
+    }
+
+    pub fn output(&self) -> u8 {
+        if self.constant.get() { self.volume.get() } else { self.decay.get() }
+    }
+}
+
+// Silences a channel once it has counted down, unless halted. Disabling the channel through $4015 clears it and
+// keeps it from being loaded.
+pub struct LengthCounter {
+    counter : Cell<u8>,
+    halted : Cell<bool>,
+    enabled : Cell<bool>
+}
+
+impl LengthCounter {
+    pub fn new() -> Self {
+        LengthCounter { counter: Cell::new(0), halted: Cell::new(false), enabled: Cell::new(false) }
+    }
+
+    // Loads from the top 5 bits of the channel's length register.
+    pub fn load(&self, value : u8) {
+        if self.enabled.get() {
+            self.counter.set(LENGTH_TABLE[(value >> 3) as usize]);
+        }
+    }
+
+    pub fn set_halted(&self, halted : bool) {
+        self.halted.set(halted);
+    }
+
+    pub fn set_enabled(&self, enabled : bool) {
+        self.enabled.set(enabled);
+        if !enabled {
+            self.counter.set(0);
+        }
+    }
+
+    // Clocked by the frame counter every half frame.
+    pub fn clock(&self) {
+        if !self.halted.get() && self.counter.get() > 0 {
+            self.counter.set(self.counter.get() - 1);
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.counter.get() > 0
+    }
+}
+
+// Sequencer at $4017 clocking the envelopes and linear counter every quarter frame and the length counters and
+// sweeps every half frame. 4-step mode raises an IRQ at the end of each sequence, 5-step mode adds a silent step
+// and never does.
+pub struct FrameCounter {
+    cycle : Cell<u32>, // CPU cycles since the sequence started.
+    five_step : Cell<bool>,
+    irq_inhibit : Cell<bool>,
+    last_write : Cell<u8>,
+    pending_write : Cell<Option<(u8,u8)>> // Value written and the CPU cycles left before it restarts the sequence.
+}
+
+impl FrameCounter {
+    pub fn new() -> Self {
+        FrameCounter { cycle: Cell::new(0), five_step: Cell::new(false), irq_inhibit: Cell::new(false), last_write: Cell::new(0), pending_write: Cell::new(None) }
+    }
+}
+
+pub struct Apu {
+    pub cycles : Cell<u64>, // CPU cycles the APU has been clocked for since power on.
+    pub pulses : [Pulse;2],
+    pub triangle : Triangle,
+    pub noise : Noise,
+    pub dmc : Dmc,
+    pub frame_counter : FrameCounter,
+    pub mixer : Mixer
+}
+
+impl Apu {
+    pub fn new() -> Self {
+        Apu { cycles: Cell::new(0), pulses: [Pulse::new(true), Pulse::new(false)], triangle: Triangle::new(), noise: Noise::new(), dmc: Dmc::new(),
+              frame_counter: FrameCounter::new(), mixer: Mixer::new(mixer::DEFAULT_SAMPLE_RATE) }
+    }
+
+    // Advances the APU by one CPU cycle. The pulse timers run at half the CPU clock.
+    pub fn step(&self, nes : &Nes) {
+        self.cycles.set(self.cycles.get() + 1);
+        self.clock_frame_counter(nes);
+        self.triangle.clock_timer();
+        self.noise.clock_timer();
+        self.dmc.clock_timer();
+        if self.cycles.get() & 1 == 0 {
+            for pulse in &self.pulses {
+                pulse.clock_timer();
+            }
+        }
+        self.mixer.clock(self.output(), nes.region.get().cpu_clock_hz());
+    }
+
+    // All channels through the console's DACs, 0 to about 1.
+    pub fn output(&self) -> f32 {
+        mixer::mix(self.pulses[0].output(), self.pulses[1].output(), self.triangle.output(), self.noise.output(), self.dmc.output())
+    }
+
+    fn clock_frame_counter(&self, nes : &Nes) {
+        let frame_counter = &self.frame_counter;
+        match frame_counter.pending_write.get() {
+            Some((value, 1)) => {
+                frame_counter.pending_write.set(None);
+                frame_counter.cycle.set(0);
+                frame_counter.five_step.set(value & FRAME_COUNTER_FIVE_STEP != 0);
+                if frame_counter.five_step.get() {
+                    // Switching to 5-step mode clocks everything straight away.
+                    self.quarter_frame();
+                    self.half_frame();
+                }
+                return;
+            }
+            Some((value, delay)) => frame_counter.pending_write.set(Some((value, delay - 1))),
+            None => {}
+        }
+        let cycle = frame_counter.cycle.get() + 1;
+        frame_counter.cycle.set(cycle);
+        let steps = nes.region.get().frame_counter_steps();
+        if cycle == steps[0] || cycle == steps[2] {
+            self.quarter_frame();
+        }
+        else if cycle == steps[1] {
+            self.quarter_frame();
+            self.half_frame();
+        }
+        else if frame_counter.five_step.get() {
+            if cycle == steps[4] {
+                self.quarter_frame();
+                self.half_frame();
+            }
+            else if cycle == steps[4] + 1 {
+                frame_counter.cycle.set(0);
+            }
+        }
+        else if cycle + 1 >= steps[3] && cycle <= steps[3] + 1 {
+            // The flag is set on three cycles in a row, so acknowledging it on the first two doesn't stick.
+            if !frame_counter.irq_inhibit.get() {
+                nes.irq_sources.set(nes.irq_sources.get() | IRQ_FRAME_COUNTER);
+            }
+            if cycle == steps[3] {
+                self.quarter_frame();
+                self.half_frame();
+            }
+            else if cycle == steps[3] + 1 {
+                frame_counter.cycle.set(0);
+            }
+        }
+    }
+
+    // Envelopes and the triangle's linear counter, clocked four times a frame.
+    pub fn quarter_frame(&self) {
+        for pulse in &self.pulses {
+            pulse.envelope.clock();
+        }
+        self.triangle.clock_linear_counter();
+        self.noise.envelope.clock();
+    }
+
+    // Length counters and sweeps, clocked twice a frame.
+    pub fn half_frame(&self) {
+        for pulse in &self.pulses {
+            pulse.length_counter.clock();
+            pulse.clock_sweep();
+        }
+        self.triangle.length_counter.clock();
+        self.noise.length_counter.clock();
+    }
+
+    // $4015: whether each channel's length counter is still running, whether the DMC is still playing and the
+    // interrupt flags. Bit 5 isn't driven. Reading acknowledges the frame interrupt.
+    pub fn read_status(&self, nes : &Nes) -> u8 {
+        let mut status = nes.open_bus.get() & 0x20;
+        let channels = [(&self.pulses[0].length_counter, STATUS_PULSE_1), (&self.pulses[1].length_counter, STATUS_PULSE_2),
+                        (&self.triangle.length_counter, STATUS_TRIANGLE), (&self.noise.length_counter, STATUS_NOISE)];
+        for (length_counter, bit) in channels.iter() {
+            if length_counter.is_active() {
+                status |= bit;
+            }
+        }
+        if self.dmc.is_active() {
+            status |= STATUS_DMC;
+        }
+        if nes.irq_sources.get() & IRQ_FRAME_COUNTER != 0 {
+            status |= STATUS_FRAME_IRQ;
+        }
+        if nes.irq_sources.get() & IRQ_DMC != 0 {
+            status |= STATUS_DMC_IRQ;
+        }
+        nes.irq_sources.set(nes.irq_sources.get() & !IRQ_FRAME_COUNTER);
+        status
+    }
+
+    pub fn write_register(&self, nes : &Nes, address : u16, value : u8) {
+        match address {
+            0x4000..=0x4007 => self.pulses[((address - 0x4000) / 4) as usize].write_register(address & 3, value),
+            0x4008..=0x400b => self.triangle.write_register(address & 3, value),
+            0x400c..=0x400f => self.noise.write_register(nes.region.get(), address & 3, value),
+            0x4010..=0x4013 => self.dmc.write_register(nes, address & 3, value),
+            0x4015 => {
+                self.pulses[0].length_counter.set_enabled(value & STATUS_PULSE_1 != 0);
+                self.pulses[1].length_counter.set_enabled(value & STATUS_PULSE_2 != 0);
+                self.triangle.length_counter.set_enabled(value & STATUS_TRIANGLE != 0);
+                self.noise.length_counter.set_enabled(value & STATUS_NOISE != 0);
+                self.dmc.set_enabled(value & STATUS_DMC != 0);
+                nes.irq_sources.set(nes.irq_sources.get() & !IRQ_DMC);
+            }
+            0x4017 => {
+                // MI-- ----. The inhibit flag acts at once but the sequence restarts 3 or 4 cycles later, depending
+                // on whether the write lands on an APU cycle. Instructions run whole, so the parity of the cycles
+                // clocked so far stands in for the write cycle's.
+                let frame_counter = &self.frame_counter;
+                frame_counter.last_write.set(value);
+                frame_counter.irq_inhibit.set(value & FRAME_COUNTER_IRQ_INHIBIT != 0);
+                if frame_counter.irq_inhibit.get() {
+                    nes.irq_sources.set(nes.irq_sources.get() & !IRQ_FRAME_COUNTER);
+                }
+                frame_counter.pending_write.set(Some((value, if self.cycles.get() & 1 == 0 { 3 } else { 4 })));
+            }
+            _ => {}
+        }
+    }
+
+    // Reset silences every channel as if $4015 had been written with 0, and restarts the frame counter as if its
+    // last value had been written again.
+    pub fn reset(&self, nes : &Nes) {
+        self.write_register(nes, 0x4015, 0);
+        self.write_register(nes, 0x4017, self.frame_counter.last_write.get());
+    }
+}
+
+#[test]
+fn test_envelope_decays_and_loops() {
+    let envelope = Envelope::new();
+    envelope.write(0x01); // Decaying, divider period 1
+    envelope.restart();
+    envelope.clock();
+    assert_eq!(envelope.output(), 15);
+    envelope.clock();
+    assert_eq!(envelope.output(), 15);
+    envelope.clock();
+    assert_eq!(envelope.output(), 14);
+    for _ in 0..28 {
+        envelope.clock();
+    }
+    assert_eq!(envelope.output(), 0);
+    envelope.clock();
+    envelope.clock();
+    assert_eq!(envelope.output(), 0); // Stays silent without the loop flag
+    envelope.write(0x21);
+    envelope.clock();
+    envelope.clock();
+    assert_eq!(envelope.output(), 15);
+    envelope.write(0x17);
+    assert_eq!(envelope.output(), 7);
+}
+
+#[test]
+fn test_length_counter_table_halt_and_enable() {
+    let length_counter = LengthCounter::new();
+    length_counter.load(0x08);
+    assert!(!length_counter.is_active()); // Disabled channels can't be loaded
+    length_counter.set_enabled(true);
+    length_counter.load(0x18); // Index 3: 2
+    length_counter.clock();
+    assert!(length_counter.is_active());
+    length_counter.clock();
+    assert!(!length_counter.is_active());
+    length_counter.clock();
+    length_counter.load(0x08); // Index 1: 254
+    length_counter.set_halted(true);
+    for _ in 0..300 {
+        length_counter.clock();
+    }
+    assert!(length_counter.is_active());
+    length_counter.set_enabled(false);
+    assert!(!length_counter.is_active());
+}
+
+#[test]
+fn test_apu_status_reports_length_counters() {
+    let nes = Nes::new();
+    nes.write(0x4015, STATUS_PULSE_1 | STATUS_PULSE_2 | STATUS_TRIANGLE | STATUS_NOISE);
+    nes.write(0x4003, 0x08);
+    nes.write(0x4007, 0x18);
+    nes.write(0x400b, 0x08);
+    nes.write(0x400f, 0x18);
+    assert_eq!(nes.read(0x4015) & 0x1f, STATUS_PULSE_1 | STATUS_PULSE_2 | STATUS_TRIANGLE | STATUS_NOISE);
+    nes.apu.half_frame();
+    nes.apu.half_frame();
+    assert_eq!(nes.read(0x4015) & 0x1f, STATUS_PULSE_1 | STATUS_TRIANGLE);
+    nes.write(0x4015, 0);
+    assert_eq!(nes.read(0x4015) & 0x1f, 0);
+    nes.write(0x4003, 0x08);
+    assert_eq!(nes.read(0x4015) & 0x1f, 0);
+}
+
+#[test]
+fn test_apu_status_reports_dmc() {
+    let nes = Nes::new();
+    nes.write(0x4010, 0x80);
+    nes.write(0x4013, 0x00); // 1 byte sample
+    nes.write(0x4015, STATUS_DMC);
+    assert_eq!(nes.read(0x4015) & 0x9f, STATUS_DMC);
+    nes.apu.dmc.fill_sample_buffer(&nes, 0);
+    assert_eq!(nes.read(0x4015) & 0x9f, STATUS_DMC_IRQ);
+    nes.write(0x4015, 0); // Writing $4015 acknowledges the DMC interrupt
+    assert_eq!(nes.read(0x4015) & 0x9f, 0);
+}
+
+#[cfg(test)]
+fn run_apu(nes : &Nes, cycles : u32) {
+    for _ in 0..cycles {
+        nes.apu.step(nes);
+    }
+}
+
+// Writes $4017 on an even cycle and runs until the write takes effect.
+#[cfg(test)]
+fn restart_frame_counter(nes : &Nes, value : u8) {
+    if nes.apu.cycles.get() & 1 == 1 {
+        run_apu(nes, 1);
+    }
+    nes.write(0x4017, value);
+    run_apu(nes, 3);
+}
+
+// Cycles, counted from the first one run, on which the frame counter clocked a quarter and a half frame. Pulse 1's
+// envelope is restarted and its length counter reloaded before every cycle, so each clock shows up on its own.
+#[cfg(test)]
+fn frame_counter_clocks(nes : &Nes, cycles : u32) -> (Vec<u32>,Vec<u32>) {
+    let pulse = &nes.apu.pulses[0];
+    pulse.length_counter.set_enabled(true);
+    let (mut quarters, mut halves) = (Vec::new(), Vec::new());
+    for cycle in 1..=cycles {
+        pulse.envelope.restart();
+        pulse.length_counter.counter.set(10);
+        nes.apu.step(nes);
+        if !pulse.envelope.start.get() {
+            quarters.push(cycle);
+        }
+        if pulse.length_counter.counter.get() != 10 {
+            halves.push(cycle);
+        }
+    }
+    (quarters, halves)
+}
+
+#[test]
+fn test_frame_counter_four_step_sequence() {
+    let nes = Nes::new();
+    restart_frame_counter(&nes, FRAME_COUNTER_IRQ_INHIBIT);
+    let (quarters, halves) = frame_counter_clocks(&nes, 2 * 29830);
+    assert_eq!(quarters, vec![7457, 14913, 22371, 29829, 29830 + 7457, 29830 + 14913, 29830 + 22371, 29830 + 29829]);
+    assert_eq!(halves, vec![14913, 29829, 29830 + 14913, 29830 + 29829]);
+}
+
+#[test]
+fn test_frame_counter_five_step_sequence() {
+    let nes = Nes::new();
+    restart_frame_counter(&nes, FRAME_COUNTER_FIVE_STEP);
+    let (quarters, halves) = frame_counter_clocks(&nes, 2 * 37282);
+    assert_eq!(quarters, vec![7457, 14913, 22371, 37281, 37282 + 7457, 37282 + 14913, 37282 + 22371, 37282 + 37281]);
+    assert_eq!(halves, vec![14913, 37281, 37282 + 14913, 37282 + 37281]);
+    assert_eq!(nes.irq_sources.get(), 0);
+}
+
+#[test]
+fn test_frame_counter_irq_set_on_three_cycles() {
+    for &(value, irq_cycles) in [(0x00, &[29828, 29829, 29830][..]), (FRAME_COUNTER_IRQ_INHIBIT, &[][..])].iter() {
+        let nes = Nes::new();
+        restart_frame_counter(&nes, value);
+        run_apu(&nes, 29827);
+        let mut raised = Vec::new();
+        for cycle in 29828..=29832 {
+            run_apu(&nes, 1);
+            if nes.irq_sources.get() == IRQ_FRAME_COUNTER {
+                raised.push(cycle);
+            }
+            nes.read(0x4015); // Acknowledges the interrupt
+        }
+        assert_eq!(raised, irq_cycles);
+    }
+}
+
+#[test]
+fn test_frame_counter_write_delay_by_parity() {
+    // Switching to 5-step mode clocks at once, on the cycle the write takes effect: 3 cycles after a write on an
+    // even cycle, 4 after an odd one.
+    for &(cycles_before, delay) in [(0, 3), (1, 4), (2, 3), (3, 4)].iter() {
+        let nes = Nes::new();
+        run_apu(&nes, cycles_before);
+        nes.write(0x4017, FRAME_COUNTER_FIVE_STEP);
+        let (quarters, halves) = frame_counter_clocks(&nes, 5);
+        assert_eq!((quarters, halves), (vec![delay], vec![delay]), "write after {} cycles", cycles_before);
+    }
+}
+
+#[test]
+fn test_frame_counter_four_step_irq_and_length_clocks() {
+    let nes = Nes::new();
+    restart_frame_counter(&nes, 0x00);
+    nes.write(0x4015, STATUS_PULSE_1);
+    nes.write(0x4003, 0x18); // Length 2
+    run_apu(&nes, 14912);
+    assert_eq!(nes.read(0x4015) & STATUS_PULSE_1, STATUS_PULSE_1);
+    run_apu(&nes, 1); // First half frame
+    run_apu(&nes, 29828 - 14913 - 1);
+    assert_eq!(nes.irq_sources.get(), 0);
+    assert_eq!(nes.read(0x4015) & STATUS_PULSE_1, STATUS_PULSE_1);
+    run_apu(&nes, 1);
+    assert_eq!(nes.irq_sources.get(), IRQ_FRAME_COUNTER); // Raised at 29828, a cycle before the half frame
+    run_apu(&nes, 1);
+    assert_eq!(nes.read(0x4015) & (STATUS_PULSE_1 | STATUS_FRAME_IRQ), STATUS_FRAME_IRQ);
+    run_apu(&nes, 1); // Set again on the last cycle of the sequence
+    assert_eq!(nes.read(0x4015) & STATUS_FRAME_IRQ, STATUS_FRAME_IRQ);
+    assert_eq!(nes.read(0x4015) & STATUS_FRAME_IRQ, 0);
+    run_apu(&nes, 29827); // The next sequence starts from 0 at 29830
+    assert_eq!(nes.irq_sources.get(), 0);
+    run_apu(&nes, 1);
+    assert_eq!(nes.irq_sources.get(), IRQ_FRAME_COUNTER);
+}
+
+#[test]
+fn test_frame_counter_irq_inhibit() {
+    let nes = Nes::new();
+    restart_frame_counter(&nes, 0x00);
+    run_apu(&nes, 29830);
+    assert_eq!(nes.irq_sources.get(), IRQ_FRAME_COUNTER);
+    nes.write(0x4017, FRAME_COUNTER_IRQ_INHIBIT);
+    assert_eq!(nes.irq_sources.get(), 0); // Setting the inhibit flag acknowledges at once
+    run_apu(&nes, 2 * 29830);
+    assert_eq!(nes.irq_sources.get(), 0);
+}
+
+#[test]
+fn test_frame_counter_five_step() {
+    let nes = Nes::new();
+    nes.write(0x4015, STATUS_PULSE_1);
+    nes.write(0x4003, 0x18); // Length 2
+    restart_frame_counter(&nes, FRAME_COUNTER_FIVE_STEP); // Clocks a half frame as it takes effect
+    run_apu(&nes, 14912);
+    assert_eq!(nes.read(0x4015) & STATUS_PULSE_1, STATUS_PULSE_1);
+    run_apu(&nes, 1);
+    assert_eq!(nes.read(0x4015) & STATUS_PULSE_1, 0);
+    nes.write(0x4003, 0x18);
+    run_apu(&nes, 37281 - 14913 - 1);
+    assert_eq!(nes.read(0x4015) & STATUS_PULSE_1, STATUS_PULSE_1);
+    run_apu(&nes, 1);
+    run_apu(&nes, 14913 + 1);
+    assert_eq!(nes.read(0x4015) & STATUS_PULSE_1, 0);
+    assert_eq!(nes.irq_sources.get(), 0);
+}
+
+#[test]
+fn test_frame_counter_write_delay_jitter() {
+    let nes = Nes::new();
+    run_apu(&nes, 2);
+    nes.write(0x4017, FRAME_COUNTER_FIVE_STEP);
+    assert_eq!(nes.apu.frame_counter.pending_write.get(), Some((FRAME_COUNTER_FIVE_STEP, 3)));
+    run_apu(&nes, 1);
+    nes.write(0x4017, FRAME_COUNTER_FIVE_STEP);
+    assert_eq!(nes.apu.frame_counter.pending_write.get(), Some((FRAME_COUNTER_FIVE_STEP, 4)));
+    run_apu(&nes, 3);
+    assert!(nes.apu.frame_counter.pending_write.get().is_some());
+    run_apu(&nes, 1);
+    assert_eq!(nes.apu.frame_counter.pending_write.get(), None);
+    assert!(nes.apu.frame_counter.five_step.get());
+    assert_eq!(nes.apu.frame_counter.cycle.get(), 0);
+}
+
+#[test]
+fn test_frame_counter_pal_timing() {
+    let nes = Nes::new();
+    nes.region.set(crate::region::Region::Pal);
+    restart_frame_counter(&nes, 0x00);
+    run_apu(&nes, 33251);
+    assert_eq!(nes.irq_sources.get(), 0);
+    run_apu(&nes, 1);
+    assert_eq!(nes.irq_sources.get(), IRQ_FRAME_COUNTER);
+}
+
+#[test]
+fn test_apu_plays_pulse_through_mixer() {
+    let nes = Nes::new();
+    nes.write(0x4015, STATUS_PULSE_1);
+    nes.write(0x4000, 0xbf); // 50% duty, constant volume 15
+    nes.write(0x4002, 0xfd); // About 440 Hz
+    nes.write(0x4003, 0x08);
+    run_apu(&nes, 1_789_773 / 10);
+    let samples = nes.apu.mixer.take_samples();
+    assert!(samples.len() > 4300);
+    // Rising edges, with some hysteresis against the ringing of the band-limited steps.
+    let mut high = false;
+    let mut edges = 0;
+    for &sample in &samples {
+        if !high && sample > 0.02 {
+            high = true;
+            edges += 1;
+        }
+        else if high && sample < -0.02 {
+            high = false;
+        }
+    }
+    assert!((42..=46).contains(&edges), "{} edges", edges);
+    assert!(samples.iter().cloned().fold(0.0, f32::max) > 0.05);
+}
+
+// blargg's apu_test and apu_reset ROMs. They aren't in the repository and the CPU can't run them through yet, so
+// the tests are ignored: copy the ROMs into roms/ and run them with --ignored.
+#[cfg(test)]
+fn run_blargg_roms(directory : &str, names : &[&str]) {
+    for name in names {
+        let path = format!("roms/{}/{}.nes", directory, name);
+        let result = crate::scheduler::run_test_rom(&path).unwrap_or_else(|| panic!("{} isn't there", path));
+        assert_eq!(result, Ok(()), "{}", path);
+    }
+}
+
+#[test]
+#[ignore = "needs blargg's ROMs in roms/ and a CPU that runs them"]
+fn test_frame_counter_blargg_roms() {
+    run_blargg_roms("apu_test/rom_singles", &["3-irq_flag", "4-jitter", "5-len_timing", "6-irq_flag_timing"]);
+    run_blargg_roms("apu_reset", &["4017_timing", "4017_written", "irq_flag_cleared", "works_immediately"]);
+}
+
+#[test]
+#[ignore = "needs blargg's ROMs in roms/ and a CPU that runs them"]
+fn test_apu_blargg_roms() {
+    run_blargg_roms("apu_test/rom_singles", &["1-len_ctr", "2-len_table", "7-dmc_basics", "8-dmc_rates"]);
+    run_blargg_roms("apu_reset", &["4015_cleared", "len_ctrs_enabled"]);
+}
@@ -0,0 +1,192 @@
+use crate::mixer;
+use rodio::{Sink, Source};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+// Plays the mixer's samples on the default output device. The emulator fills a small ring buffer once a frame and the
+// device drains it at its own clock, which never quite matches the video timing the frames are paced by. Rather than
+// letting the buffer slowly run dry (crackling) or overflow (growing latency), the mixer's sample rate is nudged by a
+// fraction of a percent towards whatever keeps the buffer half full. The pitch change is far too small to hear.
+
+const BUFFER_SECONDS : f64 = 0.1;
+const TARGET_FILL : f64 = 0.5; // Fraction of the buffer kept queued, about 50 ms of latency.
+const MAX_RATE_ADJUSTMENT : f64 = 0.005; // Furthest the sample rate is moved, when the buffer is empty or full.
+pub const VOLUME_STEP : f32 = 0.1;
+
+struct RingBuffer {
+    samples : VecDeque<f32>,
+    capacity : usize,
+    last : f32 // Repeated when the buffer runs dry, so an underrun is a gap rather than a click.
+}
+
+// The device's end of the ring buffer, pulled from the audio thread.
+struct Stream {
+    buffer : Arc<Mutex<RingBuffer>>,
+    sample_rate : u32
+}
+
+impl Iterator for Stream {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let mut buffer = self.buffer.lock().unwrap();
+        if let Some(sample) = buffer.samples.pop_front() {
+            buffer.last = sample;
+        }
+        Some(buffer.last)
+    }
+}
+
+impl Source for Stream {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+pub struct AudioOutput {
+    sink : Option<Sink>, // Playback stops when it is dropped. None without an output device, the samples then pile up and get dropped.
+    buffer : Arc<Mutex<RingBuffer>>,
+    sample_rate : f64,
+    volume : f32, // 0 to 1.
+    muted : bool
+}
+
+impl AudioOutput {
+    // Opens the default output device at its preferred sample rate.
+    pub fn new() -> Self {
+        let device = rodio::default_output_device();
+        let sample_rate = device.as_ref().and_then(|device| device.default_output_format().ok())
+                                .map_or(mixer::DEFAULT_SAMPLE_RATE, |format| format.sample_rate.0 as f64);
+        let mut audio = AudioOutput::silent(sample_rate);
+        match device {
+            Some(device) => {
+                let sink = Sink::new(&device);
+                sink.append(audio.stream());
+                audio.sink = Some(sink);
+            }
+            None => println!("No audio output device, running without sound")
+        }
+        audio
+    }
+
+    fn silent(sample_rate : f64) -> Self {
+        let capacity = (sample_rate * BUFFER_SECONDS) as usize;
+        let buffer = RingBuffer { samples: VecDeque::with_capacity(capacity), capacity, last: 0.0 };
+        AudioOutput { sink: None, buffer: Arc::new(Mutex::new(buffer)), sample_rate, volume: 1.0, muted: false }
+    }
+
+    fn stream(&self) -> Stream {
+        Stream { buffer: self.buffer.clone(), sample_rate: self.sample_rate as u32 }
+    }
+
+    pub fn sample_rate(&self) -> f64 {
+        self.sample_rate
+    }
+
+    // Adds samples at the current volume. What doesn't fit is dropped.
+    pub fn queue(&self, samples : &[f32]) {
+        let gain = if self.muted { 0.0 } else { self.volume };
+        let mut buffer = self.buffer.lock().unwrap();
+        let space = buffer.capacity - buffer.samples.len();
+        buffer.samples.extend(samples.iter().take(space).map(|sample| sample * gain));
+    }
+
+    // Fraction of the buffer queued, 0 to 1.
+    pub fn fill(&self) -> f64 {
+        let buffer = self.buffer.lock().unwrap();
+        buffer.samples.len() as f64 / buffer.capacity as f64
+    }
+
+    // Factor for the mixer's sample rate: above 1 makes more samples to refill a low buffer, below 1 fewer to drain a
+    // full one, proportional to how far it is from the target.
+    pub fn rate_adjustment(&self) -> f64 {
+        1.0 + (MAX_RATE_ADJUSTMENT * (TARGET_FILL - self.fill()) / TARGET_FILL)
+    }
+
+    pub fn volume(&self) -> f32 {
+        self.volume
+    }
+
+    pub fn set_volume(&mut self, volume : f32) {
+        self.volume = volume.clamp(0.0, 1.0);
+    }
+
+    pub fn is_muted(&self) -> bool {
+        self.muted
+    }
+
+    pub fn set_muted(&mut self, muted : bool) {
+        self.muted = muted;
+    }
+}
+
+#[test]
+fn test_audio_ring_buffer_plays_in_order_and_holds_on_underrun() {
+    let audio = AudioOutput::silent(1000.0);
+    let mut stream = audio.stream();
+    audio.queue(&[0.1, 0.2, 0.3]);
+    assert_eq!(stream.by_ref().take(5).collect::<Vec<f32>>(), vec![0.1, 0.2, 0.3, 0.3, 0.3]);
+    audio.queue(&[0.5; 150]);
+    assert_eq!(audio.fill(), 1.0); // 100 samples of room, the rest is dropped
+    assert_eq!(stream.by_ref().take(101).last(), Some(0.5));
+    assert_eq!(audio.fill(), 0.0);
+}
+
+#[test]
+fn test_audio_rate_adjustment_steers_towards_half_full() {
+    let audio = AudioOutput::silent(1000.0);
+    assert_eq!(audio.rate_adjustment(), 1.0 + MAX_RATE_ADJUSTMENT);
+    audio.queue(&[0.0; 25]);
+    assert!((audio.rate_adjustment() - (1.0 + (MAX_RATE_ADJUSTMENT / 2.0))).abs() < 1e-9);
+    audio.queue(&[0.0; 25]);
+    assert_eq!(audio.rate_adjustment(), 1.0);
+    audio.queue(&[0.0; 50]);
+    assert_eq!(audio.rate_adjustment(), 1.0 - MAX_RATE_ADJUSTMENT);
+}
+
+#[test]
+fn test_audio_rate_control_settles_against_a_faster_device() {
+    // The device plays 0.25% faster than the frames produce samples. Left alone that empties the buffer within 20
+    // seconds, with rate control the level settles where the two rates match.
+    let audio = AudioOutput::silent(48_000.0);
+    let mut stream = audio.stream();
+    let mut produced = 0.0;
+    for _ in 0..6000 {
+        produced += 800.0 * audio.rate_adjustment();
+        audio.queue(&vec![0.0; produced as usize]);
+        produced -= (produced as usize) as f64;
+        stream.by_ref().take(802).count();
+    }
+    assert!((audio.fill() - 0.25).abs() < 0.01, "fill {}", audio.fill());
+    assert!((audio.rate_adjustment() - (802.0 / 800.0)).abs() < 0.0001);
+}
+
+#[test]
+fn test_audio_volume_and_mute() {
+    let mut audio = AudioOutput::silent(1000.0);
+    let mut stream = audio.stream();
+    audio.set_volume(0.5);
+    audio.queue(&[0.4]);
+    audio.set_muted(true);
+    audio.queue(&[0.4]);
+    audio.set_muted(false);
+    audio.set_volume(1.5);
+    audio.queue(&[0.4]);
+    assert_eq!(stream.by_ref().take(3).collect::<Vec<f32>>(), vec![0.2, 0.0, 0.4]);
+    assert_eq!(audio.volume(), 1.0);
+    audio.set_volume(-1.0);
+    assert_eq!(audio.volume(), 0.0);
+}
@@ -0,0 +1,200 @@
+use crate::rom::{Mirroring,Rom};
+use crate::power::{PowerOnRam,RamRegion};
+use std::cell::{Cell,RefCell};
+
+// Cartridge as seen from the CPU bus at $4020-$FFFF. Mapper behaviour is picked from the iNES mapper number.
+pub struct Cartridge {
+    pub rom : Rom,
+    prg_ram : RefCell<Vec<u8>>,
+    prg_bank : Cell<u8>, // Switchable 16K bank at $8000 on UxROM, 32K bank at $8000 on AxROM.
+    mirroring : Cell<Mirroring>,
+    vram : RefCell<Vec<u8>>, // Extra 2KB of nametable RAM on four-screen boards.
+    chr_ram : RefCell<Vec<u8>> // Pattern tables for boards without CHR ROM.
+}
+
+impl Cartridge {
+    pub fn new(rom : Rom) -> Result<Cartridge,String> {
+        match rom.rom_mapper_type {
+            0 | 2 | 7 => {
+                if rom.rom_banks.is_empty() {
+                    return Err(String::from("Cartridge has no PRG ROM banks!"));
+                }
+                let prg_ram = vec![0;(rom.number_of_8k_ram_banks as usize) * 8192];
+                let vram = if rom.has_four_screen_vram_layout { vec![0;2048] } else { Vec::new() };
+                let mirroring = Cell::new(initial_mirroring(&rom));
+                let chr_ram = vec![0;rom.chr_ram_size];
+                Ok(Cartridge { rom, prg_ram: RefCell::new(prg_ram), prg_bank: Cell::new(0), mirroring, vram: RefCell::new(vram),
+                               chr_ram: RefCell::new(chr_ram) })
+            }
+            mapper => Err(format!("Mapper {} is not supported!", mapper))
+        }
+    }
+
+    // Puts the mapper registers and PRG RAM into their power-on state.
+    pub fn power_on(&self, power_on_ram : &PowerOnRam) {
+        self.prg_bank.set(0);
+        self.mirroring.set(initial_mirroring(&self.rom));
+        power_on_ram.fill(RamRegion::PrgRam, &mut self.prg_ram.borrow_mut());
+        power_on_ram.fill(RamRegion::Nametables, &mut self.vram.borrow_mut());
+        power_on_ram.fill(RamRegion::ChrRam, &mut self.chr_ram.borrow_mut());
+    }
+
+    // Returns None for addresses the cartridge doesn't drive, which read as open bus.
+    pub fn cpu_read(&self, address : u16) -> Option<u8> {
+        match address {
+            0x6000..=0x7fff => {
+                let prg_ram = self.prg_ram.borrow();
+                if prg_ram.is_empty() {
+                    None
+                }
+                else {
+                    Some(prg_ram[(address as usize - 0x6000) % prg_ram.len()])
+                }
+            }
+            0x8000..=0xffff if self.rom.rom_mapper_type == 7 => {
+                let bank = ((self.prg_bank.get() & 0x07) as usize * 2) + ((address as usize - 0x8000) / 16384);
+                Some(self.prg_rom_bank(bank)[(address & 0x3fff) as usize])
+            }
+            0x8000..=0xbfff => Some(self.prg_rom_bank(self.prg_bank.get() as usize)[address as usize - 0x8000]),
+            0xc000..=0xffff => Some(self.prg_rom_bank(self.rom.rom_banks.len() - 1)[address as usize - 0xc000]),
+            _ => None
+        }
+    }
+
+    pub fn cpu_write(&self, address : u16, value : u8) {
+        match address {
+            0x6000..=0x7fff => {
+                let mut prg_ram = self.prg_ram.borrow_mut();
+                let size = prg_ram.len();
+                if size != 0 {
+                    prg_ram[(address as usize - 0x6000) % size] = value;
+                }
+            }
+            0x8000..=0xffff if self.rom.rom_mapper_type == 2 => self.prg_bank.set(value),
+            0x8000..=0xffff if self.rom.rom_mapper_type == 7 => {
+                self.prg_bank.set(value & 0x07);
+                self.mirroring.set(if value & 0x10 == 0 { Mirroring::SingleScreenA } else { Mirroring::SingleScreenB });
+            }
+            _ => {}
+        }
+    }
+
+    // Pattern table read from the PPU at $0000-$1FFF.
+    pub fn ppu_read(&self, address : u16) -> u8 {
+        let chr_ram = self.chr_ram.borrow();
+        if chr_ram.is_empty() {
+            self.rom.vrom_banks.first().map_or(0, |bank| bank[(address & 0x1fff) as usize])
+        }
+        else {
+            chr_ram[(address & 0x1fff) as usize % chr_ram.len()]
+        }
+    }
+
+    // Only CHR RAM can be written, CHR ROM ignores it.
+    pub fn ppu_write(&self, address : u16, value : u8) {
+        let mut chr_ram = self.chr_ram.borrow_mut();
+        let size = chr_ram.len();
+        if size != 0 {
+            chr_ram[(address & 0x1fff) as usize % size] = value;
+        }
+    }
+
+    pub fn mirroring(&self) -> Mirroring {
+        self.mirroring.get()
+    }
+
+    // Nametables that Mirroring::ciram_index doesn't put in CIRAM.
+    pub fn nametable_read(&self, address : u16) -> u8 {
+        let vram = self.vram.borrow();
+        if vram.is_empty() { 0 } else { vram[(address & 0x7ff) as usize] }
+    }
+
+    pub fn nametable_write(&self, address : u16, value : u8) {
+        let mut vram = self.vram.borrow_mut();
+        if !vram.is_empty() {
+            vram[(address & 0x7ff) as usize] = value;
+        }
+    }
+
+    fn prg_rom_bank(&self, bank : usize) -> &[u8;16384] {
+        &self.rom.rom_banks[bank % self.rom.rom_banks.len()]
+    }
+}
+
+fn initial_mirroring(rom : &Rom) -> Mirroring {
+    if rom.has_four_screen_vram_layout {
+        Mirroring::FourScreen
+    }
+    else if rom.rom_mapper_type == 7 {
+        Mirroring::SingleScreenA
+    }
+    else {
+        rom.mirroring
+    }
+}
+
+#[test]
+fn test_nrom_mirrors_single_prg_bank() {
+    let cartridge = Cartridge::new(crate::rom::test_rom(0, 1, 1)).unwrap();
+    assert_eq!(cartridge.cpu_read(0x8000), Some(0));
+    assert_eq!(cartridge.cpu_read(0xc000), Some(0));
+    assert_eq!(cartridge.cpu_read(0x5000), None);
+}
+
+#[test]
+fn test_uxrom_switches_low_bank_and_fixes_last() {
+    let cartridge = Cartridge::new(crate::rom::test_rom(2, 4, 0)).unwrap();
+    assert_eq!(cartridge.cpu_read(0x8000), Some(0));
+    cartridge.cpu_write(0x8000, 2);
+    assert_eq!(cartridge.cpu_read(0x8000), Some(2));
+    assert_eq!(cartridge.cpu_read(0xffff), Some(3));
+    cartridge.power_on(&PowerOnRam::Zeros);
+    assert_eq!(cartridge.cpu_read(0x8000), Some(0));
+}
+
+#[test]
+fn test_prg_ram_power_on_and_write() {
+    let cartridge = Cartridge::new(crate::rom::test_rom(0, 1, 1)).unwrap();
+    cartridge.power_on(&PowerOnRam::Ones);
+    assert_eq!(cartridge.cpu_read(0x6000), Some(0xff));
+    cartridge.cpu_write(0x7fff, 0x12);
+    assert_eq!(cartridge.cpu_read(0x7fff), Some(0x12));
+}
+
+#[test]
+fn test_unsupported_mapper_is_rejected() {
+    assert!(Cartridge::new(crate::rom::test_rom(4, 1, 1)).is_err());
+}
+
+#[test]
+fn test_axrom_switches_32k_banks_and_single_screen() {
+    let cartridge = Cartridge::new(crate::rom::test_rom(7, 4, 0)).unwrap();
+    assert_eq!(cartridge.mirroring(), Mirroring::SingleScreenA);
+    cartridge.cpu_write(0x8000, 0x11);
+    assert_eq!(cartridge.cpu_read(0x8000), Some(2));
+    assert_eq!(cartridge.cpu_read(0xc000), Some(3));
+    assert_eq!(cartridge.mirroring(), Mirroring::SingleScreenB);
+    cartridge.cpu_write(0x8000, 0x00);
+    assert_eq!(cartridge.mirroring(), Mirroring::SingleScreenA);
+}
+
+#[test]
+fn test_four_screen_header_overrides_mirroring() {
+    let mut rom = crate::rom::test_rom(0, 1, 1);
+    rom.has_four_screen_vram_layout = true;
+    let cartridge = Cartridge::new(rom).unwrap();
+    assert_eq!(cartridge.mirroring(), Mirroring::FourScreen);
+    cartridge.nametable_write(0x2c01, 0x42);
+    assert_eq!(cartridge.nametable_read(0x2c01), 0x42);
+    assert_eq!(cartridge.nametable_read(0x2801), 0x00);
+}
+
+#[test]
+fn test_chr_ram_is_writable_and_chr_rom_is_not() {
+    let cartridge = Cartridge::new(crate::rom::test_rom(2, 2, 0)).unwrap();
+    cartridge.ppu_write(0x1234, 0x56);
+    assert_eq!(cartridge.ppu_read(0x1234), 0x56);
+    let cartridge = Cartridge::new(crate::rom::test_rom(0, 1, 1)).unwrap();
+    cartridge.ppu_write(0x1234, 0x56);
+    assert_eq!(cartridge.ppu_read(0x1234), 0x00);
+}
@@ -0,0 +1,74 @@
+use std::cell::Cell;
+
+#[derive(Clone, Copy)]
+pub enum Button {
+    A = 0,
+    B = 1,
+    Select = 2,
+    Start = 3,
+    Up = 4,
+    Down = 5,
+    Left = 6,
+    Right = 7
+}
+
+// Standard NES joypad. Buttons are latched into an 8-bit shift register while the strobe is high and shifted out one bit per read.
+pub struct Controller {
+    buttons : Cell<u8>,
+    shift_register : Cell<u8>,
+    strobe : Cell<bool>
+}
+
+impl Controller {
+    pub fn new() -> Self {
+        Controller { buttons: Cell::new(0), shift_register: Cell::new(0), strobe: Cell::new(false) }
+    }
+
+    pub fn set_button(&self, button : Button, pressed : bool) {
+        let mask = 1 << (button as u8);
+        if pressed {
+            self.buttons.set(self.buttons.get() | mask);
+        }
+        else {
+            self.buttons.set(self.buttons.get() & !mask);
+        }
+    }
+
+    pub fn write_strobe(&self, value : u8) {
+        self.strobe.set(value & 1 == 1);
+        if self.strobe.get() {
+            self.shift_register.set(self.buttons.get());
+        }
+    }
+
+    // Only bit 0 is driven by the joypad, the caller fills in the undriven bits.
+    pub fn read(&self) -> u8 {
+        if self.strobe.get() {
+            return self.buttons.get() & 1;
+        }
+        let bits = self.shift_register.get();
+        self.shift_register.set((bits >> 1) | 0x80); // Official controllers return 1 once all eight buttons have been read.
+        bits & 1
+    }
+}
+
+#[test]
+fn test_controller_shifts_out_buttons_in_order() {
+    let controller = Controller::new();
+    controller.set_button(Button::A, true);
+    controller.set_button(Button::Start, true);
+    controller.write_strobe(1);
+    controller.write_strobe(0);
+    let bits : Vec<u8> = (0..8).map(|_| controller.read()).collect();
+    assert_eq!(bits, vec![1, 0, 0, 1, 0, 0, 0, 0]);
+    assert_eq!(controller.read(), 1);
+}
+
+#[test]
+fn test_controller_strobe_high_returns_a_button() {
+    let controller = Controller::new();
+    controller.set_button(Button::A, true);
+    controller.write_strobe(1);
+    assert_eq!(controller.read(), 1);
+    assert_eq!(controller.read(), 1);
+}
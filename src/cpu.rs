@@ -4,6 +4,31 @@ use std::collections::HashMap;
 
 type OpcodeCallback = fn(&Nes);
 
+pub const NMI_VECTOR: u16 = 0xfffa;
+pub const RESET_VECTOR: u16 = 0xfffc;
+pub const IRQ_VECTOR: u16 = 0xfffe;
+pub const INTERRUPT_CYCLES: u8 = 7;
+
+// Base cycle count of every opcode, not counting page crossing or taken branch penalties.
+const CYCLES: [u8; 256] = [
+    7, 6, 2, 8, 3, 3, 5, 5, 3, 2, 2, 2, 4, 4, 6, 6, // 0x00
+    2, 5, 2, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7, // 0x10
+    6, 6, 2, 8, 3, 3, 5, 5, 4, 2, 2, 2, 4, 4, 6, 6, // 0x20
+    2, 5, 2, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7, // 0x30
+    6, 6, 2, 8, 3, 3, 5, 5, 3, 2, 2, 2, 3, 4, 6, 6, // 0x40
+    2, 5, 2, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7, // 0x50
+    6, 6, 2, 8, 3, 3, 5, 5, 4, 2, 2, 2, 5, 4, 6, 6, // 0x60
+    2, 5, 2, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7, // 0x70
+    2, 6, 2, 6, 3, 3, 3, 3, 2, 2, 2, 2, 4, 4, 4, 4, // 0x80
+    2, 6, 2, 6, 4, 4, 4, 4, 2, 5, 2, 5, 5, 5, 5, 5, // 0x90
+    2, 6, 2, 6, 3, 3, 3, 3, 2, 2, 2, 2, 4, 4, 4, 4, // 0xa0
+    2, 5, 2, 5, 4, 4, 4, 4, 2, 4, 2, 4, 4, 4, 4, 4, // 0xb0
+    2, 6, 2, 8, 3, 3, 5, 5, 2, 2, 2, 2, 4, 4, 6, 6, // 0xc0
+    2, 5, 2, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7, // 0xd0
+    2, 6, 2, 8, 3, 3, 5, 5, 2, 2, 2, 2, 4, 4, 6, 6, // 0xe0
+    2, 5, 2, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7, // 0xf0
+];
+
 pub struct CPU {
     opcodes: HashMap<u8, OpcodeCallback>, // Opcode mapped to function that executes
 }
@@ -166,6 +191,76 @@ impl CPU {
             None
         }
     }
+
+    // Fetches the opcode at the program counter through the bus and runs it, returning the cycles it took.
+    // Returns None for unimplemented opcodes.
+    pub fn step(&self, nes: &Nes) -> Option<u8> {
+        let opcode = nes.fetch_opcode();
+        if let Some(callback) = self.execute(opcode) {
+            callback(nes);
+            Some(CYCLES[opcode as usize])
+        } else {
+            None
+        }
+    }
+}
+
+// Pushes the program counter and status like BRK (without the break flag), sets the interrupt disable flag and jumps through the vector.
+pub fn interrupt(nes: &Nes, vector: u16) {
+    let pc = nes.program_counter.get();
+    let stack_ptr = nes.stack_pointer.get() as u16 + 0x100;
+    nes.write(stack_ptr, (pc & 0xff) as u8);
+    nes.write(stack_ptr + 1, (pc >> 8) as u8);
+    nes.write(stack_ptr + 2, nes.processor_status_flag.get() & !0b10000);
+    nes.stack_pointer
+        .set(nes.stack_pointer.get().wrapping_add(3));
+    nes.processor_status_flag
+        .set(nes.processor_status_flag.get() | 0b100);
+    let low_byte = nes.read(vector) as u16;
+    let high_byte = nes.read(vector + 1) as u16;
+    nes.program_counter.set((high_byte << 8) | low_byte);
+}
+
+#[test]
+fn test_interrupt_pushes_state_and_jumps_through_vector() {
+    let nes = Nes::new();
+    {
+        let mut memory = nes.memory.borrow_mut();
+        memory[0xfffa] = 0x00;
+        memory[0xfffb] = 0x90;
+    }
+    nes.program_counter.set(0x8123);
+    nes.processor_status_flag.set(0b10001);
+    interrupt(&nes, NMI_VECTOR);
+    let memory = nes.memory.borrow();
+    assert_eq!(memory[0x100], 0x23);
+    assert_eq!(memory[0x101], 0x81);
+    assert_eq!(memory[0x102], 0b1);
+    assert_eq!(nes.stack_pointer.get(), 3);
+    assert_eq!(nes.program_counter.get(), 0x9000);
+    assert_ne!(nes.processor_status_flag.get() & 0b100, 0);
+}
+
+#[test]
+fn test_step_fires_execute_and_read_hooks() {
+    use crate::hooks::Access;
+    let nes = Nes::new();
+    let cpu = CPU::new();
+    {
+        let mut memory = nes.memory.borrow_mut();
+        memory[0x10] = 0xa5; // LDA $20
+        memory[0x11] = 0x20;
+        memory[0x20] = 69;
+    }
+    nes.program_counter.set(0x10);
+    nes.hooks.add_watchpoint(Access::Execute, 0x10..=0x10);
+    nes.hooks.add_watchpoint(Access::Read, 0x20..=0x20);
+    assert_eq!(cpu.step(&nes), Some(3));
+    assert_eq!(nes.a.get(), 69);
+    let hits = nes.hooks.take_watch_hits();
+    assert_eq!(hits.len(), 2);
+    assert_eq!(hits[1].program_counter, 0x10);
+    assert_eq!(hits[1].value, 69);
 }
 
 fn update_processor_status_flag(operand: u16, processor_status_flag: &Cell<u8>) {
@@ -193,9 +288,8 @@ fn test_update_processor_status_for_negative() {
 
 //LDa Opcodes
 fn instruction_lda_immediate(nes: &Nes) {
-    let memory = nes.memory.borrow();
     let pc = nes.program_counter.get() as usize;
-    let operand = memory[pc + 1] as u16;
+    let operand = nes.read((pc + 1) as u16) as u16;
     update_processor_status_flag(operand as u16, &nes.processor_status_flag);
     nes.a.set(operand as u8);
     nes.program_counter.set(((pc + 2) as u16) as u16);
@@ -214,11 +308,10 @@ fn test_instruction_lda_immediate() {
 }
 
 fn instruction_lda_zero_page(nes: &Nes) {
-    let memory = nes.memory.borrow();
     let pc = nes.program_counter.get() as usize;
-    let operand = memory[pc + 1] as u16;
+    let operand = nes.read((pc + 1) as u16) as u16;
     update_processor_status_flag(operand as u16, &nes.processor_status_flag);
-    nes.a.set(memory[operand as usize]);
+    nes.a.set(nes.read(operand));
     nes.program_counter.set((pc + 2) as u16);
 }
 
@@ -236,12 +329,11 @@ fn test_instruction_lda_zero_page() {
 }
 
 fn instruction_lda_zero_page_x(nes: &Nes) {
-    let memory = nes.memory.borrow();
     let pc = nes.program_counter.get() as usize;
-    let operand = memory[pc + 1] as u16;
+    let operand = nes.read((pc + 1) as u16) as u16;
     let x = nes.x.get() as u16;
     update_processor_status_flag(operand as u16, &nes.processor_status_flag);
-    nes.a.set(memory[((operand + x) % 256) as usize]);
+    nes.a.set(nes.read((operand + x) % 256));
     nes.program_counter.set((pc + 2) as u16);
 }
 
@@ -260,11 +352,11 @@ fn test_instruction_lda_zero_page_x() {
 }
 
 fn instruction_lda_absolute(nes: &Nes) {
-    let memory = nes.memory.borrow();
     let pc = nes.program_counter.get() as usize;
-    let operand: u16 = ((memory[pc + 2] as u16) << 8) | (memory[pc + 1] as u16); // Convert the two bytes into a 16-bit unsigned integer
+    let operand: u16 =
+        (nes.read((pc + 1) as u16) as u16) | ((nes.read((pc + 2) as u16) as u16) << 8); // Convert the two bytes into a 16-bit unsigned integer
     update_processor_status_flag(operand as u16, &nes.processor_status_flag);
-    nes.a.set(memory[operand as usize]);
+    nes.a.set(nes.read(operand));
     nes.program_counter.set((pc + 3) as u16);
 }
 
@@ -273,21 +365,33 @@ fn test_instruction_lda_absolute() {
     let nes = Nes::new();
     {
         let mut memory = nes.memory.borrow_mut();
-        memory[2] = 8;
-        memory[2048] = 69;
+        memory[2] = 0x60;
+        memory[0x6000] = 69;
     }
     nes.program_counter.set(0);
     instruction_lda_absolute(&nes);
     assert_eq!(nes.a.get(), 69);
 }
 
+#[test]
+fn test_instruction_lda_absolute_open_bus() {
+    let nes = Nes::new();
+    {
+        let mut memory = nes.memory.borrow_mut();
+        memory[2] = 0x50;
+    }
+    nes.program_counter.set(0);
+    instruction_lda_absolute(&nes);
+    assert_eq!(nes.a.get(), 0x50); // Reading $5000 returns the high byte of the operand left on the bus.
+}
+
 fn instruction_lda_absolute_x(nes: &Nes) {
-    let memory = nes.memory.borrow();
     let pc = nes.program_counter.get() as usize;
     let x = nes.x.get() as u16;
-    let operand: u16 = ((memory[pc + 2] as u16) << 8) | (memory[pc + 1] as u16); // Convert the two bytes into a 16-bit unsigned integer
+    let operand: u16 =
+        (nes.read((pc + 1) as u16) as u16) | ((nes.read((pc + 2) as u16) as u16) << 8); // Convert the two bytes into a 16-bit unsigned integer
     update_processor_status_flag(operand as u16, &nes.processor_status_flag);
-    nes.a.set(memory[(operand + x) as usize]);
+    nes.a.set(nes.read(operand + x));
     nes.program_counter.set(((pc + 3) as u16) as u16);
 }
 
@@ -296,8 +400,8 @@ fn test_instruction_lda_absolute_x() {
     let nes = Nes::new();
     {
         let mut memory = nes.memory.borrow_mut();
-        memory[2] = 8;
-        memory[2048 + 255] = 69;
+        memory[2] = 0x60;
+        memory[0x6000 + 255] = 69;
         nes.x.set(255);
     }
     nes.program_counter.set(0);
@@ -306,12 +410,12 @@ fn test_instruction_lda_absolute_x() {
 }
 
 fn instruction_lda_absolute_y(nes: &Nes) {
-    let memory = nes.memory.borrow();
     let pc = nes.program_counter.get() as usize;
     let y = nes.y.get() as u16;
-    let operand: u16 = ((memory[pc + 2] as u16) << 8) | (memory[pc + 1] as u16); // Convert the two bytes into a 16-bit unsigned integer
+    let operand: u16 =
+        (nes.read((pc + 1) as u16) as u16) | ((nes.read((pc + 2) as u16) as u16) << 8); // Convert the two bytes into a 16-bit unsigned integer
     update_processor_status_flag(operand as u16, &nes.processor_status_flag);
-    nes.a.set(memory[(operand + y) as usize]);
+    nes.a.set(nes.read(operand + y));
     nes.program_counter.set(((pc + 3) as u16) as u16);
 }
 
@@ -320,8 +424,8 @@ fn test_instruction_lda_absolute_y() {
     let nes = Nes::new();
     {
         let mut memory = nes.memory.borrow_mut();
-        memory[2] = 8;
-        memory[2048 + 255] = 69;
+        memory[2] = 0x60;
+        memory[0x6000 + 255] = 69;
         nes.y.set(255);
     }
     nes.program_counter.set(0);
@@ -330,14 +434,15 @@ fn test_instruction_lda_absolute_y() {
 }
 
 fn instruction_lda_indirect_x(nes: &Nes) {
-    let memory = nes.memory.borrow();
     let pc = nes.program_counter.get() as usize;
     let x = nes.x.get() as u16;
-    let operand = memory[pc + 1] as u16;
+    let operand = nes.read((pc + 1) as u16) as u16;
     update_processor_status_flag(operand as u16, &nes.processor_status_flag);
     let address = ((operand + x) % 256) as usize;
-    nes.a
-        .set(memory[((memory[address + 1] as usize) << 8) | (memory[address] as usize)] as u8);
+    nes.a.set(nes.read(
+        (((nes.read((address + 1) as u16) as usize) << 8) | (nes.read(address as u16) as usize))
+            as u16,
+    ));
     nes.program_counter.set(((pc + 3) as u16) as u16);
 }
 
@@ -347,8 +452,8 @@ fn test_instruction_lda_indirect_x() {
     {
         let mut memory = nes.memory.borrow_mut();
         memory[1] = 1;
-        memory[256] = 8;
-        memory[2048] = 69;
+        memory[256] = 0x60;
+        memory[0x6000] = 69;
         nes.x.set(254);
     }
     nes.program_counter.set(0);
@@ -357,14 +462,12 @@ fn test_instruction_lda_indirect_x() {
 }
 
 fn instruction_lda_indirect_indexed(nes: &Nes) {
-    let memory = nes.memory.borrow();
     let pc = nes.program_counter.get() as usize;
     let y = nes.y.get() as usize;
-    let operand = memory[pc + 1] as u16;
+    let operand = nes.read((pc + 1) as u16) as u16;
     update_processor_status_flag(operand as u16, &nes.processor_status_flag);
-    let address =
-        ((memory[(operand + 1) as usize] as usize) << 8) | (memory[operand as usize] as usize);
-    nes.a.set(memory[(address + y) as usize] as u8);
+    let address = ((nes.read(operand + 1) as usize) << 8) | (nes.read(operand) as usize);
+    nes.a.set(nes.read((address + y) as u16) as u8);
     nes.program_counter.set(((pc + 3) as u16) as u16);
 }
 
@@ -374,8 +477,8 @@ fn test_instruction_lda_indirect_indexed() {
     {
         let mut memory = nes.memory.borrow_mut();
         memory[1] = 253;
-        memory[254] = 8;
-        memory[2048 + 254] = 69;
+        memory[254] = 0x60;
+        memory[0x6000 + 254] = 69;
         nes.y.set(254);
     }
     nes.program_counter.set(0);
@@ -385,9 +488,8 @@ fn test_instruction_lda_indirect_indexed() {
 
 // LDY Opcodes
 fn instruction_ldy_immediate(nes: &Nes) {
-    let memory = nes.memory.borrow();
     let pc = nes.program_counter.get() as usize;
-    let operand = memory[pc + 1] as u16;
+    let operand = nes.read((pc + 1) as u16) as u16;
     update_processor_status_flag(operand as u16, &nes.processor_status_flag);
     nes.y.set(operand as u8);
     nes.program_counter.set(((pc + 2) as u16) as u16);
@@ -406,11 +508,10 @@ fn test_instruction_ldy_immediate() {
 }
 
 fn instruction_ldy_zero_page(nes: &Nes) {
-    let memory = nes.memory.borrow();
     let pc = nes.program_counter.get() as usize;
-    let operand = memory[pc + 1] as u16;
+    let operand = nes.read((pc + 1) as u16) as u16;
     update_processor_status_flag(operand as u16, &nes.processor_status_flag);
-    nes.y.set(memory[operand as usize]);
+    nes.y.set(nes.read(operand));
     nes.program_counter.set((pc + 2) as u16);
 }
 
@@ -428,12 +529,11 @@ fn test_instruction_ldy_zero_page() {
 }
 
 fn instruction_ldy_zero_page_x(nes: &Nes) {
-    let memory = nes.memory.borrow();
     let pc = nes.program_counter.get() as usize;
-    let operand = memory[pc + 1] as u16;
+    let operand = nes.read((pc + 1) as u16) as u16;
     let x = nes.x.get() as u16;
     update_processor_status_flag(operand as u16, &nes.processor_status_flag);
-    nes.y.set(memory[((operand + x) % 256) as usize]);
+    nes.y.set(nes.read((operand + x) % 256));
     nes.program_counter.set((pc + 2) as u16);
 }
 
@@ -452,11 +552,11 @@ fn test_instruction_ldy_zero_page_x() {
 }
 
 fn instruction_ldy_absolute(nes: &Nes) {
-    let memory = nes.memory.borrow();
     let pc = nes.program_counter.get() as usize;
-    let operand: u16 = ((memory[pc + 2] as u16) << 8) | (memory[pc + 1] as u16); // Convert the two bytes into a 16-bit unsigned integer
+    let operand: u16 =
+        (nes.read((pc + 1) as u16) as u16) | ((nes.read((pc + 2) as u16) as u16) << 8); // Convert the two bytes into a 16-bit unsigned integer
     update_processor_status_flag(operand as u16, &nes.processor_status_flag);
-    nes.y.set(memory[operand as usize]);
+    nes.y.set(nes.read(operand));
     nes.program_counter.set((pc + 3) as u16);
 }
 
@@ -465,8 +565,8 @@ fn test_instruction_ldy_absolute() {
     let nes = Nes::new();
     {
         let mut memory = nes.memory.borrow_mut();
-        memory[2] = 8;
-        memory[2048] = 69;
+        memory[2] = 0x60;
+        memory[0x6000] = 69;
     }
     nes.program_counter.set(0);
     instruction_ldy_absolute(&nes);
@@ -474,12 +574,12 @@ fn test_instruction_ldy_absolute() {
 }
 
 fn instruction_ldy_absolute_x(nes: &Nes) {
-    let memory = nes.memory.borrow();
     let pc = nes.program_counter.get() as usize;
     let x = nes.x.get() as u16;
-    let operand: u16 = ((memory[pc + 2] as u16) << 8) | (memory[pc + 1] as u16); // Convert the two bytes into a 16-bit unsigned integer
+    let operand: u16 =
+        (nes.read((pc + 1) as u16) as u16) | ((nes.read((pc + 2) as u16) as u16) << 8); // Convert the two bytes into a 16-bit unsigned integer
     update_processor_status_flag(operand as u16, &nes.processor_status_flag);
-    nes.y.set(memory[(operand + x) as usize]);
+    nes.y.set(nes.read(operand + x));
     nes.program_counter.set(((pc + 3) as u16) as u16);
 }
 
@@ -488,8 +588,8 @@ fn test_instruction_ldy_absolute_x() {
     let nes = Nes::new();
     {
         let mut memory = nes.memory.borrow_mut();
-        memory[2] = 8;
-        memory[2048 + 255] = 69;
+        memory[2] = 0x60;
+        memory[0x6000 + 255] = 69;
         nes.x.set(255);
     }
     nes.program_counter.set(0);
@@ -499,9 +599,8 @@ fn test_instruction_ldy_absolute_x() {
 
 // LDX Opcodes
 fn instruction_ldx_immediate(nes: &Nes) {
-    let memory = nes.memory.borrow();
     let pc = nes.program_counter.get() as usize;
-    let operand = memory[pc + 1] as u16;
+    let operand = nes.read((pc + 1) as u16) as u16;
     update_processor_status_flag(operand as u16, &nes.processor_status_flag);
     nes.x.set(operand as u8);
     nes.program_counter.set(((pc + 2) as u16) as u16);
@@ -520,11 +619,10 @@ fn test_instruction_ldx_immediate() {
 }
 
 fn instruction_ldx_zero_page(nes: &Nes) {
-    let memory = nes.memory.borrow();
     let pc = nes.program_counter.get() as usize;
-    let operand = memory[pc + 1] as u16;
+    let operand = nes.read((pc + 1) as u16) as u16;
     update_processor_status_flag(operand as u16, &nes.processor_status_flag);
-    nes.x.set(memory[operand as usize]);
+    nes.x.set(nes.read(operand));
     nes.program_counter.set((pc + 2) as u16);
 }
 
@@ -542,12 +640,11 @@ fn test_instruction_ldx_zero_page() {
 }
 
 fn instruction_ldx_zero_page_x(nes: &Nes) {
-    let memory = nes.memory.borrow();
     let pc = nes.program_counter.get() as usize;
-    let operand = memory[pc + 1] as u16;
+    let operand = nes.read((pc + 1) as u16) as u16;
     let x = nes.x.get() as u16;
     update_processor_status_flag(operand as u16, &nes.processor_status_flag);
-    nes.x.set(memory[((operand + x) % 256) as usize]);
+    nes.x.set(nes.read((operand + x) % 256));
     nes.program_counter.set((pc + 2) as u16);
 }
 
@@ -566,11 +663,11 @@ fn test_instruction_ldx_zero_page_x() {
 }
 
 fn instruction_ldx_absolute(nes: &Nes) {
-    let memory = nes.memory.borrow();
     let pc = nes.program_counter.get() as usize;
-    let operand: u16 = ((memory[pc + 2] as u16) << 8) | (memory[pc + 1] as u16); // Convert the two bytes into a 16-bit unsigned integer
+    let operand: u16 =
+        (nes.read((pc + 1) as u16) as u16) | ((nes.read((pc + 2) as u16) as u16) << 8); // Convert the two bytes into a 16-bit unsigned integer
     update_processor_status_flag(operand as u16, &nes.processor_status_flag);
-    nes.x.set(memory[operand as usize]);
+    nes.x.set(nes.read(operand));
     nes.program_counter.set((pc + 3) as u16);
 }
 
@@ -579,8 +676,8 @@ fn test_instruction_ldx_absolute() {
     let nes = Nes::new();
     {
         let mut memory = nes.memory.borrow_mut();
-        memory[2] = 8;
-        memory[2048] = 69;
+        memory[2] = 0x60;
+        memory[0x6000] = 69;
     }
     nes.program_counter.set(0);
     instruction_ldx_absolute(&nes);
@@ -588,12 +685,12 @@ fn test_instruction_ldx_absolute() {
 }
 
 fn instruction_ldx_absolute_x(nes: &Nes) {
-    let memory = nes.memory.borrow();
     let pc = nes.program_counter.get() as usize;
     let x = nes.x.get() as u16;
-    let operand: u16 = ((memory[pc + 2] as u16) << 8) | (memory[pc + 1] as u16); // Convert the two bytes into a 16-bit unsigned integer
+    let operand: u16 =
+        (nes.read((pc + 1) as u16) as u16) | ((nes.read((pc + 2) as u16) as u16) << 8); // Convert the two bytes into a 16-bit unsigned integer
     update_processor_status_flag(operand as u16, &nes.processor_status_flag);
-    nes.x.set(memory[(operand + x) as usize]);
+    nes.x.set(nes.read(operand + x));
     nes.program_counter.set(((pc + 3) as u16) as u16);
 }
 
@@ -602,8 +699,8 @@ fn test_instruction_ldx_absolute_x() {
     let nes = Nes::new();
     {
         let mut memory = nes.memory.borrow_mut();
-        memory[2] = 8;
-        memory[2048 + 255] = 69;
+        memory[2] = 0x60;
+        memory[0x6000 + 255] = 69;
         nes.x.set(255);
     }
     nes.program_counter.set(0);
@@ -613,11 +710,10 @@ fn test_instruction_ldx_absolute_x() {
 
 //STa Opcodes
 fn instruction_sta_zero_page(nes: &Nes) {
-    let mut memory = nes.memory.borrow_mut();
     let pc = nes.program_counter.get() as usize;
-    let operand = memory[pc + 1] as u16;
+    let operand = nes.read((pc + 1) as u16) as u16;
     update_processor_status_flag(operand as u16, &nes.processor_status_flag);
-    memory[operand as usize] = nes.a.get();
+    nes.write(operand, nes.a.get());
     nes.program_counter.set(((pc + 2) as u16) as u16);
 }
 
@@ -635,12 +731,11 @@ fn test_instruction_sta_zero_page() {
 }
 
 fn instruction_sta_zero_page_x(nes: &Nes) {
-    let mut memory = nes.memory.borrow_mut();
     let pc = nes.program_counter.get() as usize;
-    let operand = memory[pc + 1] as u16;
+    let operand = nes.read((pc + 1) as u16) as u16;
     let x = nes.x.get() as u16;
     update_processor_status_flag(operand as u16, &nes.processor_status_flag);
-    memory[((operand + x) % 256) as usize] = nes.a.get();
+    nes.write((operand + x) % 256, nes.a.get());
     nes.program_counter.set(((pc + 2) as u16) as u16);
 }
 
@@ -659,11 +754,10 @@ fn test_instruction_sta_zero_page_x() {
 }
 
 fn instruction_sta_absolute(nes: &Nes) {
-    let mut memory = nes.memory.borrow_mut();
     let pc = nes.program_counter.get() as usize;
-    let operand = ((memory[pc + 2] as u16) << 8) | (memory[pc + 1] as u16);
+    let operand = (nes.read((pc + 1) as u16) as u16) | ((nes.read((pc + 2) as u16) as u16) << 8);
     update_processor_status_flag(operand as u16, &nes.processor_status_flag);
-    memory[operand as usize] = nes.a.get();
+    nes.write(operand, nes.a.get());
     nes.program_counter.set(((pc + 3) as u16) as u16);
 }
 
@@ -672,21 +766,20 @@ fn test_instruction_sta_absolute() {
     let nes = Nes::new();
     {
         let mut memory = nes.memory.borrow_mut();
-        memory[2] = 8;
+        memory[2] = 0x60;
     }
     nes.a.set(69);
     instruction_sta_absolute(&nes);
     let memory = nes.memory.borrow();
-    assert_eq!(memory[2048], 69);
+    assert_eq!(memory[0x6000], 69);
 }
 
 fn instruction_sta_absolute_x(nes: &Nes) {
-    let mut memory = nes.memory.borrow_mut();
     let pc = nes.program_counter.get() as usize;
-    let operand = ((memory[pc + 2] as u16) << 8) | (memory[pc + 1] as u16);
+    let operand = (nes.read((pc + 1) as u16) as u16) | ((nes.read((pc + 2) as u16) as u16) << 8);
     update_processor_status_flag(operand as u16, &nes.processor_status_flag);
     let x = nes.x.get() as usize;
-    memory[(operand as usize) + x] = nes.a.get();
+    nes.write(((operand as usize) + x) as u16, nes.a.get());
     nes.program_counter.set(((pc + 3) as u16) as u16);
 }
 
@@ -695,22 +788,21 @@ fn test_instruction_sta_absolute_x() {
     let nes = Nes::new();
     {
         let mut memory = nes.memory.borrow_mut();
-        memory[2] = 8;
+        memory[2] = 0x60;
     }
     nes.a.set(69);
     nes.x.set(10);
     instruction_sta_absolute_x(&nes);
     let memory = nes.memory.borrow();
-    assert_eq!(memory[2058], 69);
+    assert_eq!(memory[0x600a], 69);
 }
 
 fn instruction_sta_absolute_y(nes: &Nes) {
-    let mut memory = nes.memory.borrow_mut();
     let pc = nes.program_counter.get() as usize;
-    let operand = ((memory[pc + 2] as u16) << 8) | (memory[pc + 1] as u16);
+    let operand = (nes.read((pc + 1) as u16) as u16) | ((nes.read((pc + 2) as u16) as u16) << 8);
     update_processor_status_flag(operand as u16, &nes.processor_status_flag);
     let y = nes.y.get() as usize;
-    memory[(operand as usize) + y] = nes.a.get();
+    nes.write(((operand as usize) + y) as u16, nes.a.get());
     nes.program_counter.set(((pc + 3) as u16) as u16);
 }
 
@@ -719,26 +811,25 @@ fn test_instruction_sta_absolute_y() {
     let nes = Nes::new();
     {
         let mut memory = nes.memory.borrow_mut();
-        memory[2] = 8;
+        memory[2] = 0x60;
     }
     nes.a.set(69);
     nes.y.set(10);
     instruction_sta_absolute_y(&nes);
     let memory = nes.memory.borrow();
-    assert_eq!(memory[2058], 69);
+    assert_eq!(memory[0x600a], 69);
 }
 
 fn instruction_sta_indirect_x(nes: &Nes) {
-    let mut memory = nes.memory.borrow_mut();
     let pc = nes.program_counter.get() as usize;
     let x = nes.x.get() as u16;
-    let operand = memory[pc + 1] as u16;
+    let operand = nes.read((pc + 1) as u16) as u16;
     update_processor_status_flag(operand as u16, &nes.processor_status_flag);
     let address = ((operand + x) % 256) as usize;
-    let upper = memory[address + 1] as usize;
-    let lower = memory[address] as usize;
+    let upper = nes.read((address + 1) as u16) as usize;
+    let lower = nes.read(address as u16) as usize;
     let indirect_address = (upper << 8) | lower;
-    memory[indirect_address] = nes.a.get();
+    nes.write(indirect_address as u16, nes.a.get());
     nes.program_counter.set(((pc + 3) as u16) as u16);
 }
 
@@ -748,25 +839,22 @@ fn test_instruction_sta_indirect_x() {
     {
         let mut memory = nes.memory.borrow_mut();
         memory[1] = 1;
-        memory[256] = 8;
+        memory[256] = 0x60;
         nes.x.set(254);
     }
     nes.a.set(69);
     instruction_sta_indirect_x(&nes);
     let memory = nes.memory.borrow();
-    assert_eq!(memory[2048], 69);
+    assert_eq!(memory[0x6000], 69);
 }
 
 fn instruction_sta_indirect_indexed(nes: &Nes) {
-    let mut memory = nes.memory.borrow_mut();
     let pc = nes.program_counter.get() as usize;
     let y = nes.y.get() as usize;
-    let operand = memory[pc + 1] as u16;
+    let operand = nes.read((pc + 1) as u16) as u16;
     update_processor_status_flag(operand as u16, &nes.processor_status_flag);
-    let address = (((memory[(operand + 1) as usize] as usize) << 8)
-        | (memory[operand as usize] as usize))
-        + y;
-    memory[address] = nes.a.get() as u8;
+    let address = (((nes.read(operand + 1) as usize) << 8) | (nes.read(operand) as usize)) + y;
+    nes.write(address as u16, nes.a.get() as u8);
     nes.program_counter.set(((pc + 3) as u16) as u16);
 }
 
@@ -776,22 +864,21 @@ fn test_instruction_sta_indirect_indexed() {
     {
         let mut memory = nes.memory.borrow_mut();
         memory[1] = 253;
-        memory[254] = 8;
+        memory[254] = 0x60;
         nes.y.set(254);
     }
     nes.a.set(69);
     instruction_sta_indirect_indexed(&nes);
     let memory = nes.memory.borrow();
-    assert_eq!(memory[2048 + 254], 69);
+    assert_eq!(memory[0x6000 + 254], 69);
 }
 
 //STX Opcodes
 fn instruction_stx_zero_page(nes: &Nes) {
-    let mut memory = nes.memory.borrow_mut();
     let pc = nes.program_counter.get() as usize;
-    let operand = memory[pc + 1] as u16;
+    let operand = nes.read((pc + 1) as u16) as u16;
     update_processor_status_flag(operand as u16, &nes.processor_status_flag);
-    memory[operand as usize] = nes.x.get();
+    nes.write(operand, nes.x.get());
     nes.program_counter.set(((pc + 2) as u16) as u16);
 }
 
@@ -809,11 +896,10 @@ fn test_instruction_stx_zero_page() {
 }
 
 fn instruction_stx_absolute(nes: &Nes) {
-    let mut memory = nes.memory.borrow_mut();
     let pc = nes.program_counter.get() as usize;
-    let operand = ((memory[pc + 2] as u16) << 8) | (memory[pc + 1] as u16);
+    let operand = (nes.read((pc + 1) as u16) as u16) | ((nes.read((pc + 2) as u16) as u16) << 8);
     update_processor_status_flag(operand as u16, &nes.processor_status_flag);
-    memory[operand as usize] = nes.x.get();
+    nes.write(operand, nes.x.get());
     nes.program_counter.set(((pc + 3) as u16) as u16);
 }
 
@@ -822,21 +908,20 @@ fn test_instruction_stx_absolute() {
     let nes = Nes::new();
     {
         let mut memory = nes.memory.borrow_mut();
-        memory[2] = 8;
+        memory[2] = 0x60;
     }
     nes.x.set(69);
     instruction_stx_absolute(&nes);
     let memory = nes.memory.borrow();
-    assert_eq!(memory[2048], 69);
+    assert_eq!(memory[0x6000], 69);
 }
 
 fn instruction_stx_zero_page_y(nes: &Nes) {
-    let mut memory = nes.memory.borrow_mut();
     let pc = nes.program_counter.get() as usize;
-    let operand = memory[pc + 1] as u16;
+    let operand = nes.read((pc + 1) as u16) as u16;
     let y = nes.y.get() as u16;
     update_processor_status_flag(operand as u16, &nes.processor_status_flag);
-    memory[((operand + y) % 256) as usize] = nes.x.get();
+    nes.write((operand + y) % 256, nes.x.get());
     nes.program_counter.set(((pc + 2) as u16) as u16);
 }
 
@@ -856,11 +941,10 @@ fn test_instruction_stx_zero_page_y() {
 
 // STY Opcodes
 fn instruction_sty_zero_page(nes: &Nes) {
-    let mut memory = nes.memory.borrow_mut();
     let pc = nes.program_counter.get() as usize;
-    let operand = memory[pc + 1] as u16;
+    let operand = nes.read((pc + 1) as u16) as u16;
     update_processor_status_flag(operand as u16, &nes.processor_status_flag);
-    memory[operand as usize] = nes.y.get();
+    nes.write(operand, nes.y.get());
     nes.program_counter.set(((pc + 2) as u16) as u16);
 }
 
@@ -878,11 +962,10 @@ fn test_instruction_sty_zero_page() {
 }
 
 fn instruction_sty_absolute(nes: &Nes) {
-    let mut memory = nes.memory.borrow_mut();
     let pc = nes.program_counter.get() as usize;
-    let operand = ((memory[pc + 2] as u16) << 8) | (memory[pc + 1] as u16);
+    let operand = (nes.read((pc + 1) as u16) as u16) | ((nes.read((pc + 2) as u16) as u16) << 8);
     update_processor_status_flag(operand as u16, &nes.processor_status_flag);
-    memory[operand as usize] = nes.y.get();
+    nes.write(operand, nes.y.get());
     nes.program_counter.set(((pc + 3) as u16) as u16);
 }
 
@@ -891,21 +974,20 @@ fn test_instruction_sty_absolute() {
     let nes = Nes::new();
     {
         let mut memory = nes.memory.borrow_mut();
-        memory[2] = 8;
+        memory[2] = 0x60;
     }
     nes.y.set(69);
     instruction_sty_absolute(&nes);
     let memory = nes.memory.borrow();
-    assert_eq!(memory[2048], 69);
+    assert_eq!(memory[0x6000], 69);
 }
 
 fn instruction_sty_zero_page_x(nes: &Nes) {
-    let mut memory = nes.memory.borrow_mut();
     let pc = nes.program_counter.get() as usize;
-    let operand = memory[pc + 1] as u16;
+    let operand = nes.read((pc + 1) as u16) as u16;
     let x = nes.x.get() as u16;
     update_processor_status_flag(operand as u16, &nes.processor_status_flag);
-    memory[((operand + x) % 256) as usize] = nes.y.get();
+    nes.write((operand + x) % 256, nes.y.get());
     nes.program_counter.set(((pc + 2) as u16) as u16);
 }
 
@@ -1140,9 +1222,8 @@ fn test_instruction_sei() {
 
 fn instruction_jmp_absolute(nes: &Nes) {
     let pc = nes.program_counter.get() as usize;
-    let memory = nes.memory.borrow();
-    let high_byte = memory[pc + 2] as u16;
-    let target = (high_byte << 8) | (memory[pc + 1] as u16);
+    let high_byte = nes.read((pc + 2) as u16) as u16;
+    let target = (high_byte << 8) | (nes.read((pc + 1) as u16) as u16);
     nes.program_counter.set(target);
 }
 
@@ -1159,11 +1240,10 @@ fn test_instruction_jmp_absolute() {
 
 fn instruction_jmp_indirect(nes: &Nes) {
     let pc = nes.program_counter.get() as usize;
-    let memory = nes.memory.borrow();
-    let high_byte = memory[pc + 2] as usize;
-    let address = (high_byte << 8) | (memory[pc + 1] as usize);
-    let high_byte = memory[address + 1] as u16;
-    let target = (high_byte << 8) | (memory[address] as u16);
+    let high_byte = nes.read((pc + 2) as u16) as usize;
+    let address = (high_byte << 8) | (nes.read((pc + 1) as u16) as usize);
+    let high_byte = nes.read((address + 1) as u16) as u16;
+    let target = (high_byte << 8) | (nes.read(address as u16) as u16);
     nes.program_counter.set(target);
 }
 
@@ -1172,8 +1252,8 @@ fn test_instruction_jmp_indirect() {
     let nes = Nes::new();
     {
         let mut memory = nes.memory.borrow_mut();
-        memory[2] = 8;
-        memory[2049] = 8;
+        memory[2] = 0x60;
+        memory[0x6001] = 8;
     }
     instruction_jmp_indirect(&nes);
     assert_eq!(nes.program_counter.get(), 2048);
@@ -1182,12 +1262,11 @@ fn test_instruction_jmp_indirect() {
 // INC Opcodes
 
 fn instruction_inc_zeropage(nes: &Nes) {
-    let mut memory = nes.memory.borrow_mut();
     let pc = nes.program_counter.get() as usize;
-    let operand = memory[pc + 1] as usize;
+    let operand = nes.read((pc + 1) as u16) as usize;
     update_processor_status_flag(operand as u16, &nes.processor_status_flag);
-    let value = (memory[operand] as u16) + 1;
-    memory[operand] = (value % 256) as u8;
+    let value = (nes.read(operand as u16) as u16) + 1;
+    nes.write(operand as u16, (value % 256) as u8);
     nes.program_counter.set((pc + 2) as u16);
 }
 
@@ -1204,13 +1283,12 @@ fn test_instruction_inc_zeropage() {
 }
 
 fn instruction_inc_zeropage_x(nes: &Nes) {
-    let mut memory = nes.memory.borrow_mut();
     let pc = nes.program_counter.get() as usize;
-    let operand = memory[pc + 1] as usize;
+    let operand = nes.read((pc + 1) as u16) as usize;
     let x = nes.x.get() as usize;
     update_processor_status_flag(operand as u16, &nes.processor_status_flag);
-    let value = (memory[(operand + x) % 256] as u16) + 1;
-    memory[(operand + x) % 256] = (value % 256) as u8;
+    let value = (nes.read(((operand + x) % 256) as u16) as u16) + 1;
+    nes.write(((operand + x) % 256) as u16, (value % 256) as u8);
     nes.program_counter.set((pc + 2) as u16);
 }
 
@@ -1228,12 +1306,12 @@ fn test_instruction_inc_zeropage_x() {
 }
 
 fn instruction_inc_absolute(nes: &Nes) {
-    let mut memory = nes.memory.borrow_mut();
     let pc = nes.program_counter.get() as usize;
-    let operand = ((memory[pc + 2] as usize) << 8) | (memory[pc + 1] as usize);
+    let operand =
+        (nes.read((pc + 1) as u16) as usize) | ((nes.read((pc + 2) as u16) as usize) << 8);
     update_processor_status_flag(operand as u16, &nes.processor_status_flag);
-    let value = (memory[operand] as u16) + 1;
-    memory[operand] = (value % 256) as u8;
+    let value = (nes.read(operand as u16) as u16) + 1;
+    nes.write(operand as u16, (value % 256) as u8);
     nes.program_counter.set((pc + 2) as u16);
 }
 
@@ -1242,21 +1320,21 @@ fn test_instruction_inc_absolute() {
     let nes = Nes::new();
     {
         let mut memory = nes.memory.borrow_mut();
-        memory[2] = 8;
+        memory[2] = 0x60;
     }
     instruction_inc_absolute(&nes);
     let memory = nes.memory.borrow();
-    assert_eq!(memory[2048], 1);
+    assert_eq!(memory[0x6000], 1);
 }
 
 fn instruction_inc_absolute_x(nes: &Nes) {
-    let mut memory = nes.memory.borrow_mut();
     let pc = nes.program_counter.get() as usize;
-    let operand = ((memory[pc + 2] as usize) << 8) | (memory[pc + 1] as usize);
+    let operand =
+        (nes.read((pc + 1) as u16) as usize) | ((nes.read((pc + 2) as u16) as usize) << 8);
     let x = nes.x.get() as usize;
     update_processor_status_flag(operand as u16, &nes.processor_status_flag);
-    let value = (memory[operand + x] as u16) + 1;
-    memory[operand + x] = (value % 256) as u8;
+    let value = (nes.read((operand + x) as u16) as u16) + 1;
+    nes.write((operand + x) as u16, (value % 256) as u8);
     nes.program_counter.set((pc + 2) as u16);
 }
 
@@ -1265,12 +1343,12 @@ fn test_instruction_inc_absolute_x() {
     let nes = Nes::new();
     {
         let mut memory = nes.memory.borrow_mut();
-        memory[2] = 8;
+        memory[2] = 0x60;
     }
     nes.x.set(1);
     instruction_inc_absolute_x(&nes);
     let memory = nes.memory.borrow();
-    assert_eq!(memory[2049], 1);
+    assert_eq!(memory[0x6001], 1);
 }
 
 // INX and INY Opcodes
@@ -1306,12 +1384,11 @@ fn test_instruction_iny() {
 fn instruction_jsr(nes: &Nes) {
     let pc = nes.program_counter.get();
     let sp = (nes.stack_pointer.get() as usize) + 0x100;
-    let mut memory = nes.memory.borrow_mut();
-    memory[sp] = ((pc - 1) & 0xff) as u8; // Push the two bytes of the current addres minus 1
-    memory[sp + 1] = ((pc - 1) >> 8 & 0xff) as u8;
+    nes.write(sp as u16, ((pc - 1) & 0xff) as u8); // Push the two bytes of the current addres minus 1
+    nes.write((sp + 1) as u16, ((pc - 1) >> 8 & 0xff) as u8);
     nes.stack_pointer.set((sp + 2) as u8);
-    let high_byte = memory[(pc as usize) + 2] as u16;
-    let target = (high_byte << 8) | (memory[(pc + 1) as usize] as u16); // Get the
+    let high_byte = nes.read(((pc as usize) + 2) as u16) as u16;
+    let target = (high_byte << 8) | (nes.read(pc + 1) as u16); // Get the
     nes.program_counter.set(target);
 }
 
@@ -1335,9 +1412,8 @@ fn test_instruction_jsr() {
 
 fn instruction_rts(nes: &Nes) {
     let sp = (nes.stack_pointer.get() - 2) as usize;
-    let memory = nes.memory.borrow();
-    let high_byte = memory[sp + 0x101] as u16;
-    let return_address = (high_byte << 8) | (memory[sp + 0x100] as u16);
+    let high_byte = nes.read((sp + 0x101) as u16) as u16;
+    let return_address = (high_byte << 8) | (nes.read((sp + 0x100) as u16) as u16);
     nes.stack_pointer.set(sp as u8);
     nes.program_counter.set(return_address);
 }
@@ -1358,8 +1434,7 @@ fn test_instruction_rts() {
 // Add With Carry Opcodes
 fn instruction_adc_immediate(nes: &Nes) {
     let pc = nes.program_counter.get() as usize;
-    let memory = nes.memory.borrow();
-    let operand = memory[pc + 1] as u16;
+    let operand = nes.read((pc + 1) as u16) as u16;
     update_processor_status_flag(operand as u16, &nes.processor_status_flag);
     let a = nes.a.get() as u16;
     let carry = if (nes.processor_status_flag.get() & 1) == 1 {
@@ -1403,8 +1478,7 @@ fn test_instruction_adc_immediate() {
 
 fn instruction_adc_zeropage(nes: &Nes) {
     let pc = nes.program_counter.get() as usize;
-    let memory = nes.memory.borrow();
-    let operand = memory[memory[pc + 1] as usize] as u16;
+    let operand = nes.read(nes.read((pc + 1) as u16) as u16) as u16;
     update_processor_status_flag(operand as u16, &nes.processor_status_flag);
     let a = nes.a.get() as u16;
     let carry = if (nes.processor_status_flag.get() & 1) == 1 {
@@ -1450,10 +1524,9 @@ fn test_instruction_adc_zeropage() {
 
 fn instruction_adc_zeropage_x(nes: &Nes) {
     let pc = nes.program_counter.get() as usize;
-    let memory = nes.memory.borrow();
     let x = nes.x.get() as usize;
-    let address = ((memory[pc + 1] as usize) + x) % 256;
-    let operand = memory[address] as u16;
+    let address = ((nes.read((pc + 1) as u16) as usize) + x) % 256;
+    let operand = nes.read(address as u16) as u16;
     update_processor_status_flag(operand as u16, &nes.processor_status_flag);
     let a = nes.a.get() as u16;
     let carry = if (nes.processor_status_flag.get() & 1) == 1 {
@@ -1500,10 +1573,9 @@ fn test_instruction_adc_zeropage_x() {
 
 fn instruction_adc_absolute(nes: &Nes) {
     let pc = nes.program_counter.get() as usize;
-    let memory = nes.memory.borrow();
-    let high_byte = memory[pc + 2] as usize;
-    let address = (high_byte << 8) | (memory[pc + 1] as usize);
-    let operand = memory[address] as u16;
+    let high_byte = nes.read((pc + 2) as u16) as usize;
+    let address = (high_byte << 8) | (nes.read((pc + 1) as u16) as usize);
+    let operand = nes.read(address as u16) as u16;
     update_processor_status_flag(operand as u16, &nes.processor_status_flag);
     let a = nes.a.get() as u16;
     let carry = if (nes.processor_status_flag.get() & 1) == 1 {
@@ -1534,8 +1606,8 @@ fn test_instruction_adc_absolute() {
     let nes = Nes::new();
     {
         let mut memory = nes.memory.borrow_mut();
-        memory[2] = 8;
-        memory[2048] = 64;
+        memory[2] = 0x60;
+        memory[0x6000] = 64;
         memory[4] = 8;
         memory[8] = 128;
     }
@@ -1548,11 +1620,10 @@ fn test_instruction_adc_absolute() {
 
 fn instruction_adc_absolute_x(nes: &Nes) {
     let pc = nes.program_counter.get() as usize;
-    let memory = nes.memory.borrow();
-    let high_byte = memory[pc + 2] as usize;
+    let high_byte = nes.read((pc + 2) as u16) as usize;
     let x = nes.x.get() as usize;
-    let address = ((high_byte << 8) | (memory[pc + 1] as usize)) + x;
-    let operand = memory[address] as u16;
+    let address = ((high_byte << 8) | (nes.read((pc + 1) as u16) as usize)) + x;
+    let operand = nes.read(address as u16) as u16;
     update_processor_status_flag(operand as u16, &nes.processor_status_flag);
     let a = nes.a.get() as u16;
     let carry = if (nes.processor_status_flag.get() & 1) == 1 {
@@ -1599,13 +1670,12 @@ fn test_instruction_adc_absolute_x() {
 
 fn instruction_adc_index_indirect(nes: &Nes) {
     let pc = nes.program_counter.get() as usize;
-    let memory = nes.memory.borrow();
     let x = nes.x.get() as usize;
-    let zero_address = memory[pc + 1] as usize;
+    let zero_address = nes.read((pc + 1) as u16) as usize;
     let address = ((zero_address + x) % 256) as usize;
-    let low_byte = memory[address] as usize;
-    let high_byte = memory[address + 1] as usize;
-    let operand = memory[(high_byte << 8) | low_byte] as u16;
+    let low_byte = nes.read(address as u16) as usize;
+    let high_byte = nes.read((address + 1) as u16) as usize;
+    let operand = nes.read(((high_byte << 8) | low_byte) as u16) as u16;
     update_processor_status_flag(operand as u16, &nes.processor_status_flag);
     let a = nes.a.get() as u16;
     let carry = if (nes.processor_status_flag.get() & 1) == 1 {
@@ -1654,12 +1724,11 @@ fn test_instruction_adc_index_indirect() {
 
 fn instruction_adc_indirect_indexed(nes: &Nes) {
     let pc = nes.program_counter.get() as usize;
-    let memory = nes.memory.borrow();
     let y = nes.y.get() as usize;
-    let zero_address = memory[pc + 1] as usize;
-    let low_byte = memory[zero_address] as usize;
-    let high_byte = memory[zero_address + 1] as usize;
-    let operand = memory[((high_byte << 8) | low_byte) + y] as u16;
+    let zero_address = nes.read((pc + 1) as u16) as usize;
+    let low_byte = nes.read(zero_address as u16) as usize;
+    let high_byte = nes.read((zero_address + 1) as u16) as usize;
+    let operand = nes.read((((high_byte << 8) | low_byte) + y) as u16) as u16;
     update_processor_status_flag(operand as u16, &nes.processor_status_flag);
     let a = nes.a.get() as u16;
     let carry = if (nes.processor_status_flag.get() & 1) == 1 {
@@ -1710,8 +1779,7 @@ fn test_instruction_adc_indirect_indexed() {
 // Subtact with carry operands
 fn instruction_sbc_immediate(nes: &Nes) {
     let pc = nes.program_counter.get() as usize;
-    let memory = nes.memory.borrow();
-    let operand = !memory[pc + 1] as u16;
+    let operand = !nes.read((pc + 1) as u16) as u16;
     update_processor_status_flag(operand as u16, &nes.processor_status_flag);
     let a = nes.a.get() as u16;
     let carry = if (nes.processor_status_flag.get() & 1) == 1 {
@@ -1754,8 +1822,7 @@ fn test_instruction_sbc_immediate() {
 
 fn instruction_sbc_zeropage(nes: &Nes) {
     let pc = nes.program_counter.get() as usize;
-    let memory = nes.memory.borrow();
-    let operand = !memory[memory[pc + 1] as usize] as u16;
+    let operand = !nes.read(nes.read((pc + 1) as u16) as u16) as u16;
     update_processor_status_flag(operand as u16, &nes.processor_status_flag);
     let a = nes.a.get() as u16;
     let carry = if (nes.processor_status_flag.get() & 1) == 1 {
@@ -1800,10 +1867,9 @@ fn test_instruction_sbc_zeropage() {
 
 fn instruction_sbc_zeropage_x(nes: &Nes) {
     let pc = nes.program_counter.get() as usize;
-    let memory = nes.memory.borrow();
     let x = nes.x.get() as usize;
-    let address = ((memory[pc + 1] as usize) + x) % 256;
-    let operand = !memory[address] as u16;
+    let address = ((nes.read((pc + 1) as u16) as usize) + x) % 256;
+    let operand = !nes.read(address as u16) as u16;
     update_processor_status_flag(operand as u16, &nes.processor_status_flag);
     let a = nes.a.get() as u16;
     let carry = if (nes.processor_status_flag.get() & 1) == 1 {
@@ -1849,10 +1915,9 @@ fn test_instruction_sbc_zeropage_x() {
 
 fn instruction_sbc_absolute(nes: &Nes) {
     let pc = nes.program_counter.get() as usize;
-    let memory = nes.memory.borrow();
-    let high_byte = memory[pc + 2] as usize;
-    let address = (high_byte << 8) | (memory[pc + 1] as usize);
-    let operand = !memory[address] as u16;
+    let high_byte = nes.read((pc + 2) as u16) as usize;
+    let address = (high_byte << 8) | (nes.read((pc + 1) as u16) as usize);
+    let operand = !nes.read(address as u16) as u16;
     update_processor_status_flag(operand as u16, &nes.processor_status_flag);
     let a = nes.a.get() as u16;
     let carry = if (nes.processor_status_flag.get() & 1) == 1 {
@@ -1883,8 +1948,8 @@ fn test_instruction_sbc_absolute() {
     let nes = Nes::new();
     {
         let mut memory = nes.memory.borrow_mut();
-        memory[2] = 8;
-        memory[2048] = 64;
+        memory[2] = 0x60;
+        memory[0x6000] = 64;
         memory[4] = 8;
         memory[8] = 128;
     }
@@ -1898,11 +1963,10 @@ fn test_instruction_sbc_absolute() {
 
 fn instruction_sbc_absolute_x(nes: &Nes) {
     let pc = nes.program_counter.get() as usize;
-    let memory = nes.memory.borrow();
-    let high_byte = memory[pc + 2] as usize;
+    let high_byte = nes.read((pc + 2) as u16) as usize;
     let x = nes.x.get() as usize;
-    let address = ((high_byte << 8) | (memory[pc + 1] as usize)) + x;
-    let operand = !memory[address] as u16;
+    let address = ((high_byte << 8) | (nes.read((pc + 1) as u16) as usize)) + x;
+    let operand = !nes.read(address as u16) as u16;
     update_processor_status_flag(operand as u16, &nes.processor_status_flag);
     let a = nes.a.get() as u16;
     let carry = if (nes.processor_status_flag.get() & 1) == 1 {
@@ -1948,13 +2012,12 @@ fn test_instruction_sbc_absolute_x() {
 
 fn instruction_sbc_index_indirect(nes: &Nes) {
     let pc = nes.program_counter.get() as usize;
-    let memory = nes.memory.borrow();
     let x = nes.x.get() as usize;
-    let zero_address = memory[pc + 1] as usize;
+    let zero_address = nes.read((pc + 1) as u16) as usize;
     let address = ((zero_address + x) % 256) as usize;
-    let low_byte = memory[address] as usize;
-    let high_byte = memory[address + 1] as usize;
-    let operand = !memory[(high_byte << 8) | low_byte] as u16;
+    let low_byte = nes.read(address as u16) as usize;
+    let high_byte = nes.read((address + 1) as u16) as usize;
+    let operand = !nes.read(((high_byte << 8) | low_byte) as u16) as u16;
     update_processor_status_flag(operand as u16, &nes.processor_status_flag);
     let a = nes.a.get() as u16;
     let carry = if (nes.processor_status_flag.get() & 1) == 1 {
@@ -2002,12 +2065,11 @@ fn test_instruction_sbc_index_indirect() {
 
 fn instruction_sbc_indirect_indexed(nes: &Nes) {
     let pc = nes.program_counter.get() as usize;
-    let memory = nes.memory.borrow();
     let y = nes.y.get() as usize;
-    let zero_address = memory[pc + 1] as usize;
-    let low_byte = memory[zero_address] as usize;
-    let high_byte = memory[zero_address + 1] as usize;
-    let operand = !memory[((high_byte << 8) | low_byte) + y] as u16;
+    let zero_address = nes.read((pc + 1) as u16) as usize;
+    let low_byte = nes.read(zero_address as u16) as usize;
+    let high_byte = nes.read((zero_address + 1) as u16) as usize;
+    let operand = !nes.read((((high_byte << 8) | low_byte) + y) as u16) as u16;
     update_processor_status_flag(operand as u16, &nes.processor_status_flag);
     let a = nes.a.get() as u16;
     let carry = if (nes.processor_status_flag.get() & 1) == 1 {
@@ -2069,8 +2131,10 @@ fn test_instruction_nop() {
 // Stack opcodes
 
 fn instruction_pha(nes: &Nes) {
-    let mut memory = nes.memory.borrow_mut();
-    memory[nes.stack_pointer.get() as usize + 0x100] = nes.a.get();
+    nes.write(
+        (nes.stack_pointer.get() as usize + 0x100) as u16,
+        nes.a.get(),
+    );
     nes.stack_pointer.set(nes.stack_pointer.get() + 1);
     nes.program_counter.set(nes.program_counter.get() + 1);
 }
@@ -2086,8 +2150,10 @@ fn test_instruction_pha() {
 }
 
 fn instruction_php(nes: &Nes) {
-    let mut memory = nes.memory.borrow_mut();
-    memory[nes.stack_pointer.get() as usize + 0x100] = nes.processor_status_flag.get();
+    nes.write(
+        (nes.stack_pointer.get() as usize + 0x100) as u16,
+        nes.processor_status_flag.get(),
+    );
     nes.stack_pointer.set(nes.stack_pointer.get() + 1);
     nes.program_counter.set(nes.program_counter.get() + 1);
 }
@@ -2102,8 +2168,8 @@ fn test_instruction_php() {
 }
 
 fn instruction_pla(nes: &Nes) {
-    let memory = nes.memory.borrow_mut();
-    nes.a.set(memory[nes.stack_pointer.get() as usize + 0x100]);
+    nes.a
+        .set(nes.read((nes.stack_pointer.get() as usize + 0x100) as u16));
     update_processor_status_flag(nes.a.get() as u16, &nes.processor_status_flag);
     nes.stack_pointer.set(nes.stack_pointer.get() - 1);
     nes.program_counter.set(nes.program_counter.get() + 1);
@@ -2122,9 +2188,8 @@ fn test_instruction_pla() {
 }
 
 fn instruction_plp(nes: &Nes) {
-    let memory = nes.memory.borrow();
     nes.processor_status_flag
-        .set(memory[nes.stack_pointer.get() as usize + 0x100]);
+        .set(nes.read((nes.stack_pointer.get() as usize + 0x100) as u16));
     nes.stack_pointer.set(nes.stack_pointer.get() - 1);
     nes.program_counter.set(nes.program_counter.get() + 1);
 }
@@ -2145,9 +2210,8 @@ fn test_instruction_plp() {
 
 fn instruction_and_immediate(nes: &Nes) {
     let pc = nes.program_counter.get() as usize;
-    let memory = nes.memory.borrow();
     let a = nes.a.get() as u16;
-    let operand = memory[pc + 1] as u16;
+    let operand = nes.read((pc + 1) as u16) as u16;
     let result = a & operand;
     nes.a.set(result as u8);
     update_processor_status_flag(result as u16, &nes.processor_status_flag);
@@ -2169,8 +2233,7 @@ fn test_instruction_and_immediate() {
 
 fn instruction_and_zeropage(nes: &Nes) {
     let pc = nes.program_counter.get() as usize;
-    let memory = nes.memory.borrow();
-    let operand = memory[memory[pc + 1] as usize] as u16;
+    let operand = nes.read(nes.read((pc + 1) as u16) as u16) as u16;
     let a = nes.a.get() as u16;
     let result = a & operand;
     nes.a.set(result as u8);
@@ -2193,10 +2256,9 @@ fn test_instruction_and_zeropage() {
 
 fn instruction_and_zeropage_x(nes: &Nes) {
     let pc = nes.program_counter.get() as usize;
-    let memory = nes.memory.borrow();
     let x = nes.x.get() as usize;
-    let address = ((memory[pc + 1] as usize) + x) % 256;
-    let operand = memory[address] as u16;
+    let address = ((nes.read((pc + 1) as u16) as usize) + x) % 256;
+    let operand = nes.read(address as u16) as u16;
     let a = nes.a.get() as u16;
     let result = a & operand;
     nes.a.set(result as u8);
@@ -2220,10 +2282,9 @@ fn test_instruction_and_zeropage_x() {
 
 fn instruction_and_absolute(nes: &Nes) {
     let pc = nes.program_counter.get() as usize;
-    let memory = nes.memory.borrow();
-    let high_byte = memory[pc + 2] as usize;
-    let address = (high_byte << 8) | (memory[pc + 1] as usize);
-    let operand = memory[address] as u16;
+    let high_byte = nes.read((pc + 2) as u16) as usize;
+    let address = (high_byte << 8) | (nes.read((pc + 1) as u16) as usize);
+    let operand = nes.read(address as u16) as u16;
     let a = nes.a.get() as u16;
     let result = a & operand;
     nes.a.set(result as u8);
@@ -2246,11 +2307,10 @@ fn test_instruction_and_absolute() {
 
 fn instruction_and_absolute_x(nes: &Nes) {
     let pc = nes.program_counter.get() as usize;
-    let memory = nes.memory.borrow();
-    let high_byte = memory[pc + 2] as usize;
+    let high_byte = nes.read((pc + 2) as u16) as usize;
     let x = nes.x.get() as usize;
-    let address = ((high_byte << 8) | (memory[pc + 1] as usize)) + x;
-    let operand = memory[address] as u16;
+    let address = ((high_byte << 8) | (nes.read((pc + 1) as u16) as usize)) + x;
+    let operand = nes.read(address as u16) as u16;
     let a = nes.a.get() as u16;
     let result = a & operand;
     nes.a.set(result as u8);
@@ -2276,13 +2336,12 @@ fn test_instruction_and_absolute_x() {
 
 fn instruction_and_index_indirect(nes: &Nes) {
     let pc = nes.program_counter.get() as usize;
-    let memory = nes.memory.borrow();
     let x = nes.x.get() as usize;
-    let zero_address = memory[pc + 1] as usize;
+    let zero_address = nes.read((pc + 1) as u16) as usize;
     let address = ((zero_address + x) % 256) as usize;
-    let low_byte = memory[address] as usize;
-    let high_byte = memory[address + 1] as usize;
-    let operand = memory[(high_byte << 8) | low_byte] as u16;
+    let low_byte = nes.read(address as u16) as usize;
+    let high_byte = nes.read((address + 1) as u16) as usize;
+    let operand = nes.read(((high_byte << 8) | low_byte) as u16) as u16;
     let a = nes.a.get() as u16;
     let result = a & operand;
     nes.a.set(result as u8);
@@ -2307,12 +2366,11 @@ fn test_instruction_and_index_indirect() {
 
 fn instruction_and_indirect_indexed(nes: &Nes) {
     let pc = nes.program_counter.get() as usize;
-    let memory = nes.memory.borrow();
     let y = nes.y.get() as usize;
-    let zero_address = memory[pc + 1] as usize;
-    let low_byte = memory[zero_address] as usize;
-    let high_byte = memory[zero_address + 1] as usize;
-    let operand = memory[((high_byte << 8) | low_byte) + y] as u16;
+    let zero_address = nes.read((pc + 1) as u16) as usize;
+    let low_byte = nes.read(zero_address as u16) as usize;
+    let high_byte = nes.read((zero_address + 1) as u16) as usize;
+    let operand = nes.read((((high_byte << 8) | low_byte) + y) as u16) as u16;
     let a = nes.a.get() as u16;
     let result = a & operand;
     nes.a.set(result as u8);
@@ -2339,9 +2397,8 @@ fn test_instruction_and_indirect_indexed() {
 
 fn instruction_eor_immediate(nes: &Nes) {
     let pc = nes.program_counter.get() as usize;
-    let memory = nes.memory.borrow();
     let a = nes.a.get() as u16;
-    let operand = memory[pc + 1] as u16;
+    let operand = nes.read((pc + 1) as u16) as u16;
     let result = a ^ operand;
     nes.a.set(result as u8);
     update_processor_status_flag(result as u16, &nes.processor_status_flag);
@@ -2362,8 +2419,7 @@ fn test_instruction_eor_immediate() {
 
 fn instruction_eor_zeropage(nes: &Nes) {
     let pc = nes.program_counter.get() as usize;
-    let memory = nes.memory.borrow();
-    let operand = memory[memory[pc + 1] as usize] as u16;
+    let operand = nes.read(nes.read((pc + 1) as u16) as u16) as u16;
     let a = nes.a.get() as u16;
     let result = a ^ operand;
     nes.a.set(result as u8);
@@ -2386,10 +2442,9 @@ fn test_instruction_eor_zeropage() {
 
 fn instruction_eor_zeropage_x(nes: &Nes) {
     let pc = nes.program_counter.get() as usize;
-    let memory = nes.memory.borrow();
     let x = nes.x.get() as usize;
-    let address = ((memory[pc + 1] as usize) + x) % 256;
-    let operand = memory[address] as u16;
+    let address = ((nes.read((pc + 1) as u16) as usize) + x) % 256;
+    let operand = nes.read(address as u16) as u16;
     let a = nes.a.get() as u16;
     let result = a ^ operand;
     nes.a.set(result as u8);
@@ -2413,10 +2468,9 @@ fn test_instruction_eor_zeropage_x() {
 
 fn instruction_eor_absolute(nes: &Nes) {
     let pc = nes.program_counter.get() as usize;
-    let memory = nes.memory.borrow();
-    let high_byte = memory[pc + 2] as usize;
-    let address = (high_byte << 8) | (memory[pc + 1] as usize);
-    let operand = memory[address] as u16;
+    let high_byte = nes.read((pc + 2) as u16) as usize;
+    let address = (high_byte << 8) | (nes.read((pc + 1) as u16) as usize);
+    let operand = nes.read(address as u16) as u16;
     let a = nes.a.get() as u16;
     let result = a ^ operand;
     nes.a.set(result as u8);
@@ -2429,8 +2483,8 @@ fn test_instruction_eor_absolute() {
     let nes = Nes::new();
     {
         let mut memory = nes.memory.borrow_mut();
-        memory[2] = 8;
-        memory[2048] = 64;
+        memory[2] = 0x60;
+        memory[0x6000] = 64;
     }
     nes.a.set(64);
     instruction_eor_absolute(&nes);
@@ -2439,11 +2493,10 @@ fn test_instruction_eor_absolute() {
 
 fn instruction_eor_absolute_x(nes: &Nes) {
     let pc = nes.program_counter.get() as usize;
-    let memory = nes.memory.borrow();
-    let high_byte = memory[pc + 2] as usize;
+    let high_byte = nes.read((pc + 2) as u16) as usize;
     let x = nes.x.get() as usize;
-    let address = ((high_byte << 8) | (memory[pc + 1] as usize)) + x;
-    let operand = memory[address] as u16;
+    let address = ((high_byte << 8) | (nes.read((pc + 1) as u16) as usize)) + x;
+    let operand = nes.read(address as u16) as u16;
     let a = nes.a.get() as u16;
     let result = a ^ operand;
     nes.a.set(result as u8);
@@ -2467,13 +2520,12 @@ fn test_instruction_eor_absolute_x() {
 
 fn instruction_eor_index_indirect(nes: &Nes) {
     let pc = nes.program_counter.get() as usize;
-    let memory = nes.memory.borrow();
     let x = nes.x.get() as usize;
-    let zero_address = memory[pc + 1] as usize;
+    let zero_address = nes.read((pc + 1) as u16) as usize;
     let address = ((zero_address + x) % 256) as usize;
-    let low_byte = memory[address] as usize;
-    let high_byte = memory[address + 1] as usize;
-    let operand = memory[(high_byte << 8) | low_byte] as u16;
+    let low_byte = nes.read(address as u16) as usize;
+    let high_byte = nes.read((address + 1) as u16) as usize;
+    let operand = nes.read(((high_byte << 8) | low_byte) as u16) as u16;
     let a = nes.a.get() as u16;
     let result = a ^ operand;
     nes.a.set(result as u8);
@@ -2498,12 +2550,11 @@ fn test_instruction_eor_index_indirect() {
 
 fn instruction_eor_indirect_indexed(nes: &Nes) {
     let pc = nes.program_counter.get() as usize;
-    let memory = nes.memory.borrow();
     let y = nes.y.get() as usize;
-    let zero_address = memory[pc + 1] as usize;
-    let low_byte = memory[zero_address] as usize;
-    let high_byte = memory[zero_address + 1] as usize;
-    let operand = memory[((high_byte << 8) | low_byte) + y] as u16;
+    let zero_address = nes.read((pc + 1) as u16) as usize;
+    let low_byte = nes.read(zero_address as u16) as usize;
+    let high_byte = nes.read((zero_address + 1) as u16) as usize;
+    let operand = nes.read((((high_byte << 8) | low_byte) + y) as u16) as u16;
     let a = nes.a.get() as u16;
     let result = a ^ operand;
     nes.a.set(result as u8);
@@ -2530,9 +2581,8 @@ fn test_instruction_eor_indirect_indexed() {
 
 fn instruction_ora_immediate(nes: &Nes) {
     let pc = nes.program_counter.get() as usize;
-    let memory = nes.memory.borrow();
     let a = nes.a.get() as u16;
-    let operand = memory[pc + 1] as u16;
+    let operand = nes.read((pc + 1) as u16) as u16;
     let result = a | operand;
     nes.a.set(result as u8);
     update_processor_status_flag(result as u16, &nes.processor_status_flag);
@@ -2553,8 +2603,7 @@ fn test_instruction_ora_immediate() {
 
 fn instruction_ora_zeropage(nes: &Nes) {
     let pc = nes.program_counter.get() as usize;
-    let memory = nes.memory.borrow();
-    let operand = memory[memory[pc + 1] as usize] as u16;
+    let operand = nes.read(nes.read((pc + 1) as u16) as u16) as u16;
     let a = nes.a.get() as u16;
     let result = a | operand;
     nes.a.set(result as u8);
@@ -2577,10 +2626,9 @@ fn test_instruction_ora_zeropage() {
 
 fn instruction_ora_zeropage_x(nes: &Nes) {
     let pc = nes.program_counter.get() as usize;
-    let memory = nes.memory.borrow();
     let x = nes.x.get() as usize;
-    let address = ((memory[pc + 1] as usize) + x) % 256;
-    let operand = memory[address] as u16;
+    let address = ((nes.read((pc + 1) as u16) as usize) + x) % 256;
+    let operand = nes.read(address as u16) as u16;
     let a = nes.a.get() as u16;
     let result = a | operand;
     nes.a.set(result as u8);
@@ -2604,10 +2652,9 @@ fn test_instruction_ora_zeropage_x() {
 
 fn instruction_ora_absolute(nes: &Nes) {
     let pc = nes.program_counter.get() as usize;
-    let memory = nes.memory.borrow();
-    let high_byte = memory[pc + 2] as usize;
-    let address = (high_byte << 8) | (memory[pc + 1] as usize);
-    let operand = memory[address] as u16;
+    let high_byte = nes.read((pc + 2) as u16) as usize;
+    let address = (high_byte << 8) | (nes.read((pc + 1) as u16) as usize);
+    let operand = nes.read(address as u16) as u16;
     let a = nes.a.get() as u16;
     let result = a | operand;
     nes.a.set(result as u8);
@@ -2630,11 +2677,10 @@ fn test_instruction_ora_absolute() {
 
 fn instruction_ora_absolute_x(nes: &Nes) {
     let pc = nes.program_counter.get() as usize;
-    let memory = nes.memory.borrow();
-    let high_byte = memory[pc + 2] as usize;
+    let high_byte = nes.read((pc + 2) as u16) as usize;
     let x = nes.x.get() as usize;
-    let address = ((high_byte << 8) | (memory[pc + 1] as usize)) + x;
-    let operand = memory[address] as u16;
+    let address = ((high_byte << 8) | (nes.read((pc + 1) as u16) as usize)) + x;
+    let operand = nes.read(address as u16) as u16;
     let a = nes.a.get() as u16;
     let result = a | operand;
     nes.a.set(result as u8);
@@ -2658,13 +2704,12 @@ fn test_instruction_ora_absolute_x() {
 
 fn instruction_ora_index_indirect(nes: &Nes) {
     let pc = nes.program_counter.get() as usize;
-    let memory = nes.memory.borrow();
     let x = nes.x.get() as usize;
-    let zero_address = memory[pc + 1] as usize;
+    let zero_address = nes.read((pc + 1) as u16) as usize;
     let address = ((zero_address + x) % 256) as usize;
-    let low_byte = memory[address] as usize;
-    let high_byte = memory[address + 1] as usize;
-    let operand = memory[(high_byte << 8) | low_byte] as u16;
+    let low_byte = nes.read(address as u16) as usize;
+    let high_byte = nes.read((address + 1) as u16) as usize;
+    let operand = nes.read(((high_byte << 8) | low_byte) as u16) as u16;
     let a = nes.a.get() as u16;
     let result = a | operand;
     nes.a.set(result as u8);
@@ -2689,12 +2734,11 @@ fn test_instruction_ora_index_indirect() {
 
 fn instruction_ora_indirect_indexed(nes: &Nes) {
     let pc = nes.program_counter.get() as usize;
-    let memory = nes.memory.borrow();
     let y = nes.y.get() as usize;
-    let zero_address = memory[pc + 1] as usize;
-    let low_byte = memory[zero_address] as usize;
-    let high_byte = memory[zero_address + 1] as usize;
-    let operand = memory[((high_byte << 8) | low_byte) + y] as u16;
+    let zero_address = nes.read((pc + 1) as u16) as usize;
+    let low_byte = nes.read(zero_address as u16) as usize;
+    let high_byte = nes.read((zero_address + 1) as u16) as usize;
+    let operand = nes.read((((high_byte << 8) | low_byte) + y) as u16) as u16;
     let a = nes.a.get() as u16;
     let result = a | operand;
     nes.a.set(result as u8);
@@ -2721,8 +2765,7 @@ fn test_instruction_ora_indirect_indexed() {
 
 fn instruction_bcc(nes: &Nes) {
     let pc = nes.program_counter.get() as usize;
-    let memory = nes.memory.borrow();
-    let offset = (pc + (memory[pc + 1] as usize)) as u16;
+    let offset = (pc + (nes.read((pc + 1) as u16) as usize)) as u16;
     let status = nes.processor_status_flag.get();
     if (status & 1) == 0 {
         nes.program_counter.set(offset);
@@ -2744,8 +2787,7 @@ fn test_instruction_bcc() {
 
 fn instruction_bcs(nes: &Nes) {
     let pc = nes.program_counter.get() as usize;
-    let memory = nes.memory.borrow();
-    let offset = (pc + (memory[pc + 1] as usize)) as u16;
+    let offset = (pc + (nes.read((pc + 1) as u16) as usize)) as u16;
     let status = nes.processor_status_flag.get();
     if (status & 1) == 1 {
         nes.program_counter.set(offset);
@@ -2769,8 +2811,7 @@ fn test_instruction_bcs() {
 
 fn instruction_beq(nes: &Nes) {
     let pc = nes.program_counter.get() as usize;
-    let memory = nes.memory.borrow();
-    let offset = (pc + (memory[pc + 1] as usize)) as u16;
+    let offset = (pc + (nes.read((pc + 1) as u16) as usize)) as u16;
     let status = nes.processor_status_flag.get();
     if (status & 2) == 0 {
         nes.program_counter.set(offset);
@@ -2792,9 +2833,8 @@ fn test_instruction_beq() {
 
 fn instruction_bit_zeropage(nes: &Nes) {
     let pc = nes.program_counter.get() as usize;
-    let memory = nes.memory.borrow();
-    let zero_address = memory[pc + 1] as usize;
-    let operand = memory[zero_address];
+    let zero_address = nes.read((pc + 1) as u16) as usize;
+    let operand = nes.read(zero_address as u16);
     let a = nes.a.get();
     let result = a & operand;
     update_processor_status_flag(result as u16, &nes.processor_status_flag);
@@ -2817,10 +2857,9 @@ fn test_bit_zeropage() {
 
 fn instruction_bit_absolute(nes: &Nes) {
     let pc = nes.program_counter.get() as usize;
-    let memory = nes.memory.borrow();
-    let lowbyte = memory[pc + 1] as usize;
-    let high_byte = memory[pc + 1] as usize;
-    let operand = memory[(high_byte << 8) | lowbyte];
+    let lowbyte = nes.read((pc + 1) as u16) as usize;
+    let high_byte = nes.read((pc + 1) as u16) as usize;
+    let operand = nes.read(((high_byte << 8) | lowbyte) as u16);
     let a = nes.a.get();
     let result = a & operand;
     update_processor_status_flag(result as u16, &nes.processor_status_flag);
@@ -2843,8 +2882,7 @@ fn test_bit_absolute() {
 
 fn instruction_bmi(nes: &Nes) {
     let pc = nes.program_counter.get() as usize;
-    let memory = nes.memory.borrow();
-    let offset = (pc + (memory[pc + 1] as usize)) as u16;
+    let offset = (pc + (nes.read((pc + 1) as u16) as usize)) as u16;
     let status = nes.processor_status_flag.get();
     if (status & 0b10000000) != 0 {
         nes.program_counter.set(offset);
@@ -2868,8 +2906,7 @@ fn test_instruction_bmi() {
 
 fn instruction_bne(nes: &Nes) {
     let pc = nes.program_counter.get() as usize;
-    let memory = nes.memory.borrow();
-    let offset = (pc + (memory[pc + 1] as usize)) as u16;
+    let offset = (pc + (nes.read((pc + 1) as u16) as usize)) as u16;
     let status = nes.processor_status_flag.get();
     if (status & 2) != 0 {
         nes.program_counter.set(offset);
@@ -2892,8 +2929,7 @@ fn test_instruction_bne() {
 
 fn instruction_bpl(nes: &Nes) {
     let pc = nes.program_counter.get() as usize;
-    let memory = nes.memory.borrow();
-    let offset = (pc + (memory[pc + 1] as usize)) as u16;
+    let offset = (pc + (nes.read((pc + 1) as u16) as usize)) as u16;
     let status = nes.processor_status_flag.get();
     if (status & 0b10000000) == 0 {
         nes.program_counter.set(offset);
@@ -2915,8 +2951,7 @@ fn test_instruction_bpl() {
 
 fn instruction_bvc(nes: &Nes) {
     let pc = nes.program_counter.get() as usize;
-    let memory = nes.memory.borrow();
-    let offset = (pc + (memory[pc + 1] as usize)) as u16;
+    let offset = (pc + (nes.read((pc + 1) as u16) as usize)) as u16;
     let status = nes.processor_status_flag.get();
     if (status & 0b01000000) == 0 {
         nes.program_counter.set(offset);
@@ -2938,8 +2973,7 @@ fn test_instruction_bvc() {
 
 fn instruction_bvs(nes: &Nes) {
     let pc = nes.program_counter.get() as usize;
-    let memory = nes.memory.borrow();
-    let offset = (pc + (memory[pc + 1] as usize)) as u16;
+    let offset = (pc + (nes.read((pc + 1) as u16) as usize)) as u16;
     let status = nes.processor_status_flag.get();
     if (status & 0b01000000) != 0 {
         nes.program_counter.set(offset);
@@ -2972,13 +3006,12 @@ fn instruction_brk(nes: &Nes) {
     }
     nes.processor_status_flag
         .set(nes.processor_status_flag.get() | 0b10000); // Set the break flag
-    let mut memory = nes.memory.borrow_mut();
-    memory[stack_ptr + 0x100] = (pc & 0xff) as u8; // Stack starts in 0x100 in RAM
-    memory[stack_ptr + 0x101] = ((pc & 0xff00) >> 8) as u8;
-    memory[stack_ptr + 0x102] = nes.processor_status_flag.get();
+    nes.write((stack_ptr + 0x100) as u16, (pc & 0xff) as u8); // Stack starts in 0x100 in RAM
+    nes.write((stack_ptr + 0x101) as u16, ((pc & 0xff00) >> 8) as u8);
+    nes.write((stack_ptr + 0x102) as u16, nes.processor_status_flag.get());
     nes.stack_pointer.set((stack_ptr + 3) as u8);
-    let low_byte = memory[0xfffe] as u16; // Interrupt address is read starting at 0xfffe
-    let high_byte = memory[0xffff] as u16;
+    let low_byte = nes.read(0xfffe_u16) as u16; // Interrupt address is read starting at 0xfffe
+    let high_byte = nes.read(0xffff_u16) as u16;
     let interrupt_address = (high_byte << 8) | low_byte;
     nes.program_counter.set(interrupt_address);
 }
@@ -3004,10 +3037,10 @@ fn test_instruction_brk() {
 
 fn instruction_rti(nes: &Nes) {
     let stack_pointer = nes.stack_pointer.get() as usize + 0x100;
-    let memory = nes.memory.borrow();
-    nes.processor_status_flag.set(memory[stack_pointer]);
-    let high_byte = memory[stack_pointer - 1] as u16;
-    let low_byte = memory[stack_pointer - 2] as u16;
+    nes.processor_status_flag
+        .set(nes.read(stack_pointer as u16));
+    let high_byte = nes.read((stack_pointer - 1) as u16) as u16;
+    let low_byte = nes.read((stack_pointer - 2) as u16) as u16;
     let return_address = (high_byte << 8) | low_byte;
     nes.stack_pointer.set(((stack_pointer - 0x100) - 3) as u8);
     nes.program_counter.set(return_address);
@@ -3055,10 +3088,9 @@ fn test_instruction_asl_accumulator() {
 }
 
 fn instruction_asl_zeropage(nes: &Nes) {
-    let mut memory = nes.memory.borrow_mut();
     let pc = nes.program_counter.get() as usize;
-    let zero_address = memory[pc + 1] as usize;
-    let operand = memory[zero_address];
+    let zero_address = nes.read((pc + 1) as u16) as usize;
+    let operand = nes.read(zero_address as u16);
     if (operand & 0b10000000) != 0 {
         nes.processor_status_flag
             .set(nes.processor_status_flag.get() | 1);
@@ -3068,7 +3100,7 @@ fn instruction_asl_zeropage(nes: &Nes) {
         nes.processor_status_flag
             .set(nes.processor_status_flag.get() | 0b10000000);
     }
-    memory[zero_address] = result;
+    nes.write(zero_address as u16, result);
     nes.program_counter.set((pc as u16) + 2);
 }
 
@@ -3093,11 +3125,10 @@ fn test_instruction_asl_zeropage() {
 }
 
 fn instruction_asl_zeropage_x(nes: &Nes) {
-    let mut memory = nes.memory.borrow_mut();
     let pc = nes.program_counter.get() as usize;
     let x = nes.x.get() as usize;
-    let zero_address = memory[pc + 1] as usize;
-    let operand = memory[(zero_address + x) % 256];
+    let zero_address = nes.read((pc + 1) as u16) as usize;
+    let operand = nes.read(((zero_address + x) % 256) as u16);
     if (operand & 0b10000000) != 0 {
         nes.processor_status_flag
             .set(nes.processor_status_flag.get() | 1);
@@ -3107,7 +3138,7 @@ fn instruction_asl_zeropage_x(nes: &Nes) {
         nes.processor_status_flag
             .set(nes.processor_status_flag.get() | 0b10000000);
     }
-    memory[(zero_address + x) % 256] = result;
+    nes.write(((zero_address + x) % 256) as u16, result);
     nes.program_counter.set((pc as u16) + 2);
 }
 
@@ -3133,12 +3164,11 @@ fn test_instruction_asl_zeropage_x() {
 }
 
 fn instruction_asl_absolute(nes: &Nes) {
-    let mut memory = nes.memory.borrow_mut();
     let pc = nes.program_counter.get() as usize;
-    let low_byte = memory[pc + 1] as usize;
-    let high_byte = memory[pc + 2] as usize;
+    let low_byte = nes.read((pc + 1) as u16) as usize;
+    let high_byte = nes.read((pc + 2) as u16) as usize;
     let absolute_address = (high_byte << 8) | low_byte;
-    let operand = memory[absolute_address] as usize;
+    let operand = nes.read(absolute_address as u16) as usize;
     if (operand & 0b10000000) != 0 {
         nes.processor_status_flag
             .set(nes.processor_status_flag.get() | 1);
@@ -3148,7 +3178,7 @@ fn instruction_asl_absolute(nes: &Nes) {
         nes.processor_status_flag
             .set(nes.processor_status_flag.get() | 0b10000000);
     }
-    memory[absolute_address] = result as u8;
+    nes.write(absolute_address as u16, result as u8);
     nes.program_counter.set((pc as u16) + 3);
 }
 
@@ -3157,14 +3187,14 @@ fn test_instruction_asl_absolute() {
     let nes = Nes::new();
     {
         let mut memory = nes.memory.borrow_mut();
-        memory[2] = 8;
-        memory[2048] = 1;
+        memory[2] = 0x60;
+        memory[0x6000] = 1;
         memory[4] = 0xff;
         memory[0xff] = 0xff;
     }
     instruction_asl_absolute(&nes);
     let memory = nes.memory.borrow();
-    assert_eq!(memory[2048], 2);
+    assert_eq!(memory[0x6000], 2);
     drop(memory);
     instruction_asl_absolute(&nes);
     let memory = nes.memory.borrow();
@@ -3173,13 +3203,12 @@ fn test_instruction_asl_absolute() {
 }
 
 fn instruction_asl_absolute_x(nes: &Nes) {
-    let mut memory = nes.memory.borrow_mut();
     let pc = nes.program_counter.get() as usize;
     let x = nes.x.get() as usize;
-    let low_byte = memory[pc + 1] as usize;
-    let high_byte = memory[pc + 2] as usize;
+    let low_byte = nes.read((pc + 1) as u16) as usize;
+    let high_byte = nes.read((pc + 2) as u16) as usize;
     let absolute_address = (high_byte << 8) | low_byte;
-    let operand = memory[absolute_address + x] as usize;
+    let operand = nes.read((absolute_address + x) as u16) as usize;
     if (operand & 0b10000000) != 0 {
         nes.processor_status_flag
             .set(nes.processor_status_flag.get() | 1);
@@ -3189,7 +3218,7 @@ fn instruction_asl_absolute_x(nes: &Nes) {
         nes.processor_status_flag
             .set(nes.processor_status_flag.get() | 0b10000000);
     }
-    memory[absolute_address + x] = result as u8;
+    nes.write((absolute_address + x) as u16, result as u8);
     nes.program_counter.set((pc as u16) + 3);
 }
 
@@ -3198,15 +3227,15 @@ fn test_instruction_asl_absolute_x() {
     let nes = Nes::new();
     {
         let mut memory = nes.memory.borrow_mut();
-        memory[2] = 8;
-        memory[2409] = 1;
+        memory[2] = 0x60;
+        memory[0x6169] = 1;
         memory[4] = 0xfe;
         memory[0xff] = 0xff;
     }
     nes.x.set(1);
     instruction_asl_absolute_x(&nes);
     let memory = nes.memory.borrow();
-    assert_eq!(memory[2049], 2);
+    assert_eq!(memory[0x6001], 2);
     drop(memory);
     instruction_asl_absolute_x(&nes);
     let memory = nes.memory.borrow();
@@ -3218,9 +3247,8 @@ fn test_instruction_asl_absolute_x() {
 
 fn instruction_cmp_immediate(nes: &Nes) {
     let pc = nes.program_counter.get() as usize;
-    let memory = nes.memory.borrow();
     let a = nes.a.get();
-    let operand = memory[pc + 1];
+    let operand = nes.read((pc + 1) as u16);
     if a == operand {
         nes.processor_status_flag
             .set(nes.processor_status_flag.get() | 2);
@@ -3251,10 +3279,9 @@ fn test_instruction_cmp_immediate() {
 
 fn instruction_cmp_zeropage(nes: &Nes) {
     let pc = nes.program_counter.get() as usize;
-    let memory = nes.memory.borrow();
     let a = nes.a.get();
-    let zero_address = memory[pc + 1] as usize;
-    let operand = memory[zero_address];
+    let zero_address = nes.read((pc + 1) as u16) as usize;
+    let operand = nes.read(zero_address as u16);
     if a == operand {
         nes.processor_status_flag
             .set(nes.processor_status_flag.get() | 2);
@@ -3286,11 +3313,10 @@ fn test_instruction_cmp_zeropage() {
 
 fn instruction_cmp_zeropage_x(nes: &Nes) {
     let pc = nes.program_counter.get() as usize;
-    let memory = nes.memory.borrow();
     let a = nes.a.get();
     let x = nes.x.get() as usize;
-    let zero_address = memory[pc + 1] as usize;
-    let operand = memory[(zero_address + x) % 256];
+    let zero_address = nes.read((pc + 1) as u16) as usize;
+    let operand = nes.read(((zero_address + x) % 256) as u16);
     if a == operand {
         nes.processor_status_flag
             .set(nes.processor_status_flag.get() | 2);
@@ -3323,12 +3349,11 @@ fn test_instruction_cmp_zeropage_x() {
 
 fn instruction_cmp_absolute(nes: &Nes) {
     let pc = nes.program_counter.get() as usize;
-    let memory = nes.memory.borrow();
     let a = nes.a.get();
-    let low_byte = memory[pc + 1] as usize;
-    let high_byte = memory[pc + 2] as usize;
+    let low_byte = nes.read((pc + 1) as u16) as usize;
+    let high_byte = nes.read((pc + 2) as u16) as usize;
     let absolute_address = (high_byte << 8) | low_byte;
-    let operand = memory[absolute_address];
+    let operand = nes.read(absolute_address as u16);
     if a == operand {
         nes.processor_status_flag
             .set(nes.processor_status_flag.get() | 2);
@@ -3348,8 +3373,8 @@ fn test_instruction_cmp_absolute() {
     let nes = Nes::new();
     {
         let mut memory = nes.memory.borrow_mut();
-        memory[5] = 0x8;
-        memory[0x800] = 1;
+        memory[5] = 0x60;
+        memory[0x6000] = 1;
     }
     instruction_cmp_absolute(&nes);
     assert_eq!(nes.processor_status_flag.get(), 3);
@@ -3360,13 +3385,12 @@ fn test_instruction_cmp_absolute() {
 
 fn instruction_cmp_absolute_x(nes: &Nes) {
     let pc = nes.program_counter.get() as usize;
-    let memory = nes.memory.borrow();
     let a = nes.a.get();
     let x = nes.x.get() as usize;
-    let low_byte = memory[pc + 1] as usize;
-    let high_byte = memory[pc + 2] as usize;
+    let low_byte = nes.read((pc + 1) as u16) as usize;
+    let high_byte = nes.read((pc + 2) as u16) as usize;
     let absolute_address = (high_byte << 8) | low_byte;
-    let operand = memory[absolute_address + x];
+    let operand = nes.read((absolute_address + x) as u16);
     if a == operand {
         nes.processor_status_flag
             .set(nes.processor_status_flag.get() | 2);
@@ -3386,8 +3410,8 @@ fn test_instruction_cmp_absolute_x() {
     let nes = Nes::new();
     {
         let mut memory = nes.memory.borrow_mut();
-        memory[5] = 0x8;
-        memory[0x801] = 1;
+        memory[5] = 0x60;
+        memory[0x6001] = 1;
     }
     nes.x.set(1);
     instruction_cmp_absolute_x(&nes);
@@ -3399,13 +3423,12 @@ fn test_instruction_cmp_absolute_x() {
 
 fn instruction_cmp_absolute_y(nes: &Nes) {
     let pc = nes.program_counter.get() as usize;
-    let memory = nes.memory.borrow();
     let a = nes.a.get();
     let y = nes.y.get() as usize;
-    let low_byte = memory[pc + 1] as usize;
-    let high_byte = memory[pc + 2] as usize;
+    let low_byte = nes.read((pc + 1) as u16) as usize;
+    let high_byte = nes.read((pc + 2) as u16) as usize;
     let absolute_address = (high_byte << 8) | low_byte;
-    let operand = memory[absolute_address + y];
+    let operand = nes.read((absolute_address + y) as u16);
     if a == operand {
         nes.processor_status_flag
             .set(nes.processor_status_flag.get() | 2);
@@ -3425,8 +3448,8 @@ fn test_instruction_cmp_absolute_y() {
     let nes = Nes::new();
     {
         let mut memory = nes.memory.borrow_mut();
-        memory[5] = 0x8;
-        memory[0x801] = 1;
+        memory[5] = 0x60;
+        memory[0x6001] = 1;
     }
     nes.y.set(1);
     instruction_cmp_absolute_y(&nes);
@@ -3438,14 +3461,13 @@ fn test_instruction_cmp_absolute_y() {
 
 fn instruction_cmp_index_indirect(nes: &Nes) {
     let pc = nes.program_counter.get() as usize;
-    let memory = nes.memory.borrow();
     let a = nes.a.get();
     let x = nes.x.get() as usize;
-    let zero_address = (memory[pc + 1] as usize + x) % 256;
-    let low_byte = memory[zero_address] as usize;
-    let high_byte = memory[zero_address + 1] as usize;
+    let zero_address = (nes.read((pc + 1) as u16) as usize + x) % 256;
+    let low_byte = nes.read(zero_address as u16) as usize;
+    let high_byte = nes.read((zero_address + 1) as u16) as usize;
     let indirect_address = (high_byte << 8) | low_byte;
-    let operand = memory[indirect_address];
+    let operand = nes.read(indirect_address as u16);
     if a == operand {
         nes.processor_status_flag
             .set(nes.processor_status_flag.get() | 2);
@@ -3466,8 +3488,8 @@ fn test_instruction_cmp_index_indirect() {
     {
         let mut memory = nes.memory.borrow_mut();
         memory[0x03] = 0x08;
-        memory[0xa] = 0x08;
-        memory[0x800] = 0x01;
+        memory[0xa] = 0x60;
+        memory[0x6000] = 0x01;
     }
     nes.x.set(1);
     instruction_cmp_index_indirect(&nes);
@@ -3481,9 +3503,8 @@ fn test_instruction_cmp_index_indirect() {
 
 fn instruction_cpx_immediate(nes: &Nes) {
     let pc = nes.program_counter.get() as usize;
-    let memory = nes.memory.borrow();
     let x = nes.x.get();
-    let operand = memory[pc + 1];
+    let operand = nes.read((pc + 1) as u16);
     if x == operand {
         nes.processor_status_flag
             .set(nes.processor_status_flag.get() | 2);
@@ -3514,10 +3535,9 @@ fn test_instruction_cpx_immediate() {
 
 fn instruction_cpx_zeropage(nes: &Nes) {
     let pc = nes.program_counter.get() as usize;
-    let memory = nes.memory.borrow();
     let x = nes.x.get();
-    let zero_address = memory[pc + 1] as usize;
-    let operand = memory[zero_address];
+    let zero_address = nes.read((pc + 1) as u16) as usize;
+    let operand = nes.read(zero_address as u16);
     if x == operand {
         nes.processor_status_flag
             .set(nes.processor_status_flag.get() | 2);
@@ -3549,12 +3569,11 @@ fn test_instruction_cpx_zeropage() {
 
 fn instruction_cpx_absolute(nes: &Nes) {
     let pc = nes.program_counter.get() as usize;
-    let memory = nes.memory.borrow();
     let x = nes.x.get();
-    let low_byte = memory[pc + 1] as usize;
-    let high_byte = memory[pc + 2] as usize;
+    let low_byte = nes.read((pc + 1) as u16) as usize;
+    let high_byte = nes.read((pc + 2) as u16) as usize;
     let absolute_address = (high_byte << 8) | low_byte;
-    let operand = memory[absolute_address];
+    let operand = nes.read(absolute_address as u16);
     if x == operand {
         nes.processor_status_flag
             .set(nes.processor_status_flag.get() | 2);
@@ -3574,8 +3593,8 @@ fn test_instruction_cpx_absolute() {
     let nes = Nes::new();
     {
         let mut memory = nes.memory.borrow_mut();
-        memory[5] = 0x8;
-        memory[0x800] = 1;
+        memory[5] = 0x60;
+        memory[0x6000] = 1;
     }
     instruction_cpx_absolute(&nes);
     assert_eq!(nes.processor_status_flag.get(), 3);
@@ -3588,9 +3607,8 @@ fn test_instruction_cpx_absolute() {
 
 fn instruction_cpy_immediate(nes: &Nes) {
     let pc = nes.program_counter.get() as usize;
-    let memory = nes.memory.borrow();
     let y = nes.y.get();
-    let operand = memory[pc + 1];
+    let operand = nes.read((pc + 1) as u16);
     if y == operand {
         nes.processor_status_flag
             .set(nes.processor_status_flag.get() | 2);
@@ -3621,10 +3639,9 @@ fn test_instruction_cpy_immediate() {
 
 fn instruction_cpy_zeropage(nes: &Nes) {
     let pc = nes.program_counter.get() as usize;
-    let memory = nes.memory.borrow();
     let y = nes.y.get();
-    let zero_address = memory[pc + 1] as usize;
-    let operand = memory[zero_address];
+    let zero_address = nes.read((pc + 1) as u16) as usize;
+    let operand = nes.read(zero_address as u16);
     if y == operand {
         nes.processor_status_flag
             .set(nes.processor_status_flag.get() | 2);
@@ -3656,12 +3673,11 @@ fn test_instruction_cpy_zeropage() {
 
 fn instruction_cpy_absolute(nes: &Nes) {
     let pc = nes.program_counter.get() as usize;
-    let memory = nes.memory.borrow();
     let y = nes.y.get();
-    let low_byte = memory[pc + 1] as usize;
-    let high_byte = memory[pc + 2] as usize;
+    let low_byte = nes.read((pc + 1) as u16) as usize;
+    let high_byte = nes.read((pc + 2) as u16) as usize;
     let absolute_address = (high_byte << 8) | low_byte;
-    let operand = memory[absolute_address];
+    let operand = nes.read(absolute_address as u16);
     if y == operand {
         nes.processor_status_flag
             .set(nes.processor_status_flag.get() | 2);
@@ -3681,8 +3697,8 @@ fn test_instruction_cpy_absolute() {
     let nes = Nes::new();
     {
         let mut memory = nes.memory.borrow_mut();
-        memory[5] = 0x8;
-        memory[0x800] = 1;
+        memory[5] = 0x60;
+        memory[0x6000] = 1;
     }
     instruction_cpy_absolute(&nes);
     assert_eq!(nes.processor_status_flag.get(), 3);
@@ -3695,11 +3711,10 @@ fn test_instruction_cpy_absolute() {
 
 fn instruction_dec_zeropage(nes: &Nes) {
     let pc = nes.program_counter.get() as usize;
-    let mut memory = nes.memory.borrow_mut();
-    let zero_address = memory[pc + 1] as usize;
-    let result = (memory[zero_address] as usize) + 0xff;
+    let zero_address = nes.read((pc + 1) as u16) as usize;
+    let result = (nes.read(zero_address as u16) as usize) + 0xff;
     update_processor_status_flag(result as u16, &nes.processor_status_flag);
-    memory[zero_address] = result as u8;
+    nes.write(zero_address as u16, result as u8);
     nes.program_counter.set((pc + 2) as u16);
 }
 
@@ -3725,11 +3740,10 @@ fn test_instruction_dec_zeropage() {
 fn instruction_dec_zeropage_x(nes: &Nes) {
     let pc = nes.program_counter.get() as usize;
     let x = nes.x.get() as usize;
-    let mut memory = nes.memory.borrow_mut();
-    let zero_address = (memory[pc + 1] as usize + x) % 256;
-    let result = (memory[zero_address] as usize) + 0xff;
+    let zero_address = (nes.read((pc + 1) as u16) as usize + x) % 256;
+    let result = (nes.read(zero_address as u16) as usize) + 0xff;
     update_processor_status_flag(result as u16, &nes.processor_status_flag);
-    memory[zero_address] = result as u8;
+    nes.write(zero_address as u16, result as u8);
     nes.program_counter.set((pc + 2) as u16);
 }
 
@@ -3755,13 +3769,12 @@ fn test_instruction_dec_zeropage_x() {
 
 fn instruction_dec_absolute(nes: &Nes) {
     let pc = nes.program_counter.get() as usize;
-    let mut memory = nes.memory.borrow_mut();
-    let low_byte = memory[pc + 1] as usize;
-    let high_byte = memory[pc + 2] as usize;
+    let low_byte = nes.read((pc + 1) as u16) as usize;
+    let high_byte = nes.read((pc + 2) as u16) as usize;
     let absolute_address = (high_byte << 8) | low_byte;
-    let result = (memory[absolute_address] as usize) + 0xff;
+    let result = (nes.read(absolute_address as u16) as usize) + 0xff;
     update_processor_status_flag(result as u16, &nes.processor_status_flag);
-    memory[absolute_address] = result as u8;
+    nes.write(absolute_address as u16, result as u8);
     nes.program_counter.set((pc + 3) as u16);
 }
 
@@ -3770,8 +3783,8 @@ fn test_instruction_dec_absolute() {
     let nes = Nes::new();
     {
         let mut memory = nes.memory.borrow_mut();
-        memory[5] = 8;
-        memory[2048] = 2;
+        memory[5] = 0x60;
+        memory[0x6000] = 2;
     }
     instruction_dec_absolute(&nes);
     let memory = nes.memory.borrow();
@@ -3779,20 +3792,19 @@ fn test_instruction_dec_absolute() {
     drop(memory);
     instruction_dec_absolute(&nes);
     let memory = nes.memory.borrow();
-    assert_eq!(memory[2048], 0x1);
+    assert_eq!(memory[0x6000], 0x1);
     drop(memory);
 }
 
 fn instruction_dec_absolute_x(nes: &Nes) {
     let pc = nes.program_counter.get() as usize;
     let x = nes.x.get() as usize;
-    let mut memory = nes.memory.borrow_mut();
-    let low_byte = memory[pc + 1] as usize;
-    let high_byte = memory[pc + 2] as usize;
+    let low_byte = nes.read((pc + 1) as u16) as usize;
+    let high_byte = nes.read((pc + 2) as u16) as usize;
     let absolute_address = ((high_byte << 8) | low_byte) + x;
-    let result = (memory[absolute_address] as usize) + 0xff;
+    let result = (nes.read(absolute_address as u16) as usize) + 0xff;
     update_processor_status_flag(result as u16, &nes.processor_status_flag);
-    memory[absolute_address] = result as u8;
+    nes.write(absolute_address as u16, result as u8);
     nes.program_counter.set((pc + 3) as u16);
 }
 
@@ -3801,8 +3813,8 @@ fn test_instruction_dec_absolute_x() {
     let nes = Nes::new();
     {
         let mut memory = nes.memory.borrow_mut();
-        memory[5] = 8;
-        memory[2049] = 2;
+        memory[5] = 0x60;
+        memory[0x6001] = 2;
     }
     nes.x.set(1);
     instruction_dec_absolute_x(&nes);
@@ -3811,7 +3823,7 @@ fn test_instruction_dec_absolute_x() {
     drop(memory);
     instruction_dec_absolute_x(&nes);
     let memory = nes.memory.borrow();
-    assert_eq!(memory[2049], 0x1);
+    assert_eq!(memory[0x6001], 0x1);
     drop(memory);
 }
 
@@ -3897,9 +3909,8 @@ fn test_instruction_rol_accumulator() {
 
 fn instruction_rol_zeropage(nes: &Nes) {
     let pc = nes.program_counter.get() as usize;
-    let mut memory = nes.memory.borrow_mut();
-    let zero_address = memory[pc + 1] as usize;
-    let mut operand = memory[zero_address];
+    let zero_address = nes.read((pc + 1) as u16) as usize;
+    let mut operand = nes.read(zero_address as u16);
     if (operand & 128) != 0 {
         nes.processor_status_flag
             .set(nes.processor_status_flag.get() | 1);
@@ -3920,7 +3931,7 @@ fn instruction_rol_zeropage(nes: &Nes) {
         nes.processor_status_flag
             .set(nes.processor_status_flag.get() | 128);
     }
-    memory[zero_address] = result;
+    nes.write(zero_address as u16, result);
     nes.program_counter.set(nes.program_counter.get() + 2);
 }
 
@@ -3955,10 +3966,9 @@ fn test_instruction_rol_zeropage() {
 
 fn instruction_rol_zeropage_x(nes: &Nes) {
     let pc = nes.program_counter.get() as usize;
-    let mut memory = nes.memory.borrow_mut();
     let x = nes.x.get() as usize;
-    let zero_address = memory[pc + 1] as usize;
-    let mut operand = memory[(zero_address + x) % 256];
+    let zero_address = nes.read((pc + 1) as u16) as usize;
+    let mut operand = nes.read(((zero_address + x) % 256) as u16);
     if (operand & 128) != 0 {
         nes.processor_status_flag
             .set(nes.processor_status_flag.get() | 1);
@@ -3978,7 +3988,7 @@ fn instruction_rol_zeropage_x(nes: &Nes) {
         nes.processor_status_flag
             .set(nes.processor_status_flag.get() | 128);
     }
-    memory[(zero_address + x) % 256] = result;
+    nes.write(((zero_address + x) % 256) as u16, result);
     nes.program_counter.set(nes.program_counter.get() + 2);
 }
 
@@ -4014,11 +4024,10 @@ fn test_instruction_rol_zeropage_x() {
 
 fn instruction_rol_absolute(nes: &Nes) {
     let pc = nes.program_counter.get() as usize;
-    let mut memory = nes.memory.borrow_mut();
-    let low_byte = memory[pc + 1] as usize;
-    let high_byte = memory[pc + 2] as usize;
+    let low_byte = nes.read((pc + 1) as u16) as usize;
+    let high_byte = nes.read((pc + 2) as u16) as usize;
     let total_address = (high_byte << 8) | low_byte;
-    let mut operand = memory[total_address];
+    let mut operand = nes.read(total_address as u16);
     if (operand & 128) != 0 {
         nes.processor_status_flag
             .set(nes.processor_status_flag.get() | 1);
@@ -4038,7 +4047,7 @@ fn instruction_rol_absolute(nes: &Nes) {
         nes.processor_status_flag
             .set(nes.processor_status_flag.get() | 128);
     }
-    memory[total_address] = result;
+    nes.write(total_address as u16, result);
     nes.program_counter.set(nes.program_counter.get() + 3);
 }
 
@@ -4047,15 +4056,15 @@ fn test_instruction_rol_absolute() {
     let nes = Nes::new();
     {
         let mut memory = nes.memory.borrow_mut();
-        memory[2] = 8;
-        memory[2048] = 1;
+        memory[2] = 0x60;
+        memory[0x6000] = 1;
         memory[4] = 0xff;
         memory[0xff] = 0xff;
     }
     nes.processor_status_flag.set(0);
     instruction_rol_absolute(&nes);
     let memory = nes.memory.borrow_mut();
-    assert_eq!(memory[2048], 0b00000010);
+    assert_eq!(memory[0x6000], 0b00000010);
     nes.processor_status_flag.set(0);
     drop(memory);
     instruction_rol_absolute(&nes);
@@ -4071,12 +4080,11 @@ fn test_instruction_rol_absolute() {
 
 fn instruction_rol_absolute_x(nes: &Nes) {
     let pc = nes.program_counter.get() as usize;
-    let mut memory = nes.memory.borrow_mut();
     let x = nes.x.get() as usize;
-    let low_byte = memory[pc + 1] as usize;
-    let high_byte = memory[pc + 2] as usize;
+    let low_byte = nes.read((pc + 1) as u16) as usize;
+    let high_byte = nes.read((pc + 2) as u16) as usize;
     let total_address = (high_byte << 8) | low_byte;
-    let mut operand = memory[total_address + x];
+    let mut operand = nes.read((total_address + x) as u16);
     if (operand & 128) != 0 {
         nes.processor_status_flag
             .set(nes.processor_status_flag.get() | 1);
@@ -4096,7 +4104,7 @@ fn instruction_rol_absolute_x(nes: &Nes) {
         nes.processor_status_flag
             .set(nes.processor_status_flag.get() | 128);
     }
-    memory[total_address + x] = result;
+    nes.write((total_address + x) as u16, result);
     nes.program_counter.set(nes.program_counter.get() + 3);
 }
 
@@ -4105,8 +4113,8 @@ fn test_instruction_rol_absolute_x() {
     let nes = Nes::new();
     {
         let mut memory = nes.memory.borrow_mut();
-        memory[2] = 8;
-        memory[2049] = 1;
+        memory[2] = 0x60;
+        memory[0x6001] = 1;
         memory[4] = 0xfe;
         memory[0xff] = 0xff;
     }
@@ -4114,7 +4122,7 @@ fn test_instruction_rol_absolute_x() {
     nes.processor_status_flag.set(0);
     instruction_rol_absolute_x(&nes);
     let memory = nes.memory.borrow_mut();
-    assert_eq!(memory[2049], 0b00000010);
+    assert_eq!(memory[0x6001], 0b00000010);
     nes.processor_status_flag.set(0);
     drop(memory);
     instruction_rol_absolute_x(&nes);
@@ -4176,9 +4184,8 @@ fn test_instruction_ror_accumulator() {
 
 fn instruction_ror_zeropage(nes: &Nes) {
     let pc = nes.program_counter.get() as usize;
-    let mut memory = nes.memory.borrow_mut();
-    let zero_address = memory[pc + 1] as usize;
-    let mut operand = memory[zero_address];
+    let zero_address = nes.read((pc + 1) as u16) as usize;
+    let mut operand = nes.read(zero_address as u16);
     if (nes.processor_status_flag.get() & 1) != 0 {
         operand = operand | 128;
     }
@@ -4199,7 +4206,7 @@ fn instruction_ror_zeropage(nes: &Nes) {
         nes.processor_status_flag
             .set(nes.processor_status_flag.get() | 128);
     }
-    memory[zero_address] = result;
+    nes.write(zero_address as u16, result);
     nes.program_counter.set(nes.program_counter.get() + 2);
 }
 
@@ -4234,10 +4241,9 @@ fn test_instruction_ror_zeropage() {
 
 fn instruction_ror_zeropage_x(nes: &Nes) {
     let pc = nes.program_counter.get() as usize;
-    let mut memory = nes.memory.borrow_mut();
     let x = nes.x.get() as usize;
-    let zero_address = memory[pc + 1] as usize;
-    let mut operand = memory[(zero_address + x) % 256];
+    let zero_address = nes.read((pc + 1) as u16) as usize;
+    let mut operand = nes.read(((zero_address + x) % 256) as u16);
     if (nes.processor_status_flag.get() & 1) != 0 {
         operand = operand | 128;
     }
@@ -4258,7 +4264,7 @@ fn instruction_ror_zeropage_x(nes: &Nes) {
         nes.processor_status_flag
             .set(nes.processor_status_flag.get() | 128);
     }
-    memory[(zero_address + x) % 256] = result;
+    nes.write(((zero_address + x) % 256) as u16, result);
     nes.program_counter.set(nes.program_counter.get() + 2);
 }
 
@@ -4294,11 +4300,10 @@ fn test_instruction_ror_zeropage_x() {
 
 fn instruction_ror_absolute(nes: &Nes) {
     let pc = nes.program_counter.get() as usize;
-    let mut memory = nes.memory.borrow_mut();
-    let low_byte = memory[pc + 1] as usize;
-    let high_byte = memory[pc + 2] as usize;
+    let low_byte = nes.read((pc + 1) as u16) as usize;
+    let high_byte = nes.read((pc + 2) as u16) as usize;
     let total_address = (high_byte << 8) | low_byte;
-    let mut operand = memory[total_address];
+    let mut operand = nes.read(total_address as u16);
     if (nes.processor_status_flag.get() & 1) != 0 {
         operand = operand | 128;
     }
@@ -4319,7 +4324,7 @@ fn instruction_ror_absolute(nes: &Nes) {
         nes.processor_status_flag
             .set(nes.processor_status_flag.get() | 128);
     }
-    memory[total_address] = result;
+    nes.write(total_address as u16, result);
     nes.program_counter.set(nes.program_counter.get() + 3);
 }
 
@@ -4328,15 +4333,15 @@ fn test_instruction_ror_absolute() {
     let nes = Nes::new();
     {
         let mut memory = nes.memory.borrow_mut();
-        memory[2] = 8;
-        memory[2048] = 2;
+        memory[2] = 0x60;
+        memory[0x6000] = 2;
         memory[4] = 0xff;
         memory[0xff] = 0xff;
     }
     nes.processor_status_flag.set(0);
     instruction_ror_absolute(&nes);
     let memory = nes.memory.borrow_mut();
-    assert_eq!(memory[2048], 0b00000001);
+    assert_eq!(memory[0x6000], 0b00000001);
     nes.processor_status_flag.set(0);
     drop(memory);
     instruction_ror_absolute(&nes);
@@ -4352,12 +4357,11 @@ fn test_instruction_ror_absolute() {
 
 fn instruction_ror_absolute_x(nes: &Nes) {
     let pc = nes.program_counter.get() as usize;
-    let mut memory = nes.memory.borrow_mut();
     let x = nes.x.get() as usize;
-    let low_byte = memory[pc + 1] as usize;
-    let high_byte = memory[pc + 2] as usize;
+    let low_byte = nes.read((pc + 1) as u16) as usize;
+    let high_byte = nes.read((pc + 2) as u16) as usize;
     let total_address = (high_byte << 8) | low_byte;
-    let mut operand = memory[total_address + x];
+    let mut operand = nes.read((total_address + x) as u16);
     if (nes.processor_status_flag.get() & 1) != 0 {
         operand = operand | 128;
     }
@@ -4378,7 +4382,7 @@ fn instruction_ror_absolute_x(nes: &Nes) {
         nes.processor_status_flag
             .set(nes.processor_status_flag.get() | 128);
     }
-    memory[total_address + x] = result;
+    nes.write((total_address + x) as u16, result);
     nes.program_counter.set(nes.program_counter.get() + 3);
 }
 
@@ -4387,8 +4391,8 @@ fn test_instruction_ror_absolute_x() {
     let nes = Nes::new();
     {
         let mut memory = nes.memory.borrow_mut();
-        memory[2] = 8;
-        memory[2049] = 2;
+        memory[2] = 0x60;
+        memory[0x6001] = 2;
         memory[4] = 0xfe;
         memory[0xff] = 0xff;
     }
@@ -4396,7 +4400,7 @@ fn test_instruction_ror_absolute_x() {
     nes.processor_status_flag.set(0);
     instruction_ror_absolute_x(&nes);
     let memory = nes.memory.borrow_mut();
-    assert_eq!(memory[2049], 0b00000001);
+    assert_eq!(memory[0x6001], 0b00000001);
     nes.processor_status_flag.set(0);
     drop(memory);
     instruction_ror_absolute_x(&nes);
@@ -0,0 +1,206 @@
+use crate::nes::Nes;
+use crate::region::Region;
+use std::cell::Cell;
+
+// Delta modulation channel at $4010-$4013. It plays 1 bit deltas fetched from $C000-$FFFF by DMA, each bit moving a
+// 7 bit output level up or down by 2, and can loop the sample or raise an IRQ when it ends.
+
+pub const IRQ_DMC : u8 = 0b10; // Bit of Nes::irq_sources, which doubles as the DMC's interrupt flag.
+
+pub struct Dmc {
+    irq_enabled : Cell<bool>,
+    looping : Cell<bool>,
+    timer_period : Cell<u16>, // In CPU cycles, from the region's rate table.
+    timer : Cell<u16>,
+    output_level : Cell<u8>,
+    sample_address : Cell<u16>,
+    sample_length : Cell<u16>,
+    current_address : Cell<u16>,
+    bytes_remaining : Cell<u16>,
+    sample_buffer : Cell<Option<u8>>,
+    shift_register : Cell<u8>,
+    bits_remaining : Cell<u8>,
+    silence : Cell<bool>
+}
+
+impl Dmc {
+    pub fn new() -> Self {
+        Dmc { irq_enabled: Cell::new(false), looping: Cell::new(false), timer_period: Cell::new(Region::Ntsc.dmc_rates()[0]), timer: Cell::new(0),
+              output_level: Cell::new(0), sample_address: Cell::new(0xc000), sample_length: Cell::new(1), current_address: Cell::new(0xc000),
+              bytes_remaining: Cell::new(0), sample_buffer: Cell::new(None), shift_register: Cell::new(0), bits_remaining: Cell::new(8),
+              silence: Cell::new(true) }
+    }
+
+    // Register 0-3 of the channel.
+    pub fn write_register(&self, nes : &Nes, register : u16, value : u8) {
+        match register {
+            0 => {
+                self.irq_enabled.set(value & 0x80 != 0);
+                self.looping.set(value & 0x40 != 0);
+                self.timer_period.set(nes.region.get().dmc_rates()[(value & 0x0f) as usize]);
+                if !self.irq_enabled.get() {
+                    nes.irq_sources.set(nes.irq_sources.get() & !IRQ_DMC);
+                }
+            }
+            1 => self.output_level.set(value & 0x7f), // Direct load, used for PCM playback by writing it fast enough.
+            2 => self.sample_address.set(0xc000 | ((value as u16) << 6)),
+            _ => self.sample_length.set(((value as u16) << 4) | 1)
+        }
+    }
+
+    // Bit 4 of $4015. Enabling restarts the sample only once the last one has finished.
+    pub fn set_enabled(&self, enabled : bool) {
+        if !enabled {
+            self.bytes_remaining.set(0);
+        }
+        else if self.bytes_remaining.get() == 0 {
+            self.restart();
+        }
+    }
+
+    fn restart(&self) {
+        self.current_address.set(self.sample_address.get());
+        self.bytes_remaining.set(self.sample_length.get());
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.bytes_remaining.get() > 0
+    }
+
+    // Address the memory reader wants a byte from, when the sample buffer has run dry and the sample isn't over.
+    // The scheduler does the DMA, stalling the CPU, and hands the byte back through fill_sample_buffer.
+    pub fn dma_address(&self) -> Option<u16> {
+        if self.sample_buffer.get().is_none() && self.bytes_remaining.get() > 0 {
+            Some(self.current_address.get())
+        }
+        else {
+            None
+        }
+    }
+
+    // The address wraps from $FFFF to $8000.
+    pub fn fill_sample_buffer(&self, nes : &Nes, value : u8) {
+        self.sample_buffer.set(Some(value));
+        self.current_address.set(if self.current_address.get() == 0xffff { 0x8000 } else { self.current_address.get() + 1 });
+        self.bytes_remaining.set(self.bytes_remaining.get() - 1);
+        if self.bytes_remaining.get() == 0 {
+            if self.looping.get() {
+                self.restart();
+            }
+            else if self.irq_enabled.get() {
+                nes.irq_sources.set(nes.irq_sources.get() | IRQ_DMC);
+            }
+        }
+    }
+
+    // Clocked every CPU cycle, playing one bit per period.
+    pub fn clock_timer(&self) {
+        if self.timer.get() > 0 {
+            self.timer.set(self.timer.get() - 1);
+            return;
+        }
+        self.timer.set(self.timer_period.get() - 1);
+        if !self.silence.get() {
+            let level = self.output_level.get();
+            if self.shift_register.get() & 1 == 1 {
+                if level <= 125 {
+                    self.output_level.set(level + 2);
+                }
+            }
+            else if level >= 2 {
+                self.output_level.set(level - 2);
+            }
+        }
+        self.shift_register.set(self.shift_register.get() >> 1);
+        self.bits_remaining.set(self.bits_remaining.get() - 1);
+        if self.bits_remaining.get() == 0 {
+            // Start of a new output cycle, taking the next byte if the memory reader has one.
+            self.bits_remaining.set(8);
+            match self.sample_buffer.take() {
+                Some(value) => {
+                    self.silence.set(false);
+                    self.shift_register.set(value);
+                }
+                None => self.silence.set(true)
+            }
+        }
+    }
+
+    // Current level, 0-127.
+    pub fn output(&self) -> u8 {
+        self.output_level.get()
+    }
+}
+
+#[cfg(test)]
+fn play_bits(dmc : &Dmc, bits : u16) {
+    for _ in 0..(bits * dmc.timer_period.get()) {
+        dmc.clock_timer();
+    }
+}
+
+#[test]
+fn test_dmc_deltas_and_direct_load() {
+    let nes = Nes::new();
+    let dmc = Dmc::new();
+    dmc.write_register(&nes, 0, 0x0f);
+    dmc.write_register(&nes, 1, 0x40);
+    assert_eq!(dmc.output(), 0x40);
+    play_bits(&dmc, 8); // Silent output cycle, the level holds
+    assert_eq!(dmc.output(), 0x40);
+    dmc.bytes_remaining.set(1);
+    dmc.fill_sample_buffer(&nes, 0b0000_0111);
+    play_bits(&dmc, 8); // Takes the buffered byte at the start of the next output cycle
+    play_bits(&dmc, 8);
+    assert_eq!(dmc.output(), 0x40 + 6 - 10);
+    dmc.write_register(&nes, 1, 0x7e);
+    dmc.bytes_remaining.set(1);
+    dmc.fill_sample_buffer(&nes, 0xff);
+    play_bits(&dmc, 16);
+    assert_eq!(dmc.output(), 0x7e); // Clamped rather than wrapping
+}
+
+#[test]
+fn test_dmc_sample_address_length_and_wrap() {
+    let nes = Nes::new();
+    let dmc = Dmc::new();
+    dmc.write_register(&nes, 2, 0xff);
+    dmc.write_register(&nes, 3, 0x01);
+    dmc.set_enabled(true);
+    assert_eq!(dmc.dma_address(), Some(0xffc0));
+    assert_eq!(dmc.bytes_remaining.get(), 17);
+    let mut addresses = Vec::new();
+    while let Some(address) = dmc.dma_address() {
+        addresses.push(address);
+        dmc.fill_sample_buffer(&nes, 0);
+        dmc.sample_buffer.set(None);
+    }
+    assert_eq!(addresses, (0xffc0..=0xffd0).collect::<Vec<u16>>());
+    assert!(!dmc.is_active());
+    dmc.write_register(&nes, 2, 0x00);
+    dmc.write_register(&nes, 3, 0xff);
+    dmc.current_address.set(0xffff);
+    dmc.bytes_remaining.set(2);
+    dmc.fill_sample_buffer(&nes, 0);
+    assert_eq!(dmc.current_address.get(), 0x8000);
+    dmc.set_enabled(false);
+    assert!(!dmc.is_active());
+}
+
+#[test]
+fn test_dmc_loop_and_irq() {
+    let nes = Nes::new();
+    let dmc = Dmc::new();
+    dmc.write_register(&nes, 0, 0xc0); // Looping with IRQ enabled: loops win
+    dmc.set_enabled(true);
+    dmc.fill_sample_buffer(&nes, 0);
+    assert_eq!(dmc.bytes_remaining.get(), 1);
+    assert_eq!(nes.irq_sources.get(), 0);
+    dmc.write_register(&nes, 0, 0x80);
+    dmc.sample_buffer.set(None);
+    dmc.fill_sample_buffer(&nes, 0);
+    assert!(!dmc.is_active());
+    assert_eq!(nes.irq_sources.get(), IRQ_DMC);
+    dmc.write_register(&nes, 0, 0x00); // Disabling the IRQ acknowledges it
+    assert_eq!(nes.irq_sources.get(), 0);
+}
@@ -0,0 +1,117 @@
+use std::cell::{Cell,RefCell};
+use std::ops::RangeInclusive;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Access {
+    Read,
+    Write,
+    Execute
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct MemoryAccess {
+    pub access : Access,
+    pub address : u16,
+    pub value : u8,
+    pub program_counter : u16 // Address of the instruction that made the access.
+}
+
+pub type HookCallback = Box<dyn Fn(&MemoryAccess)>;
+
+enum HookAction {
+    Callback(HookCallback),
+    Watch // Recorded into the watch list instead of calling back, so a frontend can poll for hits (breakpoints).
+}
+
+struct Hook {
+    id : usize,
+    access : Access,
+    range : RangeInclusive<u16>,
+    action : HookAction
+}
+
+// Callbacks and watchpoints on CPU bus accesses. Hooks are fired from the bus so they see every access made by an instruction.
+// A callback must not add or remove hooks while it is running.
+pub struct Hooks {
+    next_id : Cell<usize>,
+    hooks : RefCell<Vec<Hook>>,
+    watch_hits : RefCell<Vec<MemoryAccess>>
+}
+
+impl Hooks {
+    pub fn new() -> Self {
+        Hooks { next_id: Cell::new(0), hooks: RefCell::new(Vec::new()), watch_hits: RefCell::new(Vec::new()) }
+    }
+
+    fn add(&self, access : Access, range : RangeInclusive<u16>, action : HookAction) -> usize {
+        let id = self.next_id.get();
+        self.next_id.set(id + 1);
+        self.hooks.borrow_mut().push(Hook { id, access, range, action });
+        id
+    }
+
+    pub fn add_hook(&self, access : Access, range : RangeInclusive<u16>, callback : HookCallback) -> usize {
+        self.add(access, range, HookAction::Callback(callback))
+    }
+
+    pub fn add_watchpoint(&self, access : Access, range : RangeInclusive<u16>) -> usize {
+        self.add(access, range, HookAction::Watch)
+    }
+
+    pub fn remove(&self, id : usize) -> bool {
+        let mut hooks = self.hooks.borrow_mut();
+        let count = hooks.len();
+        hooks.retain(|hook| hook.id != id);
+        hooks.len() != count
+    }
+
+    pub fn clear(&self) {
+        self.hooks.borrow_mut().clear();
+        self.watch_hits.borrow_mut().clear();
+    }
+
+    pub fn has_watch_hits(&self) -> bool {
+        !self.watch_hits.borrow().is_empty()
+    }
+
+    pub fn take_watch_hits(&self) -> Vec<MemoryAccess> {
+        self.watch_hits.replace(Vec::new())
+    }
+
+    pub fn fire(&self, memory_access : MemoryAccess) {
+        let hooks = self.hooks.borrow();
+        for hook in hooks.iter().filter(|hook| hook.access == memory_access.access && hook.range.contains(&memory_access.address)) {
+            match &hook.action {
+                HookAction::Callback(callback) => callback(&memory_access),
+                HookAction::Watch => self.watch_hits.borrow_mut().push(memory_access)
+            }
+        }
+    }
+}
+
+#[test]
+fn test_hooks_fire_only_for_matching_access_and_range() {
+    use std::rc::Rc;
+    let hooks = Hooks::new();
+    let hits = Rc::new(Cell::new(0));
+    let counter = hits.clone();
+    hooks.add_hook(Access::Write, 0x10..=0x1f, Box::new(move |_| counter.set(counter.get() + 1)));
+    hooks.fire(MemoryAccess { access: Access::Write, address: 0x10, value: 1, program_counter: 0 });
+    hooks.fire(MemoryAccess { access: Access::Read, address: 0x10, value: 1, program_counter: 0 });
+    hooks.fire(MemoryAccess { access: Access::Write, address: 0x20, value: 1, program_counter: 0 });
+    assert_eq!(hits.get(), 1);
+}
+
+#[test]
+fn test_watchpoint_records_hits_until_taken() {
+    let hooks = Hooks::new();
+    let id = hooks.add_watchpoint(Access::Read, 0x0300..=0x0300);
+    let memory_access = MemoryAccess { access: Access::Read, address: 0x0300, value: 0x42, program_counter: 0x8000 };
+    hooks.fire(memory_access);
+    assert!(hooks.has_watch_hits());
+    assert_eq!(hooks.take_watch_hits(), vec![memory_access]);
+    assert!(!hooks.has_watch_hits());
+    assert!(hooks.remove(id));
+    hooks.fire(memory_access);
+    assert!(!hooks.has_watch_hits());
+}
@@ -1,8 +1,8 @@
 extern crate ggez;
 
 use ggez::{Context,GameResult,GameError};
-use ggez::graphics::{Image,draw,DrawParam,present};
-use ggez::nalgebra::Point2;
+use ggez::graphics::{Image,ImageFormat,draw,DrawParam,present};
+use ggez::nalgebra::{Point2,Vector2};
 use std::cell::RefCell;
 
 
@@ -33,9 +33,21 @@ impl Bitmap {
         }
     }
 
+    pub fn width(&self) -> u16 {
+        self.width
+    }
+
+    pub fn height(&self) -> u16 {
+        self.height
+    }
+
     pub fn draw(&self, context : &mut Context) -> GameResult<()> {
+        self.draw_scaled(context, 1.0, 1.0)
+    }
+
+    pub fn draw_scaled(&self, context : &mut Context, scale_x : f32, scale_y : f32) -> GameResult<()> {
         if let Ok(buffer) = &self.to_image(context) {
-            draw(context, buffer, DrawParam::default().dest(Point2::new(0.0,0.0)))?;
+            draw(context, buffer, DrawParam::default().dest(Point2::new(0.0,0.0)).scale(Vector2::new(scale_x, scale_y)))?;
             present(context)
         }
         else {
@@ -48,6 +60,11 @@ impl Bitmap {
         Image::from_rgba8(context, self.width, self.height, &self.bytes.borrow())
     } 
 
+    // Writes a PNG into ggez's user data directory, the path has to start with a "/".
+    pub fn save_png(&self, context : &mut Context, path : &str) -> GameResult<()> {
+        self.to_image(context)?.encode(context, ImageFormat::Png, path)
+    }
+
     pub fn set_color(&self, x : u32, y : u32, rgb : (u8,u8,u8,u8)) {
         let mut bytes = self.bytes.borrow_mut();
         let row = y * 4;
@@ -61,7 +78,7 @@ impl Bitmap {
 
     pub fn get_color(&self, x : u32, y : u32) -> (u8,u8,u8,u8) {
         let bytes = self.bytes.borrow_mut();
-        let index : usize = ((y * (self.width as u32)) + (x * 4)) as usize;
+        let index : usize = (((y * 4) * (self.width as u32)) + (x * 4)) as usize;
         (bytes[index], bytes[index+1], bytes[index+2], bytes[index+3])
     }
 
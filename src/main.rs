@@ -1,10 +1,29 @@
 extern crate ggez;
+extern crate rodio;
 
 pub mod nesfrontend;
 pub mod img;
 pub mod nes;
 pub mod cpu;
 pub mod rom;
+pub mod controller;
+pub mod hooks;
+pub mod power;
+pub mod region;
+pub mod ppu;
+pub mod apu;
+pub mod pulse;
+pub mod triangle;
+pub mod noise;
+pub mod dmc;
+pub mod mixer;
+pub mod audio;
+pub mod scheduler;
+pub mod cartridge;
+pub mod ppuviewer;
+pub mod ntsc;
+pub mod palette;
+pub mod overscan;
 
 use std::env::args;
 use ggez::ContextBuilder;
@@ -14,36 +33,67 @@ use nesfrontend::NesFrontend;
 
 fn main() {
     if let Some(rom_path) = &args().nth(1) {
-        if let Some(palette_file) = &args().nth(2) {
-            // Make a Context.
-            let (context, event_loop) = &mut ContextBuilder::new("nes_frontend", "Daniel Lopez").window_mode(ggez::conf::WindowMode {
-                    width: 256.0,
-                    height: 240.0,
-                    ..Default::default()
-                })
-                .build()
-                .expect("Failed to create context variable for NES frontend!");
-            //Set the window title.
-            set_window_title(context, &format!("RustyNes - {}", rom_path));
-
-            //Load NES ROM
-            let rom = rom::Rom::load(rom_path.to_string(), palette_file.to_string()).expect("Failed to load ROM!");
-
-            // Create an instance of your event handler.
-            // Usually, you should provide it with the Context object to
-            // use when setting your game up.
-            match NesFrontend::new(rom) {
-                Ok(mut nes_frontend) => {
-                    // Run!
-                    event::run(context, event_loop, &mut nes_frontend).expect("Failed to run event loop!");
-                }
-                Err(e) => {
-                    println!("Error: {}", e);
-                }
+        // The optional second argument is a .pal file or one of the built-in palettes (2c02, 2c03 or 2c07), the
+        // generated 2C02 palette being used without it.
+        let palette = match args().nth(2) {
+            Some(name) => match name.parse::<palette::Preset>() {
+                Ok(preset) => Ok(preset.palette()),
+                Err(_) => palette::load(&name)
+            },
+            None => Ok(palette::Preset::Ntsc2C02.palette())
+        };
+        let palette = match palette {
+            Ok(palette) => palette,
+            Err(e) => {
+                println!("Error: {}", e);
+                return;
+            }
+        };
+        // Make a Context.
+        let (context, event_loop) = &mut ContextBuilder::new("nes_frontend", "Daniel Lopez").window_mode(ggez::conf::WindowMode {
+                width: 256.0,
+                height: 240.0,
+                ..Default::default()
+            })
+            .build()
+            .expect("Failed to create context variable for NES frontend!");
+        //Set the window title.
+        set_window_title(context, &format!("RustyNes - {}", rom_path));
+
+        //Load NES ROM
+        let rom = rom::Rom::load(rom_path.to_string(), &palette).expect("Failed to load ROM!");
+
+        // An optional third argument (ntsc, pal or dendy) overrides the region from the ROM header.
+        let region = match args().nth(3).map(|name| name.parse::<region::Region>()) {
+            Some(Ok(region)) => Some(region),
+            Some(Err(e)) => {
+                println!("Error: {}", e);
+                return;
+            }
+            None => None
+        };
+
+        // An optional fourth argument (top,bottom,left,right) overrides the region's overscan.
+        let overscan = match args().nth(4).map(|edges| edges.parse::<overscan::Overscan>()) {
+            Some(Ok(overscan)) => Some(overscan),
+            Some(Err(e)) => {
+                println!("Error: {}", e);
+                return;
+            }
+            None => None
+        };
+
+        // Create an instance of your event handler.
+        // Usually, you should provide it with the Context object to
+        // use when setting your game up.
+        match NesFrontend::new(rom, region, overscan) {
+            Ok(mut nes_frontend) => {
+                // Run!
+                event::run(context, event_loop, &mut nes_frontend).expect("Failed to run event loop!");
+            }
+            Err(e) => {
+                println!("Error: {}", e);
             }
-        }
-        else {
-            println!("No palette file given!");
         }
     }
     else {
@@ -0,0 +1,235 @@
+use std::cell::{Cell,RefCell};
+use std::f64::consts::PI;
+
+// Turns the APU's channel levels into audio. The channels are combined through the console's non-linear DACs, and
+// every change in the result is placed on the output sample grid as a band-limited step (a windowed sinc, integrated)
+// rather than point sampled, so nothing above the output's Nyquist frequency aliases back down. The samples then go
+// through the high-pass and low-pass filters the console's audio path has.
+
+pub const DEFAULT_SAMPLE_RATE : f64 = 44_100.0;
+const KERNEL_TAPS : usize = 16; // Output samples each step is spread over.
+const KERNEL_PHASES : usize = 32; // Sub-sample positions the steps are placed at.
+const KERNEL_CUTOFF : f64 = 0.9; // Fraction of the output's Nyquist frequency kept.
+const MAX_BUFFERED_SECONDS : f64 = 1.0; // Samples nobody takes are dropped beyond this.
+// First order filters of the NES audio path: two high-passes and a low-pass.
+const HIGH_PASS_HZ : [f64;2] = [90.0, 440.0];
+const LOW_PASS_HZ : f64 = 14_000.0;
+
+// Both pulse channels (levels 0-15 each) share one DAC, triangle, noise and DMC another.
+pub fn mix(pulse_1 : u8, pulse_2 : u8, triangle : u8, noise : u8, dmc : u8) -> f32 {
+    let pulse = (pulse_1 + pulse_2) as f64;
+    let tnd = (3 * triangle as u16 + 2 * noise as u16 + dmc as u16) as f64;
+    let pulse_out = if pulse == 0.0 { 0.0 } else { 95.52 / ((8128.0 / pulse) + 100.0) };
+    let tnd_out = if tnd == 0.0 { 0.0 } else { 163.67 / ((24329.0 / tnd) + 100.0) };
+    (pulse_out + tnd_out) as f32
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum FilterKind {
+    HighPass,
+    LowPass
+}
+
+struct Filter {
+    kind : FilterKind,
+    cutoff : f64,
+    previous_input : Cell<f32>,
+    previous_output : Cell<f32>
+}
+
+impl Filter {
+    fn new(kind : FilterKind, cutoff : f64) -> Self {
+        Filter { kind, cutoff, previous_input: Cell::new(0.0), previous_output: Cell::new(0.0) }
+    }
+
+    fn apply(&self, input : f32, sample_rate : f64) -> f32 {
+        let rc = 1.0 / (2.0 * PI * self.cutoff);
+        let dt = 1.0 / sample_rate;
+        let output = match self.kind {
+            FilterKind::HighPass => {
+                let alpha = (rc / (rc + dt)) as f32;
+                alpha * (self.previous_output.get() + input - self.previous_input.get())
+            }
+            FilterKind::LowPass => {
+                let alpha = (dt / (rc + dt)) as f32;
+                self.previous_output.get() + (alpha * (input - self.previous_output.get()))
+            }
+        };
+        self.previous_input.set(input);
+        self.previous_output.set(output);
+        output
+    }
+}
+
+pub struct Mixer {
+    sample_rate : Cell<f64>,
+    rate_adjustment : Cell<f64>, // Multiplies the sample rate, nudged by the frontend to keep its buffer level steady.
+    time : Cell<f64>, // Output sample position of the current CPU cycle, counted from the first buffered delta.
+    deltas : RefCell<Vec<f32>>, // Amplitude changes spread by the kernel, integrated when the samples are taken.
+    amplitude : Cell<f32>,
+    integrator : Cell<f32>,
+    filters : [Filter;3],
+    kernel : Vec<[f32;KERNEL_TAPS]>
+}
+
+impl Mixer {
+    pub fn new(sample_rate : f64) -> Self {
+        Mixer { sample_rate: Cell::new(sample_rate), rate_adjustment: Cell::new(1.0), time: Cell::new(0.0), deltas: RefCell::new(Vec::new()),
+                amplitude: Cell::new(0.0), integrator: Cell::new(0.0),
+                filters: [Filter::new(FilterKind::HighPass, HIGH_PASS_HZ[0]), Filter::new(FilterKind::HighPass, HIGH_PASS_HZ[1]),
+                          Filter::new(FilterKind::LowPass, LOW_PASS_HZ)],
+                kernel: step_kernel() }
+    }
+
+    pub fn sample_rate(&self) -> f64 {
+        self.sample_rate.get()
+    }
+
+    pub fn set_sample_rate(&self, sample_rate : f64) {
+        self.sample_rate.set(sample_rate);
+    }
+
+    // Slightly more or fewer samples per second than the nominal rate, a factor close to 1.
+    pub fn set_rate_adjustment(&self, adjustment : f64) {
+        self.rate_adjustment.set(adjustment);
+    }
+
+    // Called every CPU cycle with the mixed level and the CPU clock rate.
+    pub fn clock(&self, amplitude : f32, cpu_clock_hz : f64) {
+        let delta = amplitude - self.amplitude.get();
+        if delta != 0.0 {
+            self.amplitude.set(amplitude);
+            self.add_step(delta);
+        }
+        self.time.set(self.time.get() + ((self.sample_rate.get() * self.rate_adjustment.get()) / cpu_clock_hz));
+        let limit = (self.sample_rate.get() * MAX_BUFFERED_SECONDS) as usize;
+        if self.time.get() as usize > limit + KERNEL_TAPS {
+            self.drain(limit / 2);
+        }
+    }
+
+    fn add_step(&self, delta : f32) {
+        let time = self.time.get();
+        let position = time as usize;
+        let phase = (((time - position as f64) * KERNEL_PHASES as f64) as usize).min(KERNEL_PHASES - 1);
+        let mut deltas = self.deltas.borrow_mut();
+        if deltas.len() < position + KERNEL_TAPS {
+            deltas.resize(position + KERNEL_TAPS, 0.0);
+        }
+        for (tap, weight) in self.kernel[phase].iter().enumerate() {
+            deltas[position + tap] += delta * weight;
+        }
+    }
+
+    // Integrates and filters the oldest samples, which are removed from the buffer.
+    fn drain(&self, count : usize) -> Vec<f32> {
+        let mut deltas = self.deltas.borrow_mut();
+        if deltas.len() < count {
+            deltas.resize(count, 0.0);
+        }
+        let sample_rate = self.sample_rate.get();
+        let samples = deltas.drain(0..count).map(|delta| {
+            self.integrator.set(self.integrator.get() + delta);
+            self.filters.iter().fold(self.integrator.get(), |sample, filter| filter.apply(sample, sample_rate))
+        }).collect();
+        self.time.set(self.time.get() - count as f64);
+        samples
+    }
+
+    // Every sample no later step can still change, about 8 samples behind the emulation.
+    pub fn take_samples(&self) -> Vec<f32> {
+        let complete = (self.time.get() as usize).saturating_sub(KERNEL_TAPS);
+        self.drain(complete)
+    }
+}
+
+// Band-limited impulse, one row per sub-sample phase, each summing to 1 so a step settles at exactly its size.
+fn step_kernel() -> Vec<[f32;KERNEL_TAPS]> {
+    (0..KERNEL_PHASES).map(|phase| {
+        let offset = phase as f64 / KERNEL_PHASES as f64;
+        let mut row = [0.0;KERNEL_TAPS];
+        for (tap, weight) in row.iter_mut().enumerate() {
+            let x = (tap as f64) - ((KERNEL_TAPS / 2) as f64) - offset + 1.0;
+            let sinc = if x == 0.0 { 1.0 } else { (PI * KERNEL_CUTOFF * x).sin() / (PI * KERNEL_CUTOFF * x) };
+            let window = 0.5 + (0.5 * (PI * x / (KERNEL_TAPS / 2) as f64).cos()); // Hann
+            *weight = (sinc * window) as f32;
+        }
+        let sum : f32 = row.iter().sum();
+        for weight in row.iter_mut() {
+            *weight /= sum;
+        }
+        row
+    }).collect()
+}
+
+#[test]
+fn test_mixer_lookup_tables() {
+    assert_eq!(mix(0, 0, 0, 0, 0), 0.0);
+    assert!((mix(15, 15, 0, 0, 0) - 0.2575).abs() < 0.001);
+    assert!((mix(0, 0, 15, 15, 127) - 0.7425).abs() < 0.001);
+    assert!(mix(15, 0, 0, 0, 0) * 2.0 > mix(15, 15, 0, 0, 0)); // The DAC is non-linear
+}
+
+#[test]
+fn test_mixer_kernel_rows_sum_to_one() {
+    for row in step_kernel() {
+        assert!((row.iter().sum::<f32>() - 1.0).abs() < 0.0001);
+    }
+}
+
+#[test]
+fn test_mixer_produces_sample_rate_worth_of_samples() {
+    let mixer = Mixer::new(48_000.0);
+    let cpu_clock_hz = 1_789_773.0;
+    for _ in 0..(cpu_clock_hz as usize / 10) {
+        mixer.clock(0.0, cpu_clock_hz);
+    }
+    let samples = mixer.take_samples();
+    assert!(samples.len() >= 4800 - KERNEL_TAPS - 1 && samples.len() <= 4800);
+    mixer.set_rate_adjustment(1.01);
+    for _ in 0..(cpu_clock_hz as usize / 10) {
+        mixer.clock(0.0, cpu_clock_hz);
+    }
+    assert!((mixer.take_samples().len() as i64 - 4848).abs() <= 1);
+}
+
+#[test]
+fn test_mixer_step_is_band_limited_and_filtered() {
+    let mixer = Mixer::new(DEFAULT_SAMPLE_RATE);
+    let cpu_clock_hz = 1_789_773.0;
+    mixer.clock(0.5, cpu_clock_hz);
+    for _ in 0..(cpu_clock_hz as usize / 100) {
+        mixer.clock(0.5, cpu_clock_hz);
+    }
+    let samples = mixer.take_samples();
+    // The step rises over a few samples instead of jumping, then the high-passes pull it back towards 0.
+    let peak = samples.iter().cloned().fold(0.0, f32::max);
+    assert!(samples[0].abs() < 0.05);
+    assert!(peak > 0.3 && peak < 0.55);
+    assert!(samples.last().unwrap().abs() < 0.2 * peak);
+}
+
+#[test]
+fn test_mixer_high_frequencies_dont_alias() {
+    // A square wave near the APU's top pitch, far above what 44.1 kHz can hold, comes out as little more than
+    // its average instead of folding down to audible tones.
+    let mixer = Mixer::new(DEFAULT_SAMPLE_RATE);
+    let cpu_clock_hz = 1_789_773.0;
+    for cycle in 0..(cpu_clock_hz as usize / 20) {
+        mixer.clock(if cycle % 32 < 16 { 0.2 } else { 0.0 }, cpu_clock_hz);
+    }
+    let samples = mixer.take_samples();
+    let settled = &samples[(samples.len() / 2)..];
+    let average = settled.iter().sum::<f32>() / settled.len() as f32;
+    let ripple = settled.iter().map(|sample| (sample - average).abs()).fold(0.0, f32::max);
+    assert!(ripple < 0.02, "ripple {}", ripple);
+}
+
+#[test]
+fn test_mixer_drops_samples_nobody_takes() {
+    let mixer = Mixer::new(DEFAULT_SAMPLE_RATE);
+    for _ in 0..(3 * 1_789_773) {
+        mixer.clock(0.0, 1_789_773.0);
+    }
+    assert!(mixer.take_samples().len() <= DEFAULT_SAMPLE_RATE as usize);
+}
@@ -1,4 +1,12 @@
 use std::cell::{Cell,RefCell};
+use crate::controller::Controller;
+use crate::hooks::{Access,Hooks,MemoryAccess};
+use crate::power::{PowerOnRam,RamRegion};
+use crate::ppu::Ppu;
+use crate::apu::Apu;
+use crate::region::Region;
+use crate::cartridge::Cartridge;
+use crate::cpu;
 
 
 
@@ -9,12 +17,311 @@ pub struct Nes {
     pub x : Cell<u8>, // Index-register
     pub y : Cell<u8>, // Index-register
     pub processor_status_flag : Cell<u8>,
-    pub memory : RefCell<[u8;65536]> // The 64k of memory first 2KB is NES RAM rest is from PPU and APU.
+    pub memory : RefCell<[u8;65536]>, // The 64k of memory first 2KB is NES RAM rest is from PPU and APU.
+    pub open_bus : Cell<u8>, // Last value seen on the CPU data bus, returned for unmapped addresses and undriven bits.
+    pub controllers : [Controller;2],
+    pub hooks : Hooks,
+    pub power_on_ram : PowerOnRam,
+    pub region : Cell<Region>,
+    pub ppu : Ppu,
+    pub apu : Apu,
+    pub nmi_pending : Cell<bool>, // Latched on the PPU's NMI edge, serviced at the next instruction boundary.
+    pub irq_sources : Cell<u8>, // Level-triggered IRQ line, one bit per source. The CPU sees an IRQ while any bit is set.
+    pub oam_dma_page : Cell<Option<u8>>, // Set by a write to $4014, the scheduler runs the copy after the instruction.
+    pub last_access : Cell<(Access,u16)>, // Kind and address of the latest bus cycle, which DMA interacts with.
+    pub cartridge : RefCell<Option<Cartridge>> // Maps $4020-$FFFF when inserted, otherwise the flat memory is used.
 }
 
 impl Nes {
     pub fn new() -> Self {
-        Nes { a: Cell::new(0), x: Cell::new(0), y: Cell::new(0), program_counter: Cell::new(0), stack_pointer: Cell::new(0), processor_status_flag: Cell::new(0), memory: RefCell::new([0 as u8;65536]) }
+        Nes::with_power_on_ram(PowerOnRam::Zeros)
     }
+
+    pub fn with_power_on_ram(power_on_ram : PowerOnRam) -> Self {
+        let nes = Nes { a: Cell::new(0), x: Cell::new(0), y: Cell::new(0), program_counter: Cell::new(0), stack_pointer: Cell::new(0), processor_status_flag: Cell::new(0), memory: RefCell::new([0 as u8;65536]),
+              open_bus: Cell::new(0), controllers: [Controller::new(), Controller::new()], hooks: Hooks::new(), power_on_ram,
+              region: Cell::new(Region::Ntsc), ppu: Ppu::new(), apu: Apu::new(), nmi_pending: Cell::new(false), irq_sources: Cell::new(0),
+              oam_dma_page: Cell::new(None), last_access: Cell::new((Access::Read, 0)), cartridge: RefCell::new(None) };
+        nes.fill_power_on_ram();
+        nes
+    }
+
+    fn fill_power_on_ram(&self) {
+        let mut memory = self.memory.borrow_mut();
+        self.power_on_ram.fill(RamRegion::InternalRam, &mut memory[0..0x800]);
+        self.power_on_ram.fill(RamRegion::PrgRam, &mut memory[0x6000..0x8000]);
+        self.ppu.fill_power_on_ram(&self.power_on_ram);
+    }
+
+    // Inserts the cartridge in its power-on state and resets the console so it starts from the cartridge's reset vector.
+    pub fn insert_cartridge(&self, cartridge : Cartridge) {
+        cartridge.power_on(&self.power_on_ram);
+        self.cartridge.replace(Some(cartridge));
+        self.reset();
+    }
+
+    // Pressing the RESET button. RAM is left alone, the CPU goes through its reset sequence (which pushes nothing
+    // but still decrements the stack pointer by 3) and starts again from the reset vector.
+    pub fn reset(&self) {
+        self.stack_pointer.set(self.stack_pointer.get().wrapping_sub(3));
+        self.processor_status_flag
+            .set(self.processor_status_flag.get() | 0b100);
+        self.nmi_pending.set(false);
+        self.irq_sources.set(0);
+        self.ppu.reset();
+        self.apu.reset(self);
+        let low_byte = self.read_bus(cpu::RESET_VECTOR) as u16;
+        let high_byte = self.read_bus(cpu::RESET_VECTOR + 1) as u16;
+        self.program_counter.set((high_byte << 8) | low_byte);
+    }
+
+    // Turning the console off and on again. Everything is reinitialised, including RAM and the mapper registers.
+    // The cartridge, region, colour palette, renderer choice, audio sample rate and any debugging hooks survive.
+    pub fn power_cycle(&mut self) {
+        let cartridge = self.cartridge.replace(None);
+        let hooks = std::mem::replace(&mut self.hooks, Hooks::new());
+        let region = self.region.get();
+        let rgb_palette = self.ppu.rgb_palette.replace(Vec::new());
+        let renderer = self.ppu.renderer.get();
+        let sample_rate = self.apu.mixer.sample_rate();
+        *self = Nes::with_power_on_ram(self.power_on_ram.clone());
+        self.hooks = hooks;
+        self.region.set(region);
+        self.ppu.rgb_palette.replace(rgb_palette);
+        self.ppu.renderer.set(renderer);
+        self.apu.mixer.set_sample_rate(sample_rate);
+        if let Some(cartridge) = cartridge {
+            self.insert_cartridge(cartridge);
+        }
+        else {
+            self.reset();
+        }
+    }
+
+    // CPU bus read. Every read drives the data bus, so whatever is read becomes the new open bus value.
+    pub fn read(&self, address : u16) -> u8 {
+        let value = self.read_bus(address);
+        self.fire_hooks(Access::Read, address, value);
+        value
+    }
+
+    // Opcode fetch at the program counter, reported to execute hooks rather than read hooks.
+    pub fn fetch_opcode(&self) -> u8 {
+        let address = self.program_counter.get();
+        let opcode = self.read_bus(address);
+        self.fire_hooks(Access::Execute, address, opcode);
+        opcode
+    }
+
+    fn fire_hooks(&self, access : Access, address : u16, value : u8) {
+        self.last_access.set((access, address));
+        self.hooks.fire(MemoryAccess { access, address, value, program_counter: self.program_counter.get() });
+    }
+
+    fn read_bus(&self, address : u16) -> u8 {
+        if address == 0x4015 {
+            // The status register is inside the 2A03 and doesn't drive the external data bus, so open bus keeps its value.
+            return self.apu.read_status(self);
+        }
+        let value = match address {
+            0x2000..=0x3fff => self.ppu.read_register(self, address),
+            0x4000..=0x4014 => self.open_bus.get(), // APU and OAM DMA registers are write-only.
+            0x4016 | 0x4017 => (self.open_bus.get() & 0xe0) | self.controllers[(address - 0x4016) as usize].read(), // Only the low bits are driven by the controller ports.
+            0x4018..=0x401f => self.open_bus.get(), // APU test mode registers are disabled on retail consoles.
+            0x4020..=0xffff if self.cartridge.borrow().is_some() => {
+                self.cartridge.borrow().as_ref().and_then(|cartridge| cartridge.cpu_read(address)).unwrap_or_else(|| self.open_bus.get())
+            }
+            0x4020..=0x5fff => self.open_bus.get(), // Cartridge expansion area is unmapped on most carts.
+            0x0000..=0x1fff => self.memory.borrow()[(address & 0x7ff) as usize], // The 2KB of internal RAM is mirrored up to $1FFF.
+            _ => self.memory.borrow()[address as usize]
+        };
+        self.open_bus.set(value);
+        value
+    }
+
+    // Copies a 256 byte page through $2004 into OAM, starting at the current OAMADDR.
+    pub fn oam_dma(&self, page : u8) {
+        let start = (page as u16) << 8;
+        for offset in 0..=0xff {
+            let value = self.read(start + offset);
+            self.ppu.write_register(self, 0x2004, value);
+        }
+    }
+
+    pub fn write(&self, address : u16, value : u8) {
+        self.open_bus.set(value);
+        self.fire_hooks(Access::Write, address, value);
+        match address {
+            0x2000..=0x3fff => self.ppu.write_register(self, address, value),
+            0x4000..=0x4013 | 0x4015 | 0x4017 => self.apu.write_register(self, address, value),
+            0x4014 => self.oam_dma_page.set(Some(value)),
+            0x4016 => {
+                for controller in &self.controllers {
+                    controller.write_strobe(value);
+                }
+            }
+            0x4020..=0xffff if self.cartridge.borrow().is_some() => {
+                if let Some(cartridge) = self.cartridge.borrow().as_ref() {
+                    cartridge.cpu_write(address, value);
+                }
+            }
+            0x4000..=0x5fff => {} // Nothing is mapped here yet so the write only drives the bus.
+            0x0000..=0x1fff => self.memory.borrow_mut()[(address & 0x7ff) as usize] = value,
+            _ => self.memory.borrow_mut()[address as usize] = value
+        }
+    }
+}
+
+#[test]
+fn test_open_bus_returned_for_unmapped_read() {
+    let nes = Nes::new();
+    nes.write(0x0200, 0x5a);
+    assert_eq!(nes.read(0x5000), 0x5a);
+    {
+        let mut memory = nes.memory.borrow_mut();
+        memory[0x10] = 0x3c;
+    }
+    nes.read(0x10);
+    assert_eq!(nes.read(0x4018), 0x3c);
 }
 
+#[test]
+fn test_open_bus_returned_for_write_only_registers() {
+    let nes = Nes::new();
+    nes.write(0x4000, 0xa5);
+    assert_eq!(nes.read(0x4000), 0xa5);
+    assert_eq!(nes.read(0x4014), 0xa5);
+}
+
+#[test]
+fn test_apu_status_read_leaves_open_bus() {
+    let nes = Nes::new();
+    nes.write(0x4015, 0x01);
+    nes.write(0x4003, 0x08); // Pulse 1 length counter loaded, status reads $01
+    nes.write(0x4000, 0xe0);
+    assert_eq!(nes.read(0x4015), 0x21); // Bit 5 comes from the open bus
+    assert_eq!(nes.read(0x5000), 0xe0);
+}
+
+#[test]
+fn test_open_bus_fills_undriven_controller_bits() {
+    let nes = Nes::new();
+    nes.controllers[0].set_button(crate::controller::Button::A, true);
+    nes.write(0x4016, 1);
+    nes.write(0x4016, 0);
+    {
+        let mut memory = nes.memory.borrow_mut();
+        memory[0x20] = 0x40;
+    }
+    nes.read(0x20);
+    assert_eq!(nes.read(0x4016), 0x41); // Classic $40 | button bit seen by most games.
+    assert_eq!(nes.read(0x4016), 0x40);
+}
+
+#[test]
+fn test_unmapped_writes_are_not_stored() {
+    let nes = Nes::new();
+    nes.write(0x5000, 0x77);
+    nes.write(0x0000, 0x00);
+    assert_eq!(nes.read(0x5000), 0x00);
+    assert_eq!(nes.memory.borrow()[0x5000], 0);
+}
+
+#[test]
+fn test_hooks_fired_from_bus_with_program_counter() {
+    use std::rc::Rc;
+    let nes = Nes::new();
+    let seen = Rc::new(RefCell::new(Vec::new()));
+    let log = seen.clone();
+    nes.hooks.add_hook(Access::Write, 0x0200..=0x02ff, Box::new(move |memory_access| log.borrow_mut().push(*memory_access)));
+    nes.hooks.add_watchpoint(Access::Execute, 0x8000..=0x8000);
+    nes.program_counter.set(0x8000);
+    nes.write(0x0210, 0x99);
+    nes.write(0x0300, 0x11);
+    nes.fetch_opcode();
+    assert_eq!(*seen.borrow(), vec![MemoryAccess { access: Access::Write, address: 0x0210, value: 0x99, program_counter: 0x8000 }]);
+    assert_eq!(nes.hooks.take_watch_hits().len(), 1);
+}
+
+#[test]
+fn test_power_on_ram_fills_internal_and_prg_ram() {
+    let nes = Nes::with_power_on_ram(PowerOnRam::Ones);
+    let memory = nes.memory.borrow();
+    assert_eq!(memory[0x0000], 0xff);
+    assert_eq!(memory[0x07ff], 0xff);
+    assert_eq!(memory[0x0800], 0x00); // Only the 2KB itself, the mirrors are decoded on the bus
+    assert_eq!(memory[0x6000], 0xff);
+    assert_eq!(memory[0x7fff], 0xff);
+    assert_eq!(memory[0x8000], 0x00);
+}
+
+#[test]
+fn test_internal_ram_mirrored_up_to_1fff() {
+    let nes = Nes::with_power_on_ram(PowerOnRam::Random(7));
+    assert_eq!(nes.read(0x1234), nes.read(0x0234));
+    nes.write(0x0801, 0x5a);
+    assert_eq!(nes.read(0x0001), 0x5a);
+    assert_eq!(nes.read(0x1801), 0x5a);
+    nes.write(0x07ff, 0xa5);
+    assert_eq!(nes.read(0x1fff), 0xa5);
+}
+
+#[test]
+fn test_power_on_ram_random_is_reproducible() {
+    let first = Nes::with_power_on_ram(PowerOnRam::Random(42));
+    let second = Nes::with_power_on_ram(PowerOnRam::Random(42));
+    assert_eq!(first.memory.borrow()[0..0x800], second.memory.borrow()[0..0x800]);
+    assert_eq!(first.memory.borrow()[0x6000..0x8000], second.memory.borrow()[0x6000..0x8000]);
+}
+
+#[cfg(test)]
+fn nes_with_reset_vector() -> Nes {
+    let nes = Nes::new();
+    {
+        let mut memory = nes.memory.borrow_mut();
+        memory[0xfffc] = 0x00;
+        memory[0xfffd] = 0x80;
+    }
+    nes
+}
+
+#[test]
+fn test_reset_keeps_ram_and_reloads_program_counter() {
+    let nes = nes_with_reset_vector();
+    nes.write(0x0300, 0x42);
+    nes.stack_pointer.set(0xfd);
+    nes.program_counter.set(0x1234);
+    nes.nmi_pending.set(true);
+    nes.reset();
+    assert_eq!(nes.read(0x0300), 0x42);
+    assert_eq!(nes.stack_pointer.get(), 0xfa);
+    assert_ne!(nes.processor_status_flag.get() & 0b100, 0);
+    assert_eq!(nes.program_counter.get(), 0x8000);
+    assert!(!nes.nmi_pending.get());
+}
+
+#[test]
+fn test_power_cycle_reinitialises_ram_and_mapper() {
+    let mut nes = Nes::with_power_on_ram(PowerOnRam::Ones);
+    nes.insert_cartridge(Cartridge::new(crate::rom::test_rom(2, 4, 0)).unwrap());
+    assert_eq!(nes.stack_pointer.get(), 0xfd);
+    nes.write(0x0300, 0x42);
+    nes.write(0x8000, 2);
+    assert_eq!(nes.read(0x8000), 2);
+    nes.hooks.add_watchpoint(Access::Read, 0x0300..=0x0300);
+    nes.power_cycle();
+    assert_eq!(nes.read(0x0300), 0xff);
+    assert_eq!(nes.read(0x8000), 0);
+    assert_eq!(nes.stack_pointer.get(), 0xfd);
+    assert_eq!(nes.hooks.take_watch_hits().len(), 1);
+}
+
+#[test]
+fn test_cartridge_maps_upper_bus() {
+    let nes = Nes::new();
+    nes.insert_cartridge(Cartridge::new(crate::rom::test_rom(0, 2, 1)).unwrap());
+    assert_eq!(nes.read(0xc000), 1);
+    nes.write(0x6000, 0x99);
+    assert_eq!(nes.read(0x6000), 0x99);
+    assert_eq!(nes.read(0x5000), 0x99); // Nothing on the cart at $5000, so open bus.
+}
@@ -1,29 +1,226 @@
-use ggez::{Context, GameResult};
-use ggez::event::EventHandler;
+use ggez::{Context, GameResult, GameError};
+use ggez::event::{EventHandler, KeyCode, KeyMods};
+use ggez::timer;
 use crate::rom::Rom;
+use crate::nes::Nes;
+use crate::cartridge::Cartridge;
+use crate::scheduler::Scheduler;
+use crate::ppu::Renderer;
+use crate::region::Region;
+use crate::img::Bitmap;
+use crate::ppuviewer;
+use crate::ntsc::NtscFilter;
+use crate::overscan::Overscan;
+use crate::audio::{self,AudioOutput};
+use std::time::Duration;
+
+const QUIT_KEY : KeyCode = KeyCode::Escape; // ggez's default key handler quits on it, overriding the handler loses that.
+const RESET_KEY : KeyCode = KeyCode::R;
+const POWER_CYCLE_KEY : KeyCode = KeyCode::P;
+const RENDERER_KEY : KeyCode = KeyCode::F; // Switches between the accurate and the fast PPU renderer.
+const VIEW_KEY : KeyCode = KeyCode::F1; // Cycles through the game and the PPU debug views.
+const DUMP_KEY : KeyCode = KeyCode::F2; // Saves every debug view as a PNG and prints OAM.
+const PATTERN_PALETTE_KEY : KeyCode = KeyCode::F3; // Picks the palette the pattern table view is drawn with.
+const NTSC_FILTER_KEY : KeyCode = KeyCode::N; // Turns the composite video filter on and off.
+const OVERSCAN_KEY : KeyCode = KeyCode::O; // Turns overscan cropping on and off.
+const SCREENSHOT_KEY : KeyCode = KeyCode::F12; // Saves the game picture as shown, overscan and filter included.
+const MUTE_KEY : KeyCode = KeyCode::M;
+const VOLUME_DOWN_KEY : KeyCode = KeyCode::Minus;
+const VOLUME_UP_KEY : KeyCode = KeyCode::Equals;
+const MAX_FRAMES_PER_UPDATE : u32 = 4; // Beyond this the emulator gives up catching up rather than stalling the window.
+const WINDOW_SIZE : (f32,f32) = (256.0, 240.0);
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum View {
+    Game,
+    Nametables,
+    Oam,
+    Palettes,
+    PatternTables
+}
 
 pub struct NesFrontend {
     // Your state here...
-    rom : Rom
+    nes : Nes,
+    scheduler : Scheduler,
+    frame_time : Duration, // Real time not yet covered by emulated frames.
+    view : View,
+    pattern_palette : u8,
+    ntsc : Option<(NtscFilter, Bitmap)>, // Filter settings and its output when the composite filter is on.
+    overscan : Overscan,
+    crop_overscan : bool,
+    picture : Option<Bitmap>, // The cropped game picture, kept to be redrawn into every frame.
+    audio : AudioOutput
 }
 
 impl NesFrontend {
-    pub fn new(rom : Rom) -> GameResult<NesFrontend> {
-        let nes_frontend = NesFrontend { rom: rom  };
+    // The region comes from the ROM header unless one is given, and the overscan from the region.
+    pub fn new(rom : Rom, region : Option<Region>, overscan : Option<Overscan>) -> GameResult<NesFrontend> {
+        let nes = Nes::new();
+        let region = region.unwrap_or_else(|| Region::from_rom(&rom));
+        nes.region.set(region);
+        nes.ppu.rgb_palette.replace(rom.palette.clone());
+        let cartridge = Cartridge::new(rom).map_err(GameError::ResourceLoadError)?;
+        nes.insert_cartridge(cartridge);
+        let audio = AudioOutput::new();
+        nes.apu.mixer.set_sample_rate(audio.sample_rate());
+        let nes_frontend = NesFrontend { nes, scheduler: Scheduler::new(), frame_time: Duration::from_secs(0), view: View::Game,
+                                         pattern_palette: 0, ntsc: None, overscan: overscan.unwrap_or_else(|| Overscan::for_region(region)),
+                                         crop_overscan: true, picture: None, audio };
         Ok(nes_frontend)
     }
+
+    fn debug_view(&self, view : View) -> Option<Bitmap> {
+        match view {
+            View::Game => None,
+            View::Nametables => Some(ppuviewer::nametables(&self.nes)),
+            View::Oam => Some(ppuviewer::oam(&self.nes)),
+            View::Palettes => Some(ppuviewer::palettes(&self.nes)),
+            View::PatternTables => Some(ppuviewer::pattern_tables(&self.nes, self.pattern_palette))
+        }
+    }
+
+    // Runs the NTSC filter over the last completed frame. It's done once per emulated frame rather than on every draw,
+    // which happens at the display's refresh rate.
+    fn filter_frame(&self) {
+        if let Some((filter, output)) = &self.ntsc {
+            filter.apply(&self.nes.ppu.pixels.borrow(), self.nes.ppu.frame.get(), output);
+        }
+    }
+
+    // The game picture as it is presented: the NTSC filter's output when it's on, then cropped to the overscan.
+    // A new bitmap is only made when the size changes, with the filter or cropping toggled.
+    fn game_picture(&mut self) -> GameResult<&Bitmap> {
+        let overscan = if self.crop_overscan { self.overscan } else { Overscan::none() };
+        let frame = match &self.ntsc {
+            Some((_, output)) => output,
+            None => &self.nes.ppu.screen
+        };
+        let (width, height) = overscan.cropped_size(frame).map_err(GameError::RenderError)?;
+        let picture = match self.picture.take() {
+            Some(picture) if picture.width() == width && picture.height() == height => picture,
+            _ => Bitmap::new(width, height).map_err(GameError::RenderError)?
+        };
+        overscan.crop_into(frame, &picture).map_err(GameError::RenderError)?;
+        Ok(self.picture.get_or_insert(picture))
+    }
+
+    // Hands the frame's samples to the audio output and steers the mixer's rate by how full its buffer is.
+    fn play_audio(&self) {
+        self.audio.queue(&self.nes.apu.mixer.take_samples());
+        self.nes.apu.mixer.set_rate_adjustment(self.audio.rate_adjustment());
+    }
+
+    fn change_volume(&mut self, change : f32) {
+        self.audio.set_volume(self.audio.volume() + change);
+        println!("Volume {:.0}%", self.audio.volume() * 100.0);
+    }
+
+    fn dump_debug_views(&self, context : &mut Context) -> GameResult<()> {
+        let views = [(View::Nametables, "/nametables.png"), (View::Oam, "/oam.png"), (View::Palettes, "/palettes.png"),
+                     (View::PatternTables, "/pattern_tables.png")];
+        for (view, path) in views.iter() {
+            if let Some(bitmap) = self.debug_view(*view) {
+                bitmap.save_png(context, path)?;
+            }
+        }
+        for line in ppuviewer::oam_listing(&self.nes) {
+            println!("{}", line);
+        }
+        Ok(())
+    }
 }
 
 impl EventHandler for NesFrontend {
 
-    fn update(&mut self, _context: &mut Context) -> GameResult<()> {
-        // Update code here...
+    fn update(&mut self, context: &mut Context) -> GameResult<()> {
+        // Frames are paced at the region's real rate (60.0988 Hz on NTSC, 50.007 Hz on PAL and Dendy).
+        let frame_duration = Duration::from_secs_f64(1.0 / self.nes.region.get().frame_rate());
+        self.frame_time += timer::delta(context);
+        let mut frames = 0;
+        while self.frame_time >= frame_duration {
+            self.frame_time -= frame_duration;
+            frames += 1;
+            if frames > MAX_FRAMES_PER_UPDATE {
+                self.frame_time = Duration::from_secs(0);
+                break;
+            }
+            self.scheduler.run_frame(&self.nes);
+            self.play_audio();
+        }
+        if frames > 0 {
+            self.filter_frame();
+        }
         Ok(())
     }
 
     fn draw(&mut self, context: &mut Context) -> GameResult<()> {
-        // Draw code here...
-        let bmp = self.rom.vrom_bmps.first().expect("Empty VROM Bitmaps!");
-        bmp.draw(context)
+        // Pictures are squeezed into the window whatever their size.
+        let debug_view = self.debug_view(self.view);
+        let bitmap = match &debug_view {
+            Some(bitmap) => bitmap,
+            None => self.game_picture()?
+        };
+        bitmap.draw_scaled(context, WINDOW_SIZE.0 / bitmap.width() as f32, WINDOW_SIZE.1 / bitmap.height() as f32)
+    }
+
+    fn key_down_event(&mut self, context: &mut Context, keycode: KeyCode, _keymods: KeyMods, repeat: bool) {
+        if repeat {
+            return;
+        }
+        match keycode {
+            QUIT_KEY => ggez::event::quit(context),
+            RESET_KEY => {
+                self.nes.reset();
+                self.scheduler.reset();
+            }
+            POWER_CYCLE_KEY => {
+                self.nes.power_cycle();
+                self.scheduler = Scheduler::new();
+            }
+            RENDERER_KEY => {
+                let renderer = match self.nes.ppu.renderer.get() {
+                    Renderer::Dot => Renderer::Scanline,
+                    Renderer::Scanline => Renderer::Dot
+                };
+                println!("Using the {:?} renderer", renderer);
+                self.nes.ppu.renderer.set(renderer);
+            }
+            VIEW_KEY => {
+                self.view = match self.view {
+                    View::Game => View::Nametables,
+                    View::Nametables => View::Oam,
+                    View::Oam => View::Palettes,
+                    View::Palettes => View::PatternTables,
+                    View::PatternTables => View::Game
+                };
+            }
+            DUMP_KEY => {
+                if let Err(e) = self.dump_debug_views(context) {
+                    println!("Failed to save debug views: {}", e);
+                }
+            }
+            PATTERN_PALETTE_KEY => self.pattern_palette = (self.pattern_palette + 1) % 8,
+            NTSC_FILTER_KEY => {
+                self.ntsc = match self.ntsc {
+                    Some(_) => None,
+                    None => Some((NtscFilter::composite(), NtscFilter::new_output()))
+                };
+                self.filter_frame();
+            }
+            OVERSCAN_KEY => self.crop_overscan = !self.crop_overscan,
+            SCREENSHOT_KEY => {
+                if let Err(e) = self.game_picture().and_then(|picture| picture.save_png(context, "/screenshot.png")) {
+                    println!("Failed to save screenshot: {}", e);
+                }
+            }
+            MUTE_KEY => {
+                self.audio.set_muted(!self.audio.is_muted());
+                println!("Sound {}", if self.audio.is_muted() { "muted" } else { "on" });
+            }
+            VOLUME_DOWN_KEY => self.change_volume(-audio::VOLUME_STEP),
+            VOLUME_UP_KEY => self.change_volume(audio::VOLUME_STEP),
+            _ => {}
+        }
     }
-}
\ No newline at end of file
+}
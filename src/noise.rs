@@ -0,0 +1,108 @@
+use crate::apu::{Envelope,LengthCounter};
+use crate::region::Region;
+use std::cell::Cell;
+
+// Noise channel at $400C-$400F. A 15 bit linear feedback shift register gives pseudo-random bits, with a short
+// mode that taps bit 6 instead of bit 1 for a 93 step metallic buzz.
+
+pub struct Noise {
+    shift_register : Cell<u16>,
+    short_mode : Cell<bool>,
+    timer_period : Cell<u16>, // In CPU cycles, from the region's period table.
+    timer : Cell<u16>,
+    pub envelope : Envelope,
+    pub length_counter : LengthCounter
+}
+
+impl Noise {
+    pub fn new() -> Self {
+        Noise { shift_register: Cell::new(1), short_mode: Cell::new(false), timer_period: Cell::new(Region::Ntsc.noise_periods()[0]), timer: Cell::new(0),
+                envelope: Envelope::new(), length_counter: LengthCounter::new() }
+    }
+
+    // Register 0-3 of the channel, register 1 being unused.
+    pub fn write_register(&self, region : Region, register : u16, value : u8) {
+        match register {
+            0 => {
+                self.length_counter.set_halted(value & 0x20 != 0);
+                self.envelope.write(value);
+            }
+            1 => {}
+            2 => {
+                self.short_mode.set(value & 0x80 != 0);
+                self.timer_period.set(region.noise_periods()[(value & 0x0f) as usize]);
+            }
+            _ => {
+                self.length_counter.load(value);
+                self.envelope.restart();
+            }
+        }
+    }
+
+    // Clocked every CPU cycle, shifting the register once per period.
+    pub fn clock_timer(&self) {
+        if self.timer.get() == 0 {
+            self.timer.set(self.timer_period.get() - 1);
+            let bits = self.shift_register.get();
+            let tap = if self.short_mode.get() { 6 } else { 1 };
+            let feedback = (bits ^ (bits >> tap)) & 1;
+            self.shift_register.set((bits >> 1) | (feedback << 14));
+        }
+        else {
+            self.timer.set(self.timer.get() - 1);
+        }
+    }
+
+    // Current level, 0-15. Bit 0 of the shift register being set silences the channel.
+    pub fn output(&self) -> u8 {
+        if self.shift_register.get() & 1 == 0 && self.length_counter.is_active() {
+            self.envelope.output()
+        }
+        else {
+            0
+        }
+    }
+}
+
+#[cfg(test)]
+fn sequence_length(noise : &Noise) -> usize {
+    let start = noise.shift_register.get();
+    let mut steps = 0;
+    loop {
+        for _ in 0..noise.timer_period.get() {
+            noise.clock_timer();
+        }
+        steps += 1;
+        if noise.shift_register.get() == start {
+            return steps;
+        }
+    }
+}
+
+#[test]
+fn test_noise_shift_register_modes() {
+    let noise = Noise::new();
+    noise.clock_timer();
+    assert_eq!(noise.shift_register.get(), 0x4000); // Bit 0 and bit 1 differ, so a 1 is fed into bit 14
+    assert_eq!(sequence_length(&noise), 32767);
+    noise.write_register(Region::Ntsc, 2, 0x80);
+    assert_eq!(sequence_length(&noise), 93);
+}
+
+#[test]
+fn test_noise_period_tables_and_output() {
+    let noise = Noise::new();
+    noise.write_register(Region::Ntsc, 2, 0x0f);
+    assert_eq!(noise.timer_period.get(), 4068);
+    noise.write_register(Region::Pal, 2, 0x0f);
+    assert_eq!(noise.timer_period.get(), 3778);
+    noise.write_register(Region::Dendy, 2, 0x02);
+    assert_eq!(noise.timer_period.get(), 16);
+    noise.length_counter.set_enabled(true);
+    noise.write_register(Region::Ntsc, 0, 0x1a);
+    noise.write_register(Region::Ntsc, 3, 0x08);
+    assert_eq!(noise.shift_register.get() & 1, 1);
+    assert_eq!(noise.output(), 0);
+    noise.clock_timer();
+    assert_eq!(noise.output(), 0x0a);
+}
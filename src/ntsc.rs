@@ -0,0 +1,190 @@
+use crate::img::Bitmap;
+use crate::ppu::{SCREEN_WIDTH,SCREEN_HEIGHT};
+use std::f32::consts::PI;
+
+// Composite video filter in the style of blargg's nes_ntsc. Each PPU pixel is turned into the 8 samples of square
+// wave the PPU puts on the composite output, and the signal is decoded back to RGB the way a TV would, giving the
+// colour fringes and artifacts real hardware has. The output is wider than the PPU's 256 pixels to leave room for them.
+
+pub const OUTPUT_WIDTH : usize = 602;
+const SAMPLES_PER_PIXEL : usize = 8; // One per master clock, the colour subcarrier being 12 master clocks long.
+const SAMPLES_PER_LINE : usize = SCREEN_WIDTH * SAMPLES_PER_PIXEL;
+const SUBCARRIER_PERIOD : usize = 12;
+
+// Signal voltages relative to sync for the four luminance levels, low and high halves of the wave.
+const LEVELS_LOW : [f32;4] = [0.350, 0.518, 0.962, 1.550];
+const LEVELS_HIGH : [f32;4] = [1.094, 1.506, 1.962, 1.962];
+pub const BLACK : f32 = 0.518;
+pub const WHITE : f32 = 1.962;
+const EMPHASIS_ATTENUATION : f32 = 0.746;
+pub const HUE_OFFSET : f32 = 105.0 * PI / 180.0; // Lines the decoded hues up with the usual NES palettes.
+
+// The adjustments go from -1 to 1 like nes_ntsc's, 0 being a normal composite picture.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct NtscFilter {
+    pub sharpness : f32, // Edge enhancement of the luminance, negative values blur.
+    pub artifacts : f32, // How much luminance edges bleed into colour, -1 for none.
+    pub fringing : f32, // How much the colour subcarrier shows as dots in luminance, -1 for none.
+    pub merge_fields : bool // Average the two subcarrier phases consecutive frames alternate between, hiding the dot crawl.
+}
+
+impl NtscFilter {
+    pub fn composite() -> Self {
+        NtscFilter { sharpness: 0.0, artifacts: 0.0, fringing: 0.0, merge_fields: true }
+    }
+
+    pub fn svideo() -> Self {
+        NtscFilter { sharpness: 0.2, artifacts: -1.0, fringing: -1.0, merge_fields: true }
+    }
+
+    pub fn new_output() -> Bitmap {
+        Bitmap::new(OUTPUT_WIDTH as u16, SCREEN_HEIGHT as u16).expect("Failed to initialze bitmap!")
+    }
+
+    // Filters a frame of PPU output (colour index with the emphasis bits above it, as in Ppu::pixels) into an
+    // OUTPUT_WIDTH x 240 bitmap. The subcarrier phase moves by a third of a cycle every line, and odd frames start
+    // a third of a cycle later than even ones.
+    pub fn apply(&self, pixels : &[u16], frame : u64, output : &Bitmap) {
+        let mut signal = vec![0.0;SAMPLES_PER_LINE];
+        for y in 0..SCREEN_HEIGHT {
+            let line = &pixels[(y * SCREEN_WIDTH)..((y + 1) * SCREEN_WIDTH)];
+            let phase = (y * 4) % SUBCARRIER_PERIOD;
+            let rows = if self.merge_fields {
+                vec![self.decode_line(line, phase, &mut signal), self.decode_line(line, phase + 4, &mut signal)]
+            }
+            else {
+                vec![self.decode_line(line, phase + ((frame % 2) as usize * 4), &mut signal)]
+            };
+            for x in 0..OUTPUT_WIDTH {
+                let count = rows.len() as f32;
+                let (r, g, b) = rows.iter().fold((0.0, 0.0, 0.0), |sum, row| (sum.0 + row[x].0, sum.1 + row[x].1, sum.2 + row[x].2));
+                output.set_color(x as u32, y as u32, (to_channel(r / count), to_channel(g / count), to_channel(b / count), 0xff));
+            }
+        }
+    }
+
+    fn decode_line(&self, line : &[u16], phase : usize, signal : &mut [f32]) -> Vec<(f32,f32,f32)> {
+        for (x, pixel) in line.iter().enumerate() {
+            for sample in 0..SAMPLES_PER_PIXEL {
+                let index = (x * SAMPLES_PER_PIXEL) + sample;
+                signal[index] = (composite_level(*pixel, phase + index) - BLACK) / (WHITE - BLACK);
+            }
+        }
+        let mut sums = vec![0.0;SAMPLES_PER_LINE + 1]; // Running totals so any window can be averaged in one step
+        for (index, level) in signal.iter().enumerate() {
+            sums[index + 1] = sums[index] + level;
+        }
+        let average = |centre : usize, width : usize| {
+            let start = centre.saturating_sub(width / 2);
+            let end = (start + width).min(SAMPLES_PER_LINE);
+            (sums[end] - sums[start]) / (end - start) as f32
+        };
+        let subcarrier : Vec<(f32,f32)> = (0..SUBCARRIER_PERIOD).map(|step| {
+            let angle = (PI * step as f32 / 6.0) + HUE_OFFSET;
+            (angle.cos(), angle.sin())
+        }).collect();
+        let artifacts = 1.0 + self.artifacts;
+        let fringing = (1.0 + self.fringing) * 0.5;
+        (0..OUTPUT_WIDTH).map(|x| {
+            let centre = (x * SAMPLES_PER_LINE) / OUTPUT_WIDTH;
+            // Luminance is the signal averaged over a whole subcarrier cycle, which cancels out the colour.
+            let luma = average(centre, SUBCARRIER_PERIOD);
+            let luma = luma + (fringing * (average(centre, SUBCARRIER_PERIOD / 2) - luma));
+            let luma = luma + (self.sharpness * (luma - average(centre, SUBCARRIER_PERIOD * 2)));
+            // Colour is demodulated from what's left once the low frequencies are taken out, plus the luminance
+            // edges that leak into it.
+            let start = centre.saturating_sub(SUBCARRIER_PERIOD / 2);
+            let end = (start + SUBCARRIER_PERIOD).min(SAMPLES_PER_LINE);
+            let (mut i, mut q) = (0.0, 0.0);
+            for sample in start..end {
+                let low = average(sample, SUBCARRIER_PERIOD);
+                let level = (signal[sample] - low) + (artifacts * low);
+                let (cos, sin) = subcarrier[(phase + sample) % SUBCARRIER_PERIOD];
+                i += level * cos;
+                q += level * sin;
+            }
+            let (i, q) = (i / SUBCARRIER_PERIOD as f32, q / SUBCARRIER_PERIOD as f32);
+            yiq_to_rgb(luma, i, q)
+        }).collect()
+    }
+}
+
+pub fn yiq_to_rgb(y : f32, i : f32, q : f32) -> (f32,f32,f32) {
+    (y + (0.946_882 * i) + (0.623_557 * q), y - (0.274_788 * i) - (0.635_691 * q), y - (1.108_545 * i) + (1.709_007 * q))
+}
+
+// Voltage of the composite signal for a pixel (colour index with the emphasis bits above it) at one sample of the
+// subcarrier.
+pub fn composite_level(pixel : u16, phase : usize) -> f32 {
+    let colour = (pixel & 0x0f) as usize;
+    let level = if colour > 13 { 1 } else { ((pixel >> 4) & 3) as usize };
+    let emphasis = (pixel >> 6) & 7;
+    let in_phase = |hue : usize| (hue + phase) % SUBCARRIER_PERIOD < 6;
+    let high = if colour > 12 { LEVELS_LOW[level] } else { LEVELS_HIGH[level] };
+    let low = if colour == 0 { LEVELS_HIGH[level] } else { LEVELS_LOW[level] };
+    let signal = if in_phase(colour) { high } else { low };
+    if (emphasis & 1 != 0 && in_phase(0)) || (emphasis & 2 != 0 && in_phase(4)) || (emphasis & 4 != 0 && in_phase(8)) {
+        signal * EMPHASIS_ATTENUATION
+    }
+    else {
+        signal
+    }
+}
+
+fn to_channel(value : f32) -> u8 {
+    (value.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+#[cfg(test)]
+fn filter_flat(filter : &NtscFilter, colour : u16) -> (u8,u8,u8,u8) {
+    let output = NtscFilter::new_output();
+    filter.apply(&vec![colour;SCREEN_WIDTH * SCREEN_HEIGHT], 0, &output);
+    output.get_color((OUTPUT_WIDTH / 2) as u32, 100)
+}
+
+#[test]
+fn test_ntsc_grays_and_hues() {
+    let filter = NtscFilter::composite();
+    let black = filter_flat(&filter, 0x0f);
+    let white = filter_flat(&filter, 0x30);
+    let red = filter_flat(&filter, 0x16);
+    let blue = filter_flat(&filter, 0x12);
+    let green = filter_flat(&filter, 0x1a);
+    assert!(black.0 < 8 && black.1 < 8 && black.2 < 8);
+    assert!(white.0 > 240 && white.1 > 240 && white.2 > 240);
+    assert!(red.0 > red.1 && red.0 > red.2);
+    assert!(blue.2 > blue.0 && blue.2 > blue.1);
+    assert!(green.1 > green.0 && green.1 > green.2);
+}
+
+#[test]
+fn test_ntsc_emphasis_darkens() {
+    let filter = NtscFilter::composite();
+    let white = filter_flat(&filter, 0x30);
+    let emphasised = filter_flat(&filter, 0x30 | (0b111 << 6));
+    assert!(emphasised.0 < white.0 && emphasised.1 < white.1 && emphasised.2 < white.2);
+}
+
+#[test]
+fn test_ntsc_artifacts_and_merge_fields() {
+    // Alternating black and white columns make colour on a composite TV but not over S-Video.
+    let pixels : Vec<u16> = (0..(SCREEN_WIDTH * SCREEN_HEIGHT)).map(|index| if index % 2 == 0 { 0x0f } else { 0x30 }).collect();
+    let colourfulness = |filter : &NtscFilter, frame : u64| {
+        let output = NtscFilter::new_output();
+        filter.apply(&pixels, frame, &output);
+        (100..500).map(|x| {
+            let (r, g, b, _) = output.get_color(x, 100);
+            (r.max(g).max(b) - r.min(g).min(b)) as u32
+        }).sum::<u32>()
+    };
+    let unmerged = NtscFilter { merge_fields: false, ..NtscFilter::composite() };
+    assert!(colourfulness(&unmerged, 0) > 2 * colourfulness(&NtscFilter::svideo(), 0));
+    let frames = |filter : &NtscFilter| {
+        let (first, second) = (NtscFilter::new_output(), NtscFilter::new_output());
+        filter.apply(&pixels, 0, &first);
+        filter.apply(&pixels, 1, &second);
+        first == second
+    };
+    assert!(!frames(&unmerged));
+    assert!(frames(&NtscFilter::composite()));
+}
@@ -0,0 +1,157 @@
+use crate::img::Bitmap;
+use crate::ppu::{SCREEN_WIDTH,SCREEN_HEIGHT};
+use crate::region::Region;
+use std::str::FromStr;
+use std::fmt;
+
+// The part of the frame a TV would hide behind its bezel. Only what is shown or saved gets cropped, the PPU always
+// renders the whole 256x240 frame.
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Overscan {
+    // Lines and columns hidden at each edge, in PPU pixels.
+    pub top : u16,
+    pub bottom : u16,
+    pub left : u16,
+    pub right : u16
+}
+
+impl Overscan {
+    pub fn none() -> Self {
+        Overscan { top: 0, bottom: 0, left: 0, right: 0 }
+    }
+
+    // NTSC TVs lose about 8 lines at the top and bottom and a tile's width at the sides, where games leave
+    // scrolling garbage and attribute glitches.
+    pub fn ntsc() -> Self {
+        Overscan { top: 8, bottom: 8, left: 8, right: 8 }
+    }
+
+    // PAL TVs show nearly the whole frame, the 2C07 itself blanking the top line and 2 columns at each side.
+    pub fn pal() -> Self {
+        Overscan { top: 1, bottom: 0, left: 2, right: 2 }
+    }
+
+    pub fn for_region(region : Region) -> Self {
+        match region {
+            Region::Ntsc => Overscan::ntsc(),
+            Region::Pal | Region::Dendy => Overscan::pal()
+        }
+    }
+
+    // The visible part of a frame as (left, top, width, height). Frames wider than the PPU's, like the NTSC filter's
+    // output, have the side columns scaled to match.
+    fn visible(&self, frame : &Bitmap) -> Result<(u32,u32,u32,u32),String> {
+        let (width, height) = (frame.width() as u32, frame.height() as u32);
+        let left = (self.left as u32 * width) / SCREEN_WIDTH as u32;
+        let right = (self.right as u32 * width) / SCREEN_WIDTH as u32;
+        let (top, bottom) = ((self.top as u32 * height) / SCREEN_HEIGHT as u32, (self.bottom as u32 * height) / SCREEN_HEIGHT as u32);
+        if left + right >= width || top + bottom >= height {
+            return Err(format!("Overscan {} leaves nothing of the frame visible", self));
+        }
+        Ok((left, top, width - left - right, height - top - bottom))
+    }
+
+    pub fn cropped_size(&self, frame : &Bitmap) -> Result<(u16,u16),String> {
+        let (_, _, width, height) = self.visible(frame)?;
+        Ok((width as u16, height as u16))
+    }
+
+    // Copies the visible part of a frame into a bitmap of the cropped size, which can be kept and reused every frame.
+    pub fn crop_into(&self, frame : &Bitmap, cropped : &Bitmap) -> Result<(),String> {
+        let (left, top, width, height) = self.visible(frame)?;
+        if (cropped.width() as u32, cropped.height() as u32) != (width, height) {
+            return Err(format!("Cropping to {}x{} needs a bitmap that size, got {}x{}", width, height, cropped.width(), cropped.height()));
+        }
+        for y in 0..height {
+            for x in 0..width {
+                cropped.set_color(x, y, frame.get_color(x + left, y + top));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Overscan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{},{},{},{}", self.top, self.bottom, self.left, self.right)
+    }
+}
+
+// Parses "top,bottom,left,right".
+impl FromStr for Overscan {
+    type Err = String;
+
+    fn from_str(edges : &str) -> Result<Self, Self::Err> {
+        let edges : Vec<u16> = edges.split(',').map(|edge| edge.trim().parse::<u16>())
+                                    .collect::<Result<_,_>>().map_err(|e| format!("Invalid overscan {}: {}", edges, e))?;
+        if let [top, bottom, left, right] = edges[..] {
+            Ok(Overscan { top, bottom, left, right })
+        }
+        else {
+            Err(format!("Overscan needs 4 edges (top,bottom,left,right), got {}", edges.len()))
+        }
+    }
+}
+
+#[cfg(test)]
+fn numbered_frame(width : u16) -> Bitmap {
+    let frame = Bitmap::new(width, SCREEN_HEIGHT as u16).unwrap();
+    for y in 0..(SCREEN_HEIGHT as u32) {
+        for x in 0..(width as u32) {
+            frame.set_color(x, y, ((x & 0xff) as u8, y as u8, (x >> 8) as u8, 0xff));
+        }
+    }
+    frame
+}
+
+#[cfg(test)]
+fn crop(overscan : Overscan, frame : &Bitmap) -> Result<Bitmap,String> {
+    let (width, height) = overscan.cropped_size(frame)?;
+    let cropped = Bitmap::new(width, height)?;
+    overscan.crop_into(frame, &cropped)?;
+    Ok(cropped)
+}
+
+#[test]
+fn test_overscan_crops_each_edge() {
+    let frame = numbered_frame(SCREEN_WIDTH as u16);
+    let cropped = crop(Overscan { top: 8, bottom: 4, left: 2, right: 6 }, &frame).unwrap();
+    assert_eq!((cropped.width(), cropped.height()), (248, 228));
+    assert_eq!(cropped.get_color(0, 0), (2, 8, 0, 0xff));
+    assert_eq!(cropped.get_color(247, 227), (249, 235, 0, 0xff));
+    assert!(crop(Overscan::none(), &frame).unwrap() == frame);
+    assert_eq!(frame.get_color(0, 0), (0, 0, 0, 0xff)); // The frame itself is left alone
+    assert!(crop(Overscan { top: 120, bottom: 120, left: 0, right: 0 }, &frame).is_err());
+}
+
+#[test]
+fn test_overscan_scales_columns_of_wide_frames() {
+    let frame = numbered_frame(crate::ntsc::OUTPUT_WIDTH as u16);
+    let cropped = crop(Overscan::ntsc(), &frame).unwrap();
+    let side = (8 * crate::ntsc::OUTPUT_WIDTH / SCREEN_WIDTH) as u16;
+    assert_eq!((cropped.width(), cropped.height()), (crate::ntsc::OUTPUT_WIDTH as u16 - (2 * side), 224));
+    assert_eq!(cropped.get_color(0, 0), (side as u8, 8, 0, 0xff));
+}
+
+#[test]
+fn test_overscan_crop_into_reuses_bitmap() {
+    let cropped = Bitmap::new(240, 224).unwrap();
+    Overscan::ntsc().crop_into(&numbered_frame(SCREEN_WIDTH as u16), &cropped).unwrap();
+    assert_eq!(cropped.get_color(0, 0), (8, 8, 0, 0xff));
+    let frame = numbered_frame(SCREEN_WIDTH as u16);
+    frame.set_color(8, 8, (1, 2, 3, 0xff));
+    Overscan::ntsc().crop_into(&frame, &cropped).unwrap();
+    assert_eq!(cropped.get_color(0, 0), (1, 2, 3, 0xff));
+    assert!(Overscan::pal().crop_into(&frame, &cropped).is_err()); // Wrong size
+}
+
+#[test]
+fn test_overscan_defaults_and_parsing() {
+    assert_eq!(Overscan::for_region(Region::Ntsc), Overscan::ntsc());
+    assert_eq!(Overscan::for_region(Region::Dendy), Overscan::pal());
+    assert_eq!("8, 8, 0, 0".parse::<Overscan>(), Ok(Overscan { top: 8, bottom: 8, left: 0, right: 0 }));
+    assert_eq!(Overscan::ntsc().to_string().parse::<Overscan>(), Ok(Overscan::ntsc()));
+    assert!("8,8,0".parse::<Overscan>().is_err());
+    assert!("8,8,0,x".parse::<Overscan>().is_err());
+}
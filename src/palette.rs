@@ -0,0 +1,247 @@
+use crate::ntsc::{composite_level,yiq_to_rgb,BLACK,WHITE,HUE_OFFSET};
+use std::f32::consts::PI;
+use std::str::FromStr;
+use std::fmt;
+use std::fs;
+
+// Palettes, either read from .pal files or generated from the composite signal the PPU puts out. For generating,
+// every colour is turned into its 12 samples of square wave per subcarrier cycle and decoded to YIQ like a TV would,
+// then the usual picture controls are applied.
+//
+// A .pal file is just RGB triples indexed by NES colour: 64 of them, or 512 when it carries every emphasis
+// combination (the colour index with the emphasis bits above it, as in Ppu::pixels).
+
+const SUBCARRIER_PERIOD : usize = 12;
+const COLOURS : usize = 64;
+const EMPHASIS_COMBINATIONS : usize = 8;
+
+// The 2C03 RGB PPU (PlayChoice-10, Famicom Titler and RGB-modded consoles) has no composite output, its colours
+// come from a lookup table of 3 bit levels per channel.
+const RGB_PPU_LEVELS : [u16;COLOURS] = [
+    0o333, 0o014, 0o006, 0o326, 0o403, 0o503, 0o510, 0o420, 0o320, 0o120, 0o031, 0o040, 0o022, 0o000, 0o000, 0o000,
+    0o555, 0o036, 0o027, 0o407, 0o507, 0o704, 0o700, 0o630, 0o430, 0o140, 0o040, 0o053, 0o044, 0o000, 0o000, 0o000,
+    0o777, 0o357, 0o447, 0o637, 0o707, 0o737, 0o740, 0o750, 0o660, 0o360, 0o070, 0o276, 0o077, 0o000, 0o000, 0o000,
+    0o777, 0o567, 0o657, 0o757, 0o747, 0o755, 0o764, 0o772, 0o773, 0o572, 0o473, 0o574, 0o277, 0o000, 0o000, 0o000
+];
+const KNOWN_SIZES : [usize;2] = [COLOURS * 3, COLOURS * EMPHASIS_COMBINATIONS * 3];
+const PAL_HUE_SHIFT : f32 = -15.0; // The 2C07's colour burst sits a little off the 2C02's.
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum PaletteError {
+    Io(String, String), // File name and what went wrong
+    InvalidLength(usize), // Not a size any .pal file comes in
+    InvalidColourCount(usize) // Only 64 or 512 colours can be written
+}
+
+impl fmt::Display for PaletteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PaletteError::Io(file_name, e) => write!(f, "Failed to access palette {}: {}", file_name, e),
+            PaletteError::InvalidLength(length) => write!(f, "Palette is {} bytes, expected 192 or 1536", length),
+            PaletteError::InvalidColourCount(count) => write!(f, "Palette has {} colours, expected 64 or 512", count)
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct PaletteSettings {
+    pub hue : f32, // Rotation of every colour in degrees.
+    pub saturation : f32, // Multiplies the colour, 0 gives grays.
+    pub contrast : f32, // Multiplies the whole signal.
+    pub brightness : f32, // Added to the luminance, -1 to 1.
+    pub gamma : f32 // Output is raised to 1/gamma, 1 leaves it as decoded.
+}
+
+impl PaletteSettings {
+    pub fn new() -> Self {
+        PaletteSettings { hue: 0.0, saturation: 1.0, contrast: 1.0, brightness: 0.0, gamma: 1.0 }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Preset {
+    Ntsc2C02,
+    Rgb2C03,
+    Pal2C07
+}
+
+impl Preset {
+    // 512 colours, the 64 of the PPU for each of the 8 emphasis combinations.
+    pub fn palette(&self) -> Vec<(u8,u8,u8,u8)> {
+        match self {
+            Preset::Ntsc2C02 => generate(&PaletteSettings::new()),
+            Preset::Rgb2C03 => rgb_ppu_palette(),
+            Preset::Pal2C07 => {
+                // The 2C07 swaps the red and green emphasis bits.
+                let palette = generate(&PaletteSettings { hue: PAL_HUE_SHIFT, ..PaletteSettings::new() });
+                (0..(COLOURS * EMPHASIS_COMBINATIONS)).map(|index| {
+                    let emphasis = index / COLOURS;
+                    let swapped = (emphasis & 4) | ((emphasis & 1) << 1) | ((emphasis & 2) >> 1);
+                    palette[(swapped * COLOURS) + (index % COLOURS)]
+                }).collect()
+            }
+        }
+    }
+}
+
+impl FromStr for Preset {
+    type Err = String;
+
+    fn from_str(name : &str) -> Result<Self, Self::Err> {
+        match name.to_lowercase().as_str() {
+            "2c02" => Ok(Preset::Ntsc2C02),
+            "2c03" => Ok(Preset::Rgb2C03),
+            "2c07" => Ok(Preset::Pal2C07),
+            _ => Err(format!("Unknown palette preset {}, expected 2c02, 2c03 or 2c07", name))
+        }
+    }
+}
+
+pub fn load(file_name : &str) -> Result<Vec<(u8,u8,u8,u8)>, PaletteError> {
+    let buffer = fs::read(file_name).map_err(|e| PaletteError::Io(file_name.to_string(), e.to_string()))?;
+    from_bytes(&buffer)
+}
+
+pub fn from_bytes(buffer : &[u8]) -> Result<Vec<(u8,u8,u8,u8)>, PaletteError> {
+    if KNOWN_SIZES.contains(&buffer.len()) {
+        Ok(buffer.chunks_exact(3).map(|colour| (colour[0], colour[1], colour[2], 0xff)).collect())
+    }
+    else {
+        Err(PaletteError::InvalidLength(buffer.len()))
+    }
+}
+
+pub fn save(file_name : &str, palette : &[(u8,u8,u8,u8)]) -> Result<(), PaletteError> {
+    let buffer = to_bytes(palette)?;
+    fs::write(file_name, buffer).map_err(|e| PaletteError::Io(file_name.to_string(), e.to_string()))
+}
+
+// Alpha is dropped, .pal files having none.
+pub fn to_bytes(palette : &[(u8,u8,u8,u8)]) -> Result<Vec<u8>, PaletteError> {
+    if KNOWN_SIZES.contains(&(palette.len() * 3)) {
+        Ok(palette.iter().flat_map(|&(r, g, b, _)| vec![r, g, b]).collect())
+    }
+    else {
+        Err(PaletteError::InvalidColourCount(palette.len()))
+    }
+}
+
+// Decodes all 512 colours (emphasis bits above the colour index) from the PPU's composite voltage levels.
+pub fn generate(settings : &PaletteSettings) -> Vec<(u8,u8,u8,u8)> {
+    let hue = HUE_OFFSET + (settings.hue * PI / 180.0);
+    (0..(COLOURS * EMPHASIS_COMBINATIONS)).map(|pixel| {
+        let (mut y, mut i, mut q) = (0.0, 0.0, 0.0);
+        for phase in 0..SUBCARRIER_PERIOD {
+            let level = (composite_level(pixel as u16, phase) - BLACK) / (WHITE - BLACK);
+            let angle = (PI * phase as f32 / 6.0) + hue;
+            y += level;
+            i += level * angle.cos();
+            q += level * angle.sin();
+        }
+        let count = SUBCARRIER_PERIOD as f32;
+        let y = ((y / count) * settings.contrast) + settings.brightness;
+        let colour = settings.contrast * settings.saturation / count;
+        let (r, g, b) = yiq_to_rgb(y, i * colour, q * colour);
+        (to_channel(r, settings.gamma), to_channel(g, settings.gamma), to_channel(b, settings.gamma), 0xff)
+    }).collect()
+}
+
+// Emphasis on the RGB PPU drives its channel fully on rather than dimming the other two.
+fn rgb_ppu_palette() -> Vec<(u8,u8,u8,u8)> {
+    (0..(COLOURS * EMPHASIS_COMBINATIONS)).map(|index| {
+        let (levels, emphasis) = (RGB_PPU_LEVELS[index % COLOURS], index / COLOURS);
+        let channel = |shift : u16, bit : usize| if emphasis & bit != 0 { 0xff } else { (((levels >> shift) & 7) * 255 / 7) as u8 };
+        (channel(6, 1), channel(3, 2), channel(0, 4), 0xff)
+    }).collect()
+}
+
+fn to_channel(value : f32, gamma : f32) -> u8 {
+    (value.clamp(0.0, 1.0).powf(1.0 / gamma) * 255.0).round() as u8
+}
+
+#[test]
+fn test_load_palette_file() {
+    let palette = load("palletes/NES Classic (FBX).pal").unwrap();
+    assert_eq!(palette.len(), 64);
+    assert_eq!(palette[0x0f], (0x00, 0x00, 0x00, 0xff));
+    assert!(palette[0x30].0 > 0xf0 && palette[0x30].1 > 0xf0 && palette[0x30].2 > 0xf0);
+    assert!(palette.iter().all(|colour| colour.3 == 0xff));
+    match load("palletes/missing.pal") {
+        Err(PaletteError::Io(file_name, _)) => assert_eq!(file_name, "palletes/missing.pal"),
+        other => panic!("Unexpected result {:?}", other)
+    }
+}
+
+#[test]
+fn test_palette_lengths() {
+    assert_eq!(from_bytes(&[]), Err(PaletteError::InvalidLength(0)));
+    assert_eq!(from_bytes(&[0;191]), Err(PaletteError::InvalidLength(191)));
+    assert_eq!(from_bytes(&[0;195]), Err(PaletteError::InvalidLength(195)));
+    let mut emphasis = vec![0;1536];
+    emphasis[1533..].copy_from_slice(&[1, 2, 3]); // The last colour must not be dropped.
+    let palette = from_bytes(&emphasis).unwrap();
+    assert_eq!(palette.len(), 512);
+    assert_eq!(palette[511], (1, 2, 3, 0xff));
+    assert_eq!(to_bytes(&palette[..100]), Err(PaletteError::InvalidColourCount(100)));
+}
+
+#[test]
+fn test_save_palette_round_trip() {
+    let palette = Preset::Ntsc2C02.palette();
+    let file_name = std::env::temp_dir().join("rustynes_test_palette.pal");
+    let file_name = file_name.to_str().unwrap();
+    save(file_name, &palette).unwrap();
+    assert_eq!(fs::metadata(file_name).unwrap().len(), 1536);
+    assert_eq!(load(file_name).unwrap(), palette);
+    let fixture = fs::read("palletes/NES Classic (FBX).pal").unwrap();
+    assert_eq!(to_bytes(&from_bytes(&fixture).unwrap()).unwrap(), fixture);
+    fs::remove_file(file_name).unwrap();
+}
+
+#[test]
+fn test_generated_palette_grays_and_hues() {
+    let palette = generate(&PaletteSettings::new());
+    assert_eq!(palette.len(), 512);
+    let (black, white, gray) = (palette[0x0f], palette[0x30], palette[0x10]);
+    assert!(black.0 < 8 && black.1 < 8 && black.2 < 8);
+    assert!(white.0 > 240 && white.1 > 240 && white.2 > 240);
+    assert!(gray.0 > black.0 && gray.0 < white.0 && gray.0 == gray.1 && gray.1 == gray.2);
+    let (red, green, blue) = (palette[0x16], palette[0x1a], palette[0x12]);
+    assert!(red.0 > red.1 && red.0 > red.2);
+    assert!(green.1 > green.0 && green.1 > green.2);
+    assert!(blue.2 > blue.0 && blue.2 > blue.1);
+    // Red emphasis keeps red and dims the rest.
+    let emphasised = palette[(1 << 6) | 0x30];
+    assert!(emphasised.0 > emphasised.1 && emphasised.0 > emphasised.2 && emphasised.1 < white.1);
+}
+
+#[test]
+fn test_palette_settings() {
+    let normal = generate(&PaletteSettings::new());
+    let gray = generate(&PaletteSettings { saturation: 0.0, ..PaletteSettings::new() });
+    assert!(gray[0x16].0 == gray[0x16].1 && gray[0x16].1 == gray[0x16].2);
+    let bright = generate(&PaletteSettings { brightness: 0.2, ..PaletteSettings::new() });
+    assert!(bright[0x10].0 > normal[0x10].0);
+    let contrast = generate(&PaletteSettings { contrast: 0.5, ..PaletteSettings::new() });
+    assert!(contrast[0x30].0 < normal[0x30].0);
+    let gamma = generate(&PaletteSettings { gamma: 2.2, ..PaletteSettings::new() });
+    assert!(gamma[0x10].0 > normal[0x10].0 && gamma[0x30] == normal[0x30]);
+    // A hue step of the PPU is 30 degrees of the subcarrier, so turning by 120 degrees moves every colour by four.
+    let rotated = generate(&PaletteSettings { hue: 120.0, ..PaletteSettings::new() });
+    assert!(rotated[0x16] == normal[0x1a] || rotated[0x16] == normal[0x12]);
+}
+
+#[test]
+fn test_palette_presets() {
+    let rgb = Preset::Rgb2C03.palette();
+    assert_eq!(rgb.len(), 512);
+    assert_eq!(rgb[0x30], (0xff, 0xff, 0xff, 0xff));
+    assert_eq!(rgb[0x16], (0xff, 0x00, 0x00, 0xff));
+    assert_eq!(rgb[0x0f], (0x00, 0x00, 0x00, 0xff));
+    assert_eq!(rgb[1 << 6], (0xff, 0x6d, 0x6d, 0xff));
+    let (ntsc, pal) = (Preset::Ntsc2C02.palette(), Preset::Pal2C07.palette());
+    assert!(ntsc[(1 << 6) | 0x20].0 > ntsc[(1 << 6) | 0x20].1);
+    assert!(pal[(1 << 6) | 0x20].1 > pal[(1 << 6) | 0x20].0);
+    assert_eq!("2C03".parse::<Preset>(), Ok(Preset::Rgb2C03));
+    assert!("2c04".parse::<Preset>().is_err());
+}
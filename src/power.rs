@@ -0,0 +1,76 @@
+// Power-on contents of the console and cartridge RAMs. Real hardware powers up with semi-random values,
+// but every policy here is deterministic so movies and tests stay reproducible.
+#[derive(Clone, PartialEq, Debug)]
+pub enum PowerOnRam {
+    Zeros,
+    Ones, // Every byte $FF
+    Pattern(Vec<u8>), // Repeated from the start of each memory
+    Random(u64) // Seeded pseudo-random
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum RamRegion {
+    InternalRam,
+    PrgRam,
+    Nametables,
+    Palette,
+    Oam,
+    ChrRam
+}
+
+impl PowerOnRam {
+    pub fn fill(&self, region : RamRegion, buffer : &mut [u8]) {
+        match self {
+            PowerOnRam::Zeros => buffer.iter_mut().for_each(|byte| *byte = 0),
+            PowerOnRam::Ones => buffer.iter_mut().for_each(|byte| *byte = 0xff),
+            PowerOnRam::Pattern(pattern) => {
+                if pattern.is_empty() {
+                    buffer.iter_mut().for_each(|byte| *byte = 0);
+                }
+                else {
+                    for (byte, value) in buffer.iter_mut().zip(pattern.iter().cycle()) {
+                        *byte = *value;
+                    }
+                }
+            }
+            PowerOnRam::Random(seed) => {
+                // Each region gets its own stream so resizing one memory doesn't reshuffle the others.
+                let mut state = seed ^ ((region as u64 + 1).wrapping_mul(0x9e37_79b9_7f4a_7c15));
+                for chunk in buffer.chunks_mut(8) {
+                    let bytes = splitmix64(&mut state).to_le_bytes();
+                    chunk.copy_from_slice(&bytes[..chunk.len()]);
+                }
+            }
+        }
+    }
+}
+
+fn splitmix64(state : &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+#[test]
+fn test_power_on_pattern_repeats() {
+    let mut buffer = [0u8;6];
+    PowerOnRam::Pattern(vec![0x00, 0xff]).fill(RamRegion::InternalRam, &mut buffer);
+    assert_eq!(buffer, [0x00, 0xff, 0x00, 0xff, 0x00, 0xff]);
+}
+
+#[test]
+fn test_power_on_random_is_deterministic_per_seed() {
+    let mut first = [0u8;37];
+    let mut second = [0u8;37];
+    let mut other_seed = [0u8;37];
+    let mut other_region = [0u8;37];
+    PowerOnRam::Random(1234).fill(RamRegion::InternalRam, &mut first);
+    PowerOnRam::Random(1234).fill(RamRegion::InternalRam, &mut second);
+    PowerOnRam::Random(4321).fill(RamRegion::InternalRam, &mut other_seed);
+    PowerOnRam::Random(1234).fill(RamRegion::PrgRam, &mut other_region);
+    assert_eq!(first, second);
+    assert_ne!(first, other_seed);
+    assert_ne!(first, other_region);
+}
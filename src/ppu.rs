@@ -0,0 +1,1265 @@
+use crate::nes::Nes;
+use crate::power::{PowerOnRam,RamRegion};
+use crate::img::Bitmap;
+use crate::rom::Mirroring;
+use std::cell::{Cell,RefCell};
+
+pub const DOTS_PER_SCANLINE : u16 = 341;
+pub const SCREEN_WIDTH : usize = 256;
+pub const SCREEN_HEIGHT : usize = 240;
+
+// PPUCTRL bits
+pub const CTRL_NAMETABLE : u8 = 0b11;
+pub const CTRL_INCREMENT_32 : u8 = 0b100;
+pub const CTRL_SPRITE_PATTERN_TABLE : u8 = 0b1000;
+pub const CTRL_BACKGROUND_PATTERN_TABLE : u8 = 0b10000;
+pub const CTRL_SPRITE_SIZE_16 : u8 = 0b100000;
+pub const CTRL_NMI_ENABLE : u8 = 0b10000000;
+
+// PPUMASK bits
+pub const MASK_SHOW_BACKGROUND_LEFT : u8 = 0b10;
+pub const MASK_SHOW_SPRITES_LEFT : u8 = 0b100;
+pub const MASK_SHOW_BACKGROUND : u8 = 0b1000;
+pub const MASK_SHOW_SPRITES : u8 = 0b10000;
+pub const MASK_GRAYSCALE : u8 = 0b1;
+pub const MASK_EMPHASIS : u8 = 0b11100000; // Red, green and blue
+
+// PPUSTATUS bits
+pub const STATUS_SPRITE_OVERFLOW : u8 = 0b100000;
+pub const STATUS_SPRITE_ZERO_HIT : u8 = 0b1000000;
+pub const STATUS_VBLANK : u8 = 0b10000000;
+
+// Which back end draws the picture. Dot runs the fetches and shift registers on the dots the hardware does, so
+// mid-scanline register writes and the pattern table address lines mappers watch behave like the real PPU. Scanline
+// draws a whole line at dot 256 and is cheaper, but only sees the registers as they are at that point.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Renderer {
+    Dot,
+    Scanline
+}
+
+#[derive(Clone, Copy)]
+struct SpritePixel {
+    palette_index : u8,
+    behind_background : bool,
+    sprite_zero : bool
+}
+
+pub struct Ppu {
+    pub scanline : Cell<u16>,
+    pub dot : Cell<u16>,
+    pub frame : Cell<u64>, // Number of completed frames since power on.
+    pub ctrl : Cell<u8>, // $2000
+    pub mask : Cell<u8>, // $2001
+    pub status : Cell<u8>, // $2002
+    pub oam_address : Cell<u8>, // $2003
+    pub oam : RefCell<[u8;256]>, // Primary OAM, 64 sprites of 4 bytes.
+    pub secondary_oam : RefCell<[u8;32]>, // Sprites found on the next scanline.
+    pub sprite_count : Cell<u8>,
+    pub sprite_zero_in_range : Cell<bool>, // Secondary OAM starts with sprite 0.
+    pub renderer : Cell<Renderer>,
+    tile_fetch : Cell<(u8,u8,u8,u8)>, // Nametable byte, attribute bits, pattern low and high bytes of the next tile
+    background_shifters : Cell<[u16;4]>, // Pattern low and high, attribute low and high, two tiles each
+    sprite_shifters : RefCell<[(u8,u8,u8,u8);8]>, // Pattern low and high (already flipped), attributes and X of each sprite
+    sprites_fetched : Cell<u8>,
+    sprite_zero_fetched : Cell<bool>,
+    pub v : Cell<u16>, // Current VRAM address (15 bits).
+    pub t : Cell<u16>, // Temporary VRAM address, the top left of the screen during rendering.
+    pub x : Cell<u8>, // Fine X scroll (3 bits).
+    pub w : Cell<bool>, // First or second write toggle shared by $2005 and $2006.
+    pub read_buffer : Cell<u8>, // $2007 reads below the palette are delayed through this buffer.
+    pub latch : Cell<u8>, // PPU data bus latch, read back from write-only registers and undriven status bits.
+    pub ciram : RefCell<[u8;2048]>, // 2KB of console VRAM used for nametables.
+    pub palette : RefCell<[u8;32]>, // Palette RAM, 6 bits per entry.
+    pub pixels : RefCell<Vec<u16>>, // Colour index of every pixel in the frame being drawn, with the emphasis bits above it.
+    pub rgb_palette : RefCell<Vec<(u8,u8,u8,u8)>>, // RGBA for each colour index, or for all 512 index and emphasis combinations.
+    pub screen : Bitmap // Last completed frame.
+}
+
+impl Ppu {
+    pub fn new() -> Self {
+        Ppu { scanline: Cell::new(0), dot: Cell::new(0), frame: Cell::new(0), ctrl: Cell::new(0), mask: Cell::new(0), status: Cell::new(0),
+              oam_address: Cell::new(0), oam: RefCell::new([0;256]), secondary_oam: RefCell::new([0xff;32]),
+              sprite_count: Cell::new(0), sprite_zero_in_range: Cell::new(false), renderer: Cell::new(Renderer::Dot),
+              tile_fetch: Cell::new((0,0,0,0)), background_shifters: Cell::new([0;4]), sprite_shifters: RefCell::new([(0,0,0,0);8]),
+              sprites_fetched: Cell::new(0), sprite_zero_fetched: Cell::new(false), v: Cell::new(0), t: Cell::new(0), x: Cell::new(0), w: Cell::new(false),
+              read_buffer: Cell::new(0), latch: Cell::new(0), ciram: RefCell::new([0;2048]), palette: RefCell::new([0;32]),
+              pixels: RefCell::new(vec![0;SCREEN_WIDTH * SCREEN_HEIGHT]), rgb_palette: RefCell::new(vec![(0,0,0,0xff);64]),
+              screen: Bitmap::new(SCREEN_WIDTH as u16, SCREEN_HEIGHT as u16).expect("Failed to initialze bitmap!") }
+    }
+
+    pub fn fill_power_on_ram(&self, power_on_ram : &PowerOnRam) {
+        power_on_ram.fill(RamRegion::Nametables, &mut self.ciram.borrow_mut()[..]);
+        power_on_ram.fill(RamRegion::Palette, &mut self.palette.borrow_mut()[..]);
+        power_on_ram.fill(RamRegion::Oam, &mut self.oam.borrow_mut()[..]);
+    }
+
+    // What the RESET line clears. OAMADDR, PPUADDR and the memories are left alone.
+    pub fn reset(&self) {
+        self.ctrl.set(0);
+        self.mask.set(0);
+        self.w.set(false);
+        self.t.set(0);
+        self.x.set(0);
+        self.read_buffer.set(0);
+    }
+
+    pub fn rendering_enabled(&self) -> bool {
+        self.mask.get() & (MASK_SHOW_BACKGROUND | MASK_SHOW_SPRITES) != 0
+    }
+
+    // Advances the PPU by one dot.
+    pub fn step(&self, nes : &Nes) {
+        let scanline = self.scanline.get() as usize;
+        let dot = self.dot.get();
+        let visible_line = scanline < SCREEN_HEIGHT;
+        let pre_render_line = scanline == (nes.region.get().scanlines_per_frame() - 1) as usize;
+        match self.renderer.get() {
+            Renderer::Dot => {
+                if visible_line || pre_render_line {
+                    self.render_dot(nes, scanline, dot, visible_line);
+                }
+            }
+            Renderer::Scanline => {
+                if visible_line && dot == 256 {
+                    self.render_scanline(nes, scanline);
+                }
+            }
+        }
+        if visible_line && dot == 256 {
+            self.sprite_count.set(0);
+            if self.rendering_enabled() {
+                self.evaluate_sprites(scanline);
+            }
+        }
+        if scanline == nes.region.get().vblank_scanline() as usize && dot == 1 {
+            self.status.set(self.status.get() | STATUS_VBLANK);
+            if self.ctrl.get() & CTRL_NMI_ENABLE != 0 {
+                nes.nmi_pending.set(true);
+            }
+        }
+        if pre_render_line && dot == 1 {
+            self.status.set(self.status.get() & !(STATUS_VBLANK | STATUS_SPRITE_ZERO_HIT | STATUS_SPRITE_OVERFLOW));
+            self.sprite_count.set(0); // No evaluation happens on the pre-render line so the first line has no sprites.
+        }
+        if self.rendering_enabled() && (visible_line || pre_render_line) {
+            match dot {
+                256 => self.increment_y(),
+                257 => self.v.set((self.v.get() & !0x041f) | (self.t.get() & 0x041f)), // Horizontal scroll bits from t
+                304 if pre_render_line => self.v.set((self.v.get() & !0x7be0) | (self.t.get() & 0x7be0)), // Vertical scroll bits from t
+                _ => {}
+            }
+        }
+        if scanline == SCREEN_HEIGHT && dot == 0 {
+            self.update_screen();
+        }
+        let mut dot = self.dot.get() + 1;
+        if pre_render_line && dot == DOTS_PER_SCANLINE - 1 && self.frame.get() % 2 == 1 && self.rendering_enabled()
+            && nes.region.get().skips_dot_on_odd_frames() {
+            dot = DOTS_PER_SCANLINE; // The last dot of the pre-render line is skipped.
+        }
+        if dot < DOTS_PER_SCANLINE {
+            self.dot.set(dot);
+            return;
+        }
+        self.dot.set(0);
+        let scanline = self.scanline.get() + 1;
+        if scanline < nes.region.get().scanlines_per_frame() {
+            self.scanline.set(scanline);
+        }
+        else {
+            self.scanline.set(0);
+            self.frame.set(self.frame.get() + 1);
+        }
+    }
+
+    // Draws one visible scanline: the background from the scroll position in v, then the sprites picked for this line
+    // by the evaluation on the previous line.
+    fn render_scanline(&self, nes : &Nes, scanline : usize) {
+        let mut background = [0u8;SCREEN_WIDTH]; // Palette RAM index per pixel, 0 where transparent
+        let mut sprites : [Option<SpritePixel>;SCREEN_WIDTH] = [None;SCREEN_WIDTH];
+        let mask = self.mask.get();
+        if mask & MASK_SHOW_BACKGROUND != 0 {
+            self.render_background(nes, &mut background);
+        }
+        if mask & MASK_SHOW_SPRITES != 0 {
+            self.render_sprites(nes, scanline, &mut sprites);
+        }
+        let mut pixels = self.pixels.borrow_mut();
+        let line = &mut pixels[(scanline * SCREEN_WIDTH)..((scanline + 1) * SCREEN_WIDTH)];
+        for (x, pixel) in line.iter_mut().enumerate() {
+            *pixel = self.output_pixel(x, background[x], sprites[x]);
+        }
+    }
+
+    // Combines the background and sprite pixels at x into the colour sent out, applying left column clipping,
+    // sprite priority, sprite-0 hit, grayscale and emphasis.
+    fn output_pixel(&self, x : usize, background : u8, sprite : Option<SpritePixel>) -> u16 {
+        let mask = self.mask.get();
+        let background_index = if x < 8 && mask & MASK_SHOW_BACKGROUND_LEFT == 0 { 0 } else { background };
+        let sprite = if x < 8 && mask & MASK_SHOW_SPRITES_LEFT == 0 { None } else { sprite };
+        let index = match sprite {
+            Some(sprite) => {
+                if sprite.sprite_zero && background_index != 0 && x != 255 {
+                    self.status.set(self.status.get() | STATUS_SPRITE_ZERO_HIT);
+                }
+                if sprite.behind_background && background_index != 0 { background_index } else { sprite.palette_index }
+            }
+            None => background_index
+        };
+        let colour = self.palette.borrow()[index as usize] & self.grayscale_mask();
+        (colour as u16) | (((mask & MASK_EMPHASIS) as u16) << 1)
+    }
+
+    // One dot of the dot renderer: background fetches and shifting on the hardware's schedule, pattern fetches for
+    // the sprites on the next line, and on visible lines one pixel out.
+    fn render_dot(&self, nes : &Nes, scanline : usize, dot : u16, visible_line : bool) {
+        let rendering = self.rendering_enabled();
+        if rendering && ((2..=257).contains(&dot) || (321..=337).contains(&dot)) {
+            self.fetch_background(nes, dot);
+        }
+        if rendering && (257..=320).contains(&dot) {
+            self.fetch_sprite(nes, scanline + 1, dot);
+        }
+        if visible_line && (1..=256).contains(&dot) {
+            let x = (dot - 1) as usize;
+            let pixel = if rendering { self.output_pixel(x, self.background_pixel(), self.sprite_pixel(x)) } else { self.output_pixel(x, 0, None) };
+            self.pixels.borrow_mut()[(scanline * SCREEN_WIDTH) + x] = pixel;
+        }
+    }
+
+    // Each tile takes 8 dots: nametable, attribute, pattern low and pattern high fetches 2 dots apart, then
+    // coarse X is incremented. The tile goes into the low half of the shift registers at the start of the next one.
+    fn fetch_background(&self, nes : &Nes, dot : u16) {
+        let mut shifters = self.background_shifters.get();
+        for shifter in shifters.iter_mut() {
+            *shifter <<= 1;
+        }
+        let (tile_index, attribute, low, high) = self.tile_fetch.get();
+        let v = self.v.get();
+        let pattern_address = |tile_index : u8| {
+            let pattern_table = if self.ctrl.get() & CTRL_BACKGROUND_PATTERN_TABLE != 0 { 0x1000 } else { 0 };
+            pattern_table + ((tile_index as u16) * 16) + ((v >> 12) & 7)
+        };
+        match (dot - 1) % 8 {
+            0 => {
+                shifters[0] = (shifters[0] & 0xff00) | low as u16;
+                shifters[1] = (shifters[1] & 0xff00) | high as u16;
+                shifters[2] = (shifters[2] & 0xff00) | if attribute & 1 != 0 { 0xff } else { 0 };
+                shifters[3] = (shifters[3] & 0xff00) | if attribute & 2 != 0 { 0xff } else { 0 };
+                self.tile_fetch.set((self.read_memory(nes, 0x2000 | (v & 0x0fff)), attribute, low, high));
+            }
+            2 => {
+                let attribute = self.read_memory(nes, 0x23c0 | (v & 0x0c00) | ((v >> 4) & 0x38) | ((v >> 2) & 0x07));
+                let shift = ((v >> 4) & 4) | (v & 2);
+                self.tile_fetch.set((tile_index, (attribute >> shift) & 3, low, high));
+            }
+            4 => self.tile_fetch.set((tile_index, attribute, self.read_memory(nes, pattern_address(tile_index)), high)),
+            6 => self.tile_fetch.set((tile_index, attribute, low, self.read_memory(nes, pattern_address(tile_index) + 8))),
+            7 => self.v.set(increment_coarse_x(v)),
+            _ => {}
+        }
+        self.background_shifters.set(shifters);
+    }
+
+    fn background_pixel(&self) -> u8 {
+        if self.mask.get() & MASK_SHOW_BACKGROUND == 0 {
+            return 0;
+        }
+        let shifters = self.background_shifters.get();
+        let bit = 15 - self.x.get();
+        let colour = ((((shifters[1] >> bit) & 1) << 1) | ((shifters[0] >> bit) & 1)) as u8;
+        let palette = ((((shifters[3] >> bit) & 1) << 1) | ((shifters[2] >> bit) & 1)) as u8;
+        if colour == 0 { 0 } else { (palette << 2) | colour }
+    }
+
+    // Dots 257-320 fetch the patterns of the 8 sprite slots for the next line, the low byte on the 5th dot of each
+    // slot and the high byte on the 7th. Empty slots still fetch tile $FF.
+    fn fetch_sprite(&self, nes : &Nes, line : usize, dot : u16) {
+        let slot = ((dot - 257) / 8) as usize;
+        if dot == 257 {
+            self.sprites_fetched.set(self.sprite_count.get());
+            self.sprite_zero_fetched.set(self.sprite_zero_in_range.get());
+        }
+        let cycle = (dot - 257) % 8;
+        if cycle != 4 && cycle != 6 {
+            return;
+        }
+        let mut sprite = [0xff;4];
+        let used = slot < self.sprites_fetched.get() as usize;
+        if used {
+            sprite.copy_from_slice(&self.secondary_oam.borrow()[(slot * 4)..((slot * 4) + 4)]);
+        }
+        let address = self.sprite_pattern_address(&sprite, line);
+        let pattern = self.read_memory(nes, address.unwrap_or_else(|| self.empty_sprite_address()) + if cycle == 6 { 8 } else { 0 });
+        let pattern = if sprite[2] & 0x40 != 0 { pattern.reverse_bits() } else { pattern }; // Horizontal flip
+        let pattern = if used && address.is_some() { pattern } else { 0 };
+        let mut sprite_shifters = self.sprite_shifters.borrow_mut();
+        let (low, high, _, _) = sprite_shifters[slot];
+        sprite_shifters[slot] = if cycle == 4 { (pattern, high, sprite[2], sprite[3]) } else { (low, pattern, sprite[2], sprite[3]) };
+    }
+
+    fn sprite_pixel(&self, x : usize) -> Option<SpritePixel> {
+        if self.mask.get() & MASK_SHOW_SPRITES == 0 {
+            return None;
+        }
+        let sprite_shifters = self.sprite_shifters.borrow();
+        for (index, (low, high, attributes, sprite_x)) in sprite_shifters.iter().take(self.sprites_fetched.get() as usize).enumerate() {
+            let column = x.wrapping_sub(*sprite_x as usize);
+            if column >= 8 {
+                continue;
+            }
+            let bit = 7 - column;
+            let colour = (((high >> bit) & 1) << 1) | ((low >> bit) & 1);
+            if colour != 0 {
+                return Some(SpritePixel {
+                    palette_index: 0x10 | ((attributes & 3) << 2) | colour,
+                    behind_background: attributes & 0x20 != 0,
+                    sprite_zero: index == 0 && self.sprite_zero_fetched.get()
+                });
+            }
+        }
+        None
+    }
+
+    fn render_background(&self, nes : &Nes, background : &mut [u8;SCREEN_WIDTH]) {
+        let pattern_table = if self.ctrl.get() & CTRL_BACKGROUND_PATTERN_TABLE != 0 { 0x1000 } else { 0 };
+        let mut v = self.v.get();
+        let fine_y = (v >> 12) & 7;
+        let mut fine_x = self.x.get();
+        let mut tile = (0, 0, 0); // Pattern low byte, high byte and palette of the current tile
+        for (x, pixel) in background.iter_mut().enumerate() {
+            if x == 0 || fine_x == 0 {
+                let tile_index = self.read_memory(nes, 0x2000 | (v & 0x0fff)) as u16;
+                let attribute = self.read_memory(nes, 0x23c0 | (v & 0x0c00) | ((v >> 4) & 0x38) | ((v >> 2) & 0x07));
+                let shift = ((v >> 4) & 4) | (v & 2); // Which 16x16 quadrant of the attribute byte
+                let address = pattern_table + (tile_index * 16) + fine_y;
+                tile = (self.read_memory(nes, address), self.read_memory(nes, address + 8), (attribute >> shift) & 3);
+            }
+            let bit = 7 - fine_x;
+            let colour = (((tile.1 >> bit) & 1) << 1) | ((tile.0 >> bit) & 1);
+            *pixel = if colour == 0 { 0 } else { (tile.2 << 2) | colour };
+            fine_x += 1;
+            if fine_x == 8 {
+                fine_x = 0;
+                v = increment_coarse_x(v);
+            }
+        }
+    }
+
+    fn sprite_height(&self) -> u16 {
+        if self.ctrl.get() & CTRL_SPRITE_SIZE_16 != 0 { 16 } else { 8 }
+    }
+
+    fn render_sprites(&self, nes : &Nes, scanline : usize, sprites : &mut [Option<SpritePixel>;SCREEN_WIDTH]) {
+        let secondary_oam = self.secondary_oam.borrow();
+        // Lower OAM entries win, so draw them last.
+        for index in (0..self.sprite_count.get() as usize).rev() {
+            let sprite = &secondary_oam[(index * 4)..((index * 4) + 4)];
+            let attributes = sprite[2];
+            let address = match self.sprite_pattern_address(sprite, scanline) {
+                Some(address) => address,
+                None => continue // Evaluated on a line with a different sprite size.
+            };
+            let low = self.read_memory(nes, address);
+            let high = self.read_memory(nes, address + 8);
+            for column in 0..8 {
+                let x = sprite[3] as usize + column;
+                if x >= SCREEN_WIDTH {
+                    break;
+                }
+                let bit = if attributes & 0x40 != 0 { column } else { 7 - column }; // Horizontal flip
+                let colour = (((high >> bit) & 1) << 1) | ((low >> bit) & 1);
+                if colour != 0 {
+                    sprites[x] = Some(SpritePixel {
+                        palette_index: 0x10 | ((attributes & 3) << 2) | colour,
+                        behind_background: attributes & 0x20 != 0,
+                        sprite_zero: index == 0 && self.sprite_zero_in_range.get()
+                    });
+                }
+            }
+        }
+    }
+
+    // Address of the low pattern byte of the sprite's row on the given line, None if the sprite isn't on it.
+    fn sprite_pattern_address(&self, sprite : &[u8], line : usize) -> Option<u16> {
+        let height = self.sprite_height();
+        let mut row = (line as u16).wrapping_sub(sprite[0] as u16 + 1) & 0xff;
+        if row >= height {
+            return None;
+        }
+        if sprite[2] & 0x80 != 0 {
+            row = height - 1 - row; // Vertical flip
+        }
+        let tile = sprite[1] as u16;
+        if height == 16 {
+            Some(((tile & 1) * 0x1000) + ((tile & 0xfe) * 16) + (if row >= 8 { 16 + row - 8 } else { row }))
+        }
+        else {
+            Some(self.sprite_pattern_table() + (tile * 16) + row)
+        }
+    }
+
+    fn empty_sprite_address(&self) -> u16 {
+        if self.sprite_height() == 16 { 0x1000 + (0xfe * 16) } else { self.sprite_pattern_table() + (0xff * 16) }
+    }
+
+    fn sprite_pattern_table(&self) -> u16 {
+        if self.ctrl.get() & CTRL_SPRITE_PATTERN_TABLE != 0 { 0x1000 } else { 0 }
+    }
+
+    // Picks up to 8 sprites from primary OAM that are on the next scanline. Once 8 are found the hardware keeps
+    // looking for a 9th to set the overflow flag, but wrongly steps through the other bytes of each entry as well.
+    fn evaluate_sprites(&self, scanline : usize) {
+        let height = self.sprite_height();
+        let oam = self.oam.borrow();
+        let mut secondary_oam = self.secondary_oam.borrow_mut();
+        let in_range = |y : u8| (scanline as u16).wrapping_sub(y as u16) < height;
+        let mut count = 0;
+        let mut n = 0;
+        self.sprite_zero_in_range.set(false);
+        while n < 64 && count < 8 {
+            if in_range(oam[n * 4]) {
+                secondary_oam[(count * 4)..((count * 4) + 4)].copy_from_slice(&oam[(n * 4)..((n * 4) + 4)]);
+                if n == 0 {
+                    self.sprite_zero_in_range.set(true);
+                }
+                count += 1;
+            }
+            n += 1;
+        }
+        self.sprite_count.set(count as u8);
+        let mut m = 0;
+        while n < 64 {
+            if in_range(oam[(n * 4) + m]) {
+                self.status.set(self.status.get() | STATUS_SPRITE_OVERFLOW);
+                break;
+            }
+            n += 1;
+            m = (m + 1) & 3;
+        }
+    }
+
+    // End of a visible scanline: move v down one pixel row, wrapping into the nametable below after row 29.
+    fn increment_y(&self) {
+        let v = self.v.get();
+        if v & 0x7000 != 0x7000 {
+            self.v.set(v + 0x1000);
+            return;
+        }
+        let v = v & !0x7000;
+        let coarse_y = (v & 0x03e0) >> 5;
+        let (coarse_y, v) = match coarse_y {
+            29 => (0, v ^ 0x0800),
+            31 => (0, v),
+            _ => (coarse_y + 1, v)
+        };
+        self.v.set((v & !0x03e0) | (coarse_y << 5));
+    }
+
+    // Grayscale mode keeps only the luminance column of the colour.
+    fn grayscale_mask(&self) -> u8 {
+        if self.mask.get() & MASK_GRAYSCALE != 0 { 0x30 } else { 0x3f }
+    }
+
+    fn update_screen(&self) {
+        for (index, pixel) in self.pixels.borrow().iter().enumerate() {
+            self.screen.set_color((index % SCREEN_WIDTH) as u32, (index / SCREEN_WIDTH) as u32, self.colour_to_rgb(*pixel));
+        }
+    }
+
+    // RGBA for a colour index with the emphasis bits above it, as stored in pixels.
+    pub fn colour_to_rgb(&self, pixel : u16) -> (u8,u8,u8,u8) {
+        let rgb_palette = self.rgb_palette.borrow();
+        if rgb_palette.len() >= 512 {
+            rgb_palette[(pixel & 0x1ff) as usize]
+        }
+        else {
+            let rgb = rgb_palette.get((pixel & 0x3f) as usize).copied().unwrap_or((0,0,0,0xff));
+            emphasise(rgb, (pixel >> 6) as u8)
+        }
+    }
+
+    // CPU read of $2000-$3FFF, the registers are mirrored every 8 bytes.
+    pub fn read_register(&self, nes : &Nes, address : u16) -> u8 {
+        let value = match address & 7 {
+            2 => {
+                let status = (self.status.get() & 0xe0) | (self.latch.get() & 0x1f);
+                self.status.set(self.status.get() & !STATUS_VBLANK);
+                self.w.set(false);
+                status
+            }
+            4 => self.oam.borrow()[self.oam_address.get() as usize],
+            7 => {
+                let address = self.v.get() & 0x3fff;
+                let value = if address >= 0x3f00 {
+                    // Palette reads aren't delayed but still refill the buffer with the nametable byte underneath.
+                    self.read_buffer.set(self.read_memory(nes, address - 0x1000));
+                    (self.latch.get() & 0xc0) | self.read_memory(nes, address)
+                }
+                else {
+                    let buffered = self.read_buffer.get();
+                    self.read_buffer.set(self.read_memory(nes, address));
+                    buffered
+                };
+                self.increment_v();
+                value
+            }
+            _ => self.latch.get() // Write-only registers return whatever was last on the PPU bus.
+        };
+        self.latch.set(value);
+        value
+    }
+
+    // CPU write of $2000-$3FFF.
+    pub fn write_register(&self, nes : &Nes, address : u16, value : u8) {
+        self.latch.set(value);
+        match address & 7 {
+            0 => {
+                // Turning NMIs on during vblank raises one straight away.
+                if self.ctrl.get() & CTRL_NMI_ENABLE == 0 && value & CTRL_NMI_ENABLE != 0 && self.status.get() & STATUS_VBLANK != 0 {
+                    nes.nmi_pending.set(true);
+                }
+                self.ctrl.set(value);
+                self.t.set((self.t.get() & 0xf3ff) | (((value & CTRL_NAMETABLE) as u16) << 10));
+            }
+            1 => self.mask.set(value),
+            3 => self.oam_address.set(value),
+            4 => {
+                let oam_address = self.oam_address.get();
+                self.oam.borrow_mut()[oam_address as usize] = value;
+                self.oam_address.set(oam_address.wrapping_add(1));
+            }
+            5 => {
+                if !self.w.get() {
+                    self.t.set((self.t.get() & 0xffe0) | ((value >> 3) as u16));
+                    self.x.set(value & 7);
+                }
+                else {
+                    self.t.set((self.t.get() & 0x8c1f) | (((value & 7) as u16) << 12) | (((value & 0xf8) as u16) << 2));
+                }
+                self.w.set(!self.w.get());
+            }
+            6 => {
+                if !self.w.get() {
+                    self.t.set((self.t.get() & 0x00ff) | (((value & 0x3f) as u16) << 8));
+                }
+                else {
+                    self.t.set((self.t.get() & 0xff00) | value as u16);
+                    self.v.set(self.t.get());
+                }
+                self.w.set(!self.w.get());
+            }
+            7 => {
+                self.write_memory(nes, self.v.get() & 0x3fff, value);
+                self.increment_v();
+            }
+            _ => {} // PPUSTATUS is read-only.
+        }
+    }
+
+    fn increment_v(&self) {
+        let increment = if self.ctrl.get() & CTRL_INCREMENT_32 != 0 { 32 } else { 1 };
+        self.v.set(self.v.get().wrapping_add(increment) & 0x7fff);
+    }
+
+    // PPU address space: pattern tables on the cartridge, nametables in CIRAM and palette RAM.
+    pub fn read_memory(&self, nes : &Nes, address : u16) -> u8 {
+        match address & 0x3fff {
+            0x0000..=0x1fff => nes.cartridge.borrow().as_ref().map_or(0, |cartridge| cartridge.ppu_read(address)),
+            0x2000..=0x3eff => {
+                match nametable_mirroring(nes).ciram_index(address) {
+                    Some(index) => self.ciram.borrow()[index],
+                    None => nes.cartridge.borrow().as_ref().map_or(0, |cartridge| cartridge.nametable_read(address))
+                }
+            }
+            _ => self.palette.borrow()[palette_index(address)] & self.grayscale_mask()
+        }
+    }
+
+    pub fn write_memory(&self, nes : &Nes, address : u16, value : u8) {
+        match address & 0x3fff {
+            0x0000..=0x1fff => {
+                if let Some(cartridge) = nes.cartridge.borrow().as_ref() {
+                    cartridge.ppu_write(address, value);
+                }
+            }
+            0x2000..=0x3eff => {
+                match nametable_mirroring(nes).ciram_index(address) {
+                    Some(index) => self.ciram.borrow_mut()[index] = value,
+                    None => {
+                        if let Some(cartridge) = nes.cartridge.borrow().as_ref() {
+                            cartridge.nametable_write(address, value);
+                        }
+                    }
+                }
+            }
+            _ => self.palette.borrow_mut()[palette_index(address)] = value & 0x3f
+        }
+    }
+}
+
+fn increment_coarse_x(v : u16) -> u16 {
+    if v & 0x001f == 31 {
+        (v & !0x001f) ^ 0x0400 // Wrap into the horizontally adjacent nametable
+    }
+    else {
+        v + 1
+    }
+}
+
+// $3F00-$3FFF mirrors the 32 bytes of palette RAM, and the backdrop entries of the sprite palettes ($3F10, $3F14,
+// $3F18 and $3F1C) are the same memory as the background ones.
+fn palette_index(address : u16) -> usize {
+    let index = (address & 0x1f) as usize;
+    if index & 0x13 == 0x10 { index & 0x0f } else { index }
+}
+
+// For palettes without emphasis colours, dims the channels that aren't emphasised like the PPU does to the video signal.
+fn emphasise(rgb : (u8,u8,u8,u8), emphasis : u8) -> (u8,u8,u8,u8) {
+    if emphasis == 0 {
+        return rgb;
+    }
+    let dim = |channel : u8, bit : u8| if emphasis & bit == 0 { ((channel as u16 * 209) / 256) as u8 } else { channel };
+    (dim(rgb.0, 0b001), dim(rgb.1, 0b010), dim(rgb.2, 0b100), rgb.3)
+}
+
+// The cartridge wires up CIRAM's address lines. With nothing inserted the nametables are laid out side by side,
+// $2800 and $2C00 mirroring $2000 and $2400.
+fn nametable_mirroring(nes : &Nes) -> Mirroring {
+    nes.cartridge.borrow().as_ref().map_or(Mirroring::Vertical, |cartridge| cartridge.mirroring())
+}
+
+#[test]
+fn test_ppuctrl_write_sets_nametable_bits_of_t() {
+    let nes = Nes::new();
+    nes.write(0x2000, 0b11);
+    assert_eq!(nes.ppu.t.get(), 0x0c00);
+    nes.write(0x2000, 0b01);
+    assert_eq!(nes.ppu.t.get(), 0x0400);
+}
+
+#[test]
+fn test_ppustatus_read_clears_vblank_and_write_toggle() {
+    let nes = Nes::new();
+    nes.ppu.status.set(STATUS_VBLANK | STATUS_SPRITE_ZERO_HIT);
+    nes.write(0x2005, 0x10);
+    assert!(nes.ppu.w.get());
+    assert_eq!(nes.read(0x2002) & 0xe0, STATUS_VBLANK | STATUS_SPRITE_ZERO_HIT);
+    assert!(!nes.ppu.w.get());
+    assert_eq!(nes.read(0x2002) & 0xe0, STATUS_SPRITE_ZERO_HIT);
+}
+
+#[test]
+fn test_ppustatus_low_bits_come_from_latch() {
+    let nes = Nes::new();
+    nes.write(0x2001, 0x1f);
+    assert_eq!(nes.read(0x2002), 0x1f);
+}
+
+#[test]
+fn test_write_only_registers_read_back_latch() {
+    let nes = Nes::new();
+    nes.write(0x2000, 0x5a);
+    assert_eq!(nes.read(0x2000), 0x5a);
+    assert_eq!(nes.read(0x2005), 0x5a);
+    assert_eq!(nes.read(0x3ff8), 0x5a); // Mirror of $2000.
+}
+
+#[test]
+fn test_oamdata_write_increments_and_read_does_not() {
+    let nes = Nes::new();
+    nes.write(0x2003, 0xfe);
+    nes.write(0x2004, 0x11);
+    nes.write(0x2004, 0x22);
+    nes.write(0x2004, 0x33);
+    assert_eq!(nes.ppu.oam_address.get(), 0x01);
+    assert_eq!(nes.ppu.oam.borrow()[0xfe], 0x11);
+    assert_eq!(nes.ppu.oam.borrow()[0x00], 0x33);
+    nes.write(0x2003, 0xff);
+    assert_eq!(nes.read(0x2004), 0x22);
+    assert_eq!(nes.read(0x2004), 0x22);
+}
+
+#[test]
+fn test_ppuscroll_writes_fill_t_and_fine_x() {
+    let nes = Nes::new();
+    nes.write(0x2005, 0x7d); // Coarse X 15, fine X 5
+    assert_eq!(nes.ppu.t.get(), 15);
+    assert_eq!(nes.ppu.x.get(), 0b101);
+    nes.write(0x2005, 0xb3); // Coarse Y 22, fine Y 3
+    assert_eq!(nes.ppu.t.get(), (3 << 12) | (22 << 5) | 15);
+    assert!(!nes.ppu.w.get());
+}
+
+#[test]
+fn test_ppuaddr_writes_copy_t_into_v_on_second_write() {
+    let nes = Nes::new();
+    nes.write(0x2006, 0xff);
+    assert_eq!(nes.ppu.t.get(), 0x3f00);
+    assert_eq!(nes.ppu.v.get(), 0);
+    nes.write(0x2006, 0x12);
+    assert_eq!(nes.ppu.v.get(), 0x3f12);
+}
+
+#[test]
+fn test_ppudata_reads_are_buffered_below_palette() {
+    let nes = Nes::new();
+    nes.write(0x2006, 0x20);
+    nes.write(0x2006, 0x00);
+    nes.write(0x2007, 0xaa);
+    nes.write(0x2007, 0xbb);
+    nes.write(0x2006, 0x20);
+    nes.write(0x2006, 0x00);
+    assert_eq!(nes.read(0x2007), 0x00); // Stale buffer contents
+    assert_eq!(nes.read(0x2007), 0xaa);
+    assert_eq!(nes.read(0x2007), 0xbb);
+}
+
+#[test]
+fn test_ppudata_palette_reads_are_immediate() {
+    let nes = Nes::new();
+    nes.ppu.ciram.borrow_mut()[0x701] = 0x77; // Nametable byte underneath $3F01
+    nes.write(0x2006, 0x3f);
+    nes.write(0x2006, 0x01);
+    nes.write(0x2007, 0x2c);
+    nes.write(0x2006, 0x3f);
+    nes.write(0x2006, 0x01);
+    assert_eq!(nes.read(0x2007) & 0x3f, 0x2c);
+    assert_eq!(nes.ppu.read_buffer.get(), 0x77);
+}
+
+#[test]
+fn test_ppudata_increment_by_32() {
+    let nes = Nes::new();
+    nes.write(0x2000, CTRL_INCREMENT_32);
+    nes.write(0x2006, 0x20);
+    nes.write(0x2006, 0x00);
+    nes.write(0x2007, 0x01);
+    assert_eq!(nes.ppu.v.get(), 0x2020);
+    nes.read(0x2007);
+    assert_eq!(nes.ppu.v.get(), 0x2040);
+}
+
+#[test]
+fn test_nametable_mirroring_follows_cartridge() {
+    let nes = Nes::new();
+    nes.insert_cartridge(crate::cartridge::Cartridge::new(crate::rom::test_rom(7, 2, 0)).unwrap());
+    nes.ppu.write_memory(&nes, 0x2c10, 0x55);
+    assert_eq!(nes.ppu.ciram.borrow()[0x010], 0x55);
+    assert_eq!(nes.ppu.read_memory(&nes, 0x2410), 0x55);
+    nes.write(0x8000, 0x10); // Switch to the upper CIRAM bank
+    assert_eq!(nes.ppu.read_memory(&nes, 0x2010), 0x00);
+    nes.ppu.write_memory(&nes, 0x2010, 0x66);
+    assert_eq!(nes.ppu.ciram.borrow()[0x410], 0x66);
+
+    let nes = nes_with_background(); // Horizontal mirroring
+    nes.ppu.write_memory(&nes, 0x2405, 0x77);
+    assert_eq!(nes.ppu.read_memory(&nes, 0x2005), 0x77);
+    assert_eq!(nes.ppu.read_memory(&nes, 0x2805), 0x00);
+}
+
+#[test]
+fn test_palette_ram_mirrors() {
+    let nes = Nes::new();
+    nes.ppu.write_memory(&nes, 0x3f10, 0x21);
+    nes.ppu.write_memory(&nes, 0x3f1c, 0x22);
+    nes.ppu.write_memory(&nes, 0x3f11, 0x23);
+    nes.ppu.write_memory(&nes, 0x3ff1, 0xff);
+    assert_eq!(nes.ppu.read_memory(&nes, 0x3f00), 0x21);
+    assert_eq!(nes.ppu.read_memory(&nes, 0x3f0c), 0x22);
+    assert_eq!(nes.ppu.read_memory(&nes, 0x3f11), 0x3f); // Mirrored every 32 bytes, 6 bits wide
+    assert_eq!(nes.ppu.read_memory(&nes, 0x3f01), 0x00);
+    nes.write(0x2001, MASK_GRAYSCALE);
+    assert_eq!(nes.ppu.read_memory(&nes, 0x3f00), 0x20);
+}
+
+#[test]
+fn test_grayscale_and_emphasis_without_emphasis_palette() {
+    let nes = nes_with_background();
+    nes.ppu.rgb_palette.borrow_mut()[0x00] = (100, 100, 100, 0xff);
+    nes.ppu.palette.borrow_mut()[0] = 0x0c;
+    nes.write(0x2001, MASK_GRAYSCALE | 0x20); // Red emphasis
+    run_ppu_frame(&nes);
+    assert_eq!(pixel(&nes, 0, 0), 0x040);
+    assert_eq!(nes.ppu.screen.get_color(0, 0), (100, 81, 81, 0xff));
+}
+
+#[test]
+fn test_emphasis_palette_is_used_when_available() {
+    let nes = nes_with_background();
+    nes.ppu.rgb_palette.replace(vec![(0,0,0,0xff);512]);
+    nes.ppu.rgb_palette.borrow_mut()[(0b101 << 6) | 0x0f] = (1, 2, 3, 0xff);
+    nes.write(0x2001, 0xa0); // Red and blue emphasis
+    run_ppu_frame(&nes);
+    assert_eq!(pixel(&nes, 0, 0), (0b101 << 6) | 0x0f);
+    assert_eq!(nes.ppu.screen.get_color(0, 0), (1, 2, 3, 0xff));
+}
+
+#[test]
+fn test_ppudata_writes_reach_chr_ram() {
+    let nes = Nes::new();
+    nes.insert_cartridge(crate::cartridge::Cartridge::new(crate::rom::test_rom(2, 2, 0)).unwrap());
+    nes.write(0x2006, 0x00);
+    nes.write(0x2006, 0x10);
+    nes.write(0x2007, 0xaa);
+    nes.write(0x2007, 0xbb);
+    nes.write(0x2006, 0x00);
+    nes.write(0x2006, 0x10);
+    nes.read(0x2007);
+    assert_eq!(nes.read(0x2007), 0xaa);
+    assert_eq!(nes.read(0x2007), 0xbb);
+}
+
+#[cfg(test)]
+fn step_ppu(nes : &Nes, dots : u64) {
+    for _ in 0..dots {
+        nes.ppu.step(nes);
+    }
+}
+
+#[test]
+fn test_vblank_and_nmi_at_scanline_241_dot_1() {
+    let nes = Nes::new();
+    nes.write(0x2000, CTRL_NMI_ENABLE);
+    step_ppu(&nes, (241 * 341) + 1);
+    assert_eq!(nes.ppu.status.get() & STATUS_VBLANK, 0);
+    assert!(!nes.nmi_pending.get());
+    step_ppu(&nes, 1);
+    assert!(nes.ppu.status.get() & STATUS_VBLANK != 0);
+    assert!(nes.nmi_pending.get());
+}
+
+#[test]
+fn test_dendy_vblank_starts_at_scanline_291() {
+    let nes = Nes::new();
+    nes.region.set(crate::region::Region::Dendy);
+    step_ppu(&nes, (291 * 341) + 1);
+    assert_eq!(nes.ppu.status.get() & STATUS_VBLANK, 0);
+    step_ppu(&nes, 1);
+    assert!(nes.ppu.status.get() & STATUS_VBLANK != 0);
+}
+
+#[test]
+fn test_no_nmi_when_disabled() {
+    let nes = Nes::new();
+    step_ppu(&nes, (241 * 341) + 2);
+    assert!(nes.ppu.status.get() & STATUS_VBLANK != 0);
+    assert!(!nes.nmi_pending.get());
+}
+
+#[test]
+fn test_enabling_nmi_during_vblank_raises_nmi() {
+    let nes = Nes::new();
+    step_ppu(&nes, (241 * 341) + 2);
+    nes.write(0x2000, CTRL_NMI_ENABLE);
+    assert!(nes.nmi_pending.get());
+    nes.nmi_pending.set(false);
+    nes.write(0x2000, CTRL_NMI_ENABLE); // Already enabled, no new edge
+    assert!(!nes.nmi_pending.get());
+    nes.write(0x2000, 0);
+    nes.read(0x2002); // Acknowledging vblank stops a later enable from raising an NMI
+    nes.write(0x2000, CTRL_NMI_ENABLE);
+    assert!(!nes.nmi_pending.get());
+}
+
+#[test]
+fn test_pre_render_line_clears_flags_at_dot_1() {
+    let nes = Nes::new();
+    step_ppu(&nes, (241 * 341) + 2);
+    nes.ppu.status.set(nes.ppu.status.get() | STATUS_SPRITE_ZERO_HIT | STATUS_SPRITE_OVERFLOW);
+    step_ppu(&nes, ((261 - 241) * 341) - 1);
+    assert_eq!(nes.ppu.status.get() & 0xe0, 0xe0);
+    step_ppu(&nes, 1);
+    assert_eq!(nes.ppu.status.get() & 0xe0, 0);
+}
+
+#[test]
+fn test_odd_frames_skip_a_dot_when_rendering() {
+    let frame_length = |nes : &Nes| {
+        let mut dots = 0;
+        let frame = nes.ppu.frame.get();
+        while nes.ppu.frame.get() == frame {
+            nes.ppu.step(nes);
+            dots += 1;
+        }
+        dots
+    };
+    let nes = Nes::new();
+    nes.write(0x2001, MASK_SHOW_BACKGROUND);
+    assert_eq!(frame_length(&nes), 262 * 341);
+    assert_eq!(frame_length(&nes), (262 * 341) - 1);
+    assert_eq!(frame_length(&nes), 262 * 341);
+    nes.write(0x2001, 0);
+    assert_eq!(frame_length(&nes), 262 * 341);
+    nes.region.set(crate::region::Region::Pal);
+    nes.write(0x2001, MASK_SHOW_BACKGROUND);
+    assert_eq!(frame_length(&nes), 312 * 341);
+    assert_eq!(frame_length(&nes), 312 * 341);
+}
+
+#[cfg(test)]
+fn nes_with_background() -> Nes {
+    let mut rom = crate::rom::test_rom(0, 1, 1);
+    for row in 0..8 {
+        rom.vrom_banks[0][16 + row] = 0xf0; // Tile 1: left half colour 1
+        rom.vrom_banks[0][32 + row + 8] = 0xff; // Tile 2: solid colour 2
+    }
+    let nes = Nes::new();
+    nes.insert_cartridge(crate::cartridge::Cartridge::new(rom).unwrap());
+    {
+        let mut palette = nes.ppu.palette.borrow_mut();
+        palette[0] = 0x0f;
+        palette[1] = 0x11;
+        palette[2] = 0x12;
+        palette[5] = 0x21;
+        palette[6] = 0x22;
+    }
+    nes
+}
+
+#[cfg(test)]
+fn run_ppu_frame(nes : &Nes) {
+    // Finish the current frame first, the frame after power on has no pre-render line to fetch its first two tiles.
+    for _ in 0..2 {
+        let frame = nes.ppu.frame.get();
+        while nes.ppu.frame.get() == frame {
+            nes.ppu.step(nes);
+        }
+    }
+}
+
+#[cfg(test)]
+fn pixel(nes : &Nes, x : usize, y : usize) -> u16 {
+    nes.ppu.pixels.borrow()[(y * SCREEN_WIDTH) + x]
+}
+
+#[test]
+fn test_background_renders_tiles_and_attributes() {
+    let nes = nes_with_background();
+    {
+        let mut ciram = nes.ppu.ciram.borrow_mut();
+        ciram[0] = 1;
+        ciram[2] = 2; // Third tile, in the second 16x16 attribute quadrant
+        ciram[0x3c0] = 0b0100; // Top right quadrant uses palette 1
+    }
+    nes.write(0x2001, MASK_SHOW_BACKGROUND | MASK_SHOW_BACKGROUND_LEFT);
+    run_ppu_frame(&nes);
+    assert_eq!(pixel(&nes, 0, 0), 0x11);
+    assert_eq!(pixel(&nes, 4, 7), 0x0f);
+    assert_eq!(pixel(&nes, 16, 3), 0x22);
+    assert_eq!(pixel(&nes, 0, 8), 0x0f);
+}
+
+#[test]
+fn test_background_fine_and_coarse_scroll() {
+    let nes = nes_with_background();
+    {
+        let mut ciram = nes.ppu.ciram.borrow_mut();
+        ciram[(2 * 32) + 1] = 1; // Row 2, column 1
+    }
+    nes.write(0x2005, 8 + 2); // Scroll right by one tile and 2 pixels
+    nes.write(0x2005, 16 + 1); // and down by two rows and one pixel
+    nes.write(0x2001, MASK_SHOW_BACKGROUND | MASK_SHOW_BACKGROUND_LEFT);
+    run_ppu_frame(&nes);
+    run_ppu_frame(&nes);
+    assert_eq!(pixel(&nes, 0, 0), 0x11);
+    assert_eq!(pixel(&nes, 1, 6), 0x11);
+    assert_eq!(pixel(&nes, 2, 0), 0x0f);
+    assert_eq!(pixel(&nes, 0, 7), 0x0f);
+}
+
+#[test]
+fn test_background_left_column_clipping() {
+    let nes = nes_with_background();
+    {
+        let mut ciram = nes.ppu.ciram.borrow_mut();
+        ciram[0] = 2;
+        ciram[1] = 2;
+    }
+    nes.write(0x2001, MASK_SHOW_BACKGROUND);
+    run_ppu_frame(&nes);
+    assert_eq!(pixel(&nes, 7, 0), 0x0f);
+    assert_eq!(pixel(&nes, 8, 0), 0x12);
+}
+
+#[test]
+fn test_screen_bitmap_uses_rgb_palette() {
+    let nes = nes_with_background();
+    nes.ppu.rgb_palette.borrow_mut()[0x0f] = (1, 2, 3, 0xff);
+    nes.write(0x2001, MASK_SHOW_BACKGROUND);
+    run_ppu_frame(&nes);
+    assert_eq!(nes.ppu.screen.get_color(100, 100), (1, 2, 3, 0xff));
+}
+
+#[cfg(test)]
+fn nes_with_sprites(sprites : &[[u8;4]]) -> Nes {
+    let nes = nes_with_background();
+    {
+        let mut cartridge = nes.cartridge.borrow_mut();
+        let chr = &mut cartridge.as_mut().unwrap().rom.vrom_banks[0];
+        chr[48] = 0xff; // Tile 3: only the top row, colour 1
+        for row in 0..8 {
+            chr[0x1000 + 32 + row] = 0xff; // Tile $102: solid colour 1
+            chr[0x1000 + 48 + row + 8] = 0xff; // Tile $103: solid colour 2
+        }
+        let mut palette = nes.ppu.palette.borrow_mut();
+        palette[0x11] = 0x31;
+        palette[0x12] = 0x32;
+        palette[0x15] = 0x35;
+        palette[0x16] = 0x36;
+    }
+    {
+        let mut oam = nes.ppu.oam.borrow_mut();
+        oam.iter_mut().for_each(|byte| *byte = 0xff);
+        for (index, sprite) in sprites.iter().enumerate() {
+            oam[(index * 4)..((index * 4) + 4)].copy_from_slice(sprite);
+        }
+    }
+    nes.write(0x2001, MASK_SHOW_BACKGROUND | MASK_SHOW_BACKGROUND_LEFT | MASK_SHOW_SPRITES | MASK_SHOW_SPRITES_LEFT);
+    nes
+}
+
+#[cfg(test)]
+fn run_to_vblank(nes : &Nes) {
+    let frame = nes.ppu.frame.get();
+    while nes.ppu.frame.get() == frame || nes.ppu.scanline.get() as usize != SCREEN_HEIGHT {
+        nes.ppu.step(nes);
+    }
+}
+
+#[test]
+fn test_sprites_are_drawn_one_line_below_y_with_flips() {
+    let nes = nes_with_sprites(&[[9, 1, 0, 20], [9, 1, 0x40, 40], [29, 3, 0x81, 60]]);
+    run_to_vblank(&nes);
+    assert_eq!(pixel(&nes, 20, 9), 0x0f);
+    assert_eq!(pixel(&nes, 20, 10), 0x31);
+    assert_eq!(pixel(&nes, 23, 17), 0x31);
+    assert_eq!(pixel(&nes, 24, 10), 0x0f);
+    assert_eq!(pixel(&nes, 20, 18), 0x0f);
+    assert_eq!(pixel(&nes, 40, 10), 0x0f); // Horizontally flipped
+    assert_eq!(pixel(&nes, 44, 10), 0x31);
+    assert_eq!(pixel(&nes, 60, 30), 0x0f); // Vertically flipped, using sprite palette 1
+    assert_eq!(pixel(&nes, 60, 37), 0x35);
+}
+
+#[test]
+fn test_sprite_priority_and_ordering() {
+    let nes = nes_with_sprites(&[[7, 2, 0x20, 24], [49, 2, 0, 100], [49, 2, 1, 104], [7, 2, 1, 26]]);
+    nes.ppu.ciram.borrow_mut()[32 + 3] = 1; // Left half opaque at x 24 to 27, line 8 to 15
+    run_to_vblank(&nes);
+    assert_eq!(pixel(&nes, 24, 8), 0x11); // Sprite behind the background
+    assert_eq!(pixel(&nes, 26, 8), 0x11); // The sprite behind still hides the higher numbered sprite in front
+    assert_eq!(pixel(&nes, 30, 8), 0x32); // Background transparent
+    assert_eq!(pixel(&nes, 32, 8), 0x36);
+    assert_eq!(pixel(&nes, 104, 50), 0x32); // Lower OAM index wins
+    assert_eq!(pixel(&nes, 110, 50), 0x36);
+}
+
+#[test]
+fn test_sprites_8x16() {
+    let nes = nes_with_sprites(&[[19, 3, 0, 0], [19, 3, 0x80, 16]]);
+    nes.write(0x2000, CTRL_SPRITE_SIZE_16);
+    run_to_vblank(&nes);
+    assert_eq!(pixel(&nes, 0, 20), 0x31);
+    assert_eq!(pixel(&nes, 7, 35), 0x32);
+    assert_eq!(pixel(&nes, 0, 36), 0x0f);
+    assert_eq!(pixel(&nes, 16, 20), 0x32);
+    assert_eq!(pixel(&nes, 16, 35), 0x31);
+}
+
+#[test]
+fn test_sprite_zero_hit() {
+    let nes = nes_with_sprites(&[[9, 2, 0, 8]]);
+    nes.ppu.ciram.borrow_mut()[32] = 2;
+    nes.ppu.ciram.borrow_mut()[32 + 1] = 2;
+    run_to_vblank(&nes);
+    assert!(nes.ppu.status.get() & STATUS_SPRITE_ZERO_HIT != 0);
+    nes.ppu.step(&nes);
+    while nes.ppu.scanline.get() != 0 {
+        nes.ppu.step(&nes);
+    }
+    assert_eq!(nes.ppu.status.get() & STATUS_SPRITE_ZERO_HIT, 0); // Cleared on the pre-render line
+}
+
+#[test]
+fn test_sprite_zero_hit_not_at_x_255_or_in_clipped_column() {
+    let nes = nes_with_sprites(&[[9, 2, 0, 255]]);
+    nes.ppu.ciram.borrow_mut()[32 + 31] = 2;
+    run_to_vblank(&nes);
+    assert_eq!(nes.ppu.status.get() & STATUS_SPRITE_ZERO_HIT, 0);
+
+    let nes = nes_with_sprites(&[[9, 2, 0, 0]]);
+    nes.ppu.ciram.borrow_mut()[32] = 2;
+    nes.write(0x2001, MASK_SHOW_BACKGROUND | MASK_SHOW_SPRITES | MASK_SHOW_SPRITES_LEFT);
+    run_to_vblank(&nes);
+    assert_eq!(nes.ppu.status.get() & STATUS_SPRITE_ZERO_HIT, 0);
+}
+
+#[test]
+fn test_only_eight_sprites_per_line_and_overflow() {
+    let sprites : Vec<[u8;4]> = (0..9).map(|index| [9, 2, 0, index * 8]).collect();
+    let nes = nes_with_sprites(&sprites);
+    run_to_vblank(&nes);
+    assert_eq!(pixel(&nes, 56, 10), 0x32);
+    assert_eq!(pixel(&nes, 64, 10), 0x0f);
+    assert!(nes.ppu.status.get() & STATUS_SPRITE_OVERFLOW != 0);
+}
+
+#[test]
+fn test_sprite_overflow_hardware_bug() {
+    // After 8 sprites are found the evaluation also steps through the entry's bytes, so sprite 9's tile number is
+    // taken as its Y coordinate.
+    let mut sprites : Vec<[u8;4]> = (0..8).map(|index| [10, 2, 0, index * 8]).collect();
+    sprites.push([200, 0, 0, 0]);
+    sprites.push([200, 10, 0, 0]);
+    let nes = nes_with_sprites(&sprites);
+    run_to_vblank(&nes);
+    assert!(nes.ppu.status.get() & STATUS_SPRITE_OVERFLOW != 0);
+
+    // and a real 9th sprite can be missed.
+    let mut sprites : Vec<[u8;4]> = (0..8).map(|index| [10, 2, 0, index * 8]).collect();
+    sprites.push([200, 0, 0, 0]);
+    sprites.push([10, 0xff, 0xff, 0xff]);
+    let nes = nes_with_sprites(&sprites);
+    run_to_vblank(&nes);
+    assert_eq!(nes.ppu.status.get() & STATUS_SPRITE_OVERFLOW, 0);
+}
+
+#[cfg(test)]
+fn render_random_scene(renderer : Renderer, seed : u64, ctrl : u8, mask : u8) -> (Vec<u16>, u8) {
+    let random = PowerOnRam::Random(seed);
+    let mut rom = crate::rom::test_rom(0, 1, 1);
+    random.fill(RamRegion::PrgRam, &mut rom.vrom_banks[0]); // Random tiles
+    let nes = Nes::with_power_on_ram(random);
+    nes.insert_cartridge(crate::cartridge::Cartridge::new(rom).unwrap());
+    nes.ppu.renderer.set(renderer);
+    {
+        let mut oam = nes.ppu.oam.borrow_mut();
+        for sprite in oam.chunks_mut(4) {
+            sprite[0] %= 232; // Keep most sprites on screen
+        }
+    }
+    nes.write(0x2000, ctrl);
+    nes.write(0x2005, (seed * 37) as u8);
+    nes.write(0x2005, (seed * 11) as u8 % 240);
+    nes.write(0x2001, mask);
+    run_ppu_frame(&nes);
+    let pixels = nes.ppu.pixels.borrow().clone();
+    run_to_vblank(&nes);
+    (pixels, nes.ppu.status.get() & (STATUS_SPRITE_ZERO_HIT | STATUS_SPRITE_OVERFLOW))
+}
+
+#[test]
+fn test_dot_and_scanline_renderers_draw_identical_frames() {
+    let settings = [
+        (0, MASK_SHOW_BACKGROUND | MASK_SHOW_SPRITES | MASK_SHOW_BACKGROUND_LEFT | MASK_SHOW_SPRITES_LEFT),
+        (CTRL_NAMETABLE | CTRL_BACKGROUND_PATTERN_TABLE, MASK_SHOW_BACKGROUND | MASK_SHOW_SPRITES),
+        (CTRL_SPRITE_SIZE_16, MASK_SHOW_BACKGROUND | MASK_SHOW_SPRITES | MASK_SHOW_SPRITES_LEFT | MASK_GRAYSCALE),
+        (CTRL_SPRITE_PATTERN_TABLE, MASK_SHOW_SPRITES | MASK_SHOW_SPRITES_LEFT | MASK_EMPHASIS),
+        (0, MASK_SHOW_BACKGROUND | MASK_SHOW_BACKGROUND_LEFT),
+        (0, 0)
+    ];
+    for seed in 1..4 {
+        for (ctrl, mask) in settings.iter() {
+            let dot = render_random_scene(Renderer::Dot, seed, *ctrl, *mask);
+            let scanline = render_random_scene(Renderer::Scanline, seed, *ctrl, *mask);
+            assert!(dot == scanline, "Frames differ with seed {}, PPUCTRL {:02x} and PPUMASK {:02x}", seed, ctrl, mask);
+        }
+    }
+}
+
+#[cfg(test)]
+fn run_to_dot(nes : &Nes, scanline : u16, dot : u16) {
+    while nes.ppu.scanline.get() != scanline || nes.ppu.dot.get() != dot {
+        nes.ppu.step(nes);
+    }
+}
+
+// Draws frames from nestest.nes's tiles, menu text and code bytes, with the register writes games make during a frame:
+// scroll splits in hblank, a forced blank to update the palette, a pattern table switch and sprites turned off.
+// The frames are hashed with FNV-1a. The CPU can't run the ROM's own code yet, so the writes are scripted, and the frames
+// aren't the ones the ROM draws on a console.
+#[cfg(test)]
+fn nestest_frame_hashes(renderer : Renderer) -> Vec<u64> {
+    let rom = crate::rom::Rom::load("roms/nestest.nes".to_string(), &crate::palette::Preset::Ntsc2C02.palette()).unwrap();
+    let nes = Nes::new();
+    nes.insert_cartridge(crate::cartridge::Cartridge::new(rom).unwrap());
+    nes.ppu.renderer.set(renderer);
+    run_ppu_frame(&nes);
+    let menu_text = 0xc306;
+    for (nametable, text) in [(0x2000, menu_text), (0x2800, menu_text + 0x100)].iter() {
+        nes.write(0x2006, (nametable >> 8) as u8);
+        nes.write(0x2006, 0x00);
+        for offset in 0..0x400 {
+            nes.write(0x2007, nes.read(text + offset));
+        }
+    }
+    nes.write(0x2006, 0x3f);
+    nes.write(0x2006, 0x00);
+    for offset in 0..0x20 {
+        nes.write(0x2007, nes.read(0xc000 + offset) & 0x3f);
+    }
+    nes.write(0x2003, 0);
+    for offset in 0..0x100 {
+        let value = nes.read(0xc100 + offset);
+        nes.write(0x2004, if offset % 4 == 0 { value % 232 } else { value });
+    }
+    let mut hashes = Vec::new();
+    for frame in 0..4u16 {
+        run_to_dot(&nes, 241, 10);
+        nes.write(0x2000, (frame & 1) as u8 | CTRL_SPRITE_PATTERN_TABLE);
+        nes.write(0x2005, (frame * 75) as u8);
+        nes.write(0x2005, (frame * 20) as u8);
+        nes.write(0x2001, MASK_SHOW_BACKGROUND | MASK_SHOW_SPRITES | MASK_SHOW_BACKGROUND_LEFT | MASK_SHOW_SPRITES_LEFT);
+        // Status bar style split: $2006, $2005, $2005, $2006 in hblank.
+        run_to_dot(&nes, 60, 260);
+        nes.write(0x2006, 0x08);
+        nes.write(0x2005, 64);
+        nes.write(0x2005, (frame * 3) as u8);
+        nes.write(0x2006, 0x20 | (frame * 3) as u8);
+        run_to_dot(&nes, 110, 270);
+        nes.write(0x2001, 0);
+        nes.write(0x2006, 0x3f);
+        nes.write(0x2006, 0x03);
+        nes.write(0x2007, 0x16 + frame as u8);
+        run_to_dot(&nes, 118, 280);
+        nes.write(0x2006, 0x21);
+        nes.write(0x2006, 0xe0);
+        nes.write(0x2001, MASK_SHOW_BACKGROUND | MASK_SHOW_SPRITES | MASK_SHOW_BACKGROUND_LEFT);
+        run_to_dot(&nes, 160, 300);
+        nes.write(0x2000, CTRL_BACKGROUND_PATTERN_TABLE);
+        run_to_dot(&nes, 200, 290);
+        nes.write(0x2001, MASK_SHOW_BACKGROUND | 0b0100_0000); // Green emphasis, sprites off
+        run_to_dot(&nes, SCREEN_HEIGHT as u16, 0);
+        let pixels = nes.ppu.pixels.borrow();
+        hashes.push(pixels.iter().fold(0xcbf2_9ce4_8422_2325, |hash : u64, pixel| (hash ^ *pixel as u64).wrapping_mul(0x0100_0000_01b3)));
+    }
+    hashes
+}
+
+#[test]
+fn test_dot_and_scanline_renderers_agree_on_scripted_nestest_frames() {
+    let dot = nestest_frame_hashes(Renderer::Dot);
+    assert_eq!(dot, nestest_frame_hashes(Renderer::Scanline));
+    // Recorded from the dot renderer to catch regressions in both, not checked against hardware.
+    assert_eq!(dot, vec![0x910f_1fb7_4c31_6ae4, 0xca03_0847_f694_5142, 0xca71_1851_d442_9cb6, 0xd166_b7f1_8ed8_9358]);
+}
+
+#[test]
+fn test_dot_renderer_sees_mid_scanline_writes() {
+    let nes = nes_with_background();
+    nes.ppu.ciram.borrow_mut().iter_mut().take(0x3c0).for_each(|tile| *tile = 2);
+    nes.write(0x2001, MASK_SHOW_BACKGROUND | MASK_SHOW_BACKGROUND_LEFT);
+    run_ppu_frame(&nes);
+    while nes.ppu.scanline.get() != 100 || nes.ppu.dot.get() != 129 {
+        nes.ppu.step(&nes);
+    }
+    nes.write(0x2001, 0); // Background off from the middle of line 100
+    while nes.ppu.scanline.get() != 101 {
+        nes.ppu.step(&nes);
+    }
+    assert_eq!(pixel(&nes, 127, 100), 0x12);
+    assert_eq!(pixel(&nes, 128, 100), 0x0f);
+    assert_eq!(pixel(&nes, 127, 99), 0x12);
+}
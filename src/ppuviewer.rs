@@ -0,0 +1,194 @@
+use crate::nes::Nes;
+use crate::img::Bitmap;
+use crate::ppu::{CTRL_BACKGROUND_PATTERN_TABLE,CTRL_SPRITE_PATTERN_TABLE,CTRL_SPRITE_SIZE_16,SCREEN_WIDTH,SCREEN_HEIGHT};
+
+// Debug views of the PPU's memories. They read through the PPU's address space so the cartridge's current banks and
+// mirroring are shown, and colours come from the live palette RAM.
+
+const SCROLL_OUTLINE : (u8,u8,u8,u8) = (0xff, 0x00, 0xff, 0xff);
+const OAM_CELL_SIZE : u32 = 32; // Each sprite preview sits in the middle of a 32x32 cell, 8 to a row.
+
+// Palette RAM index (0-31) to RGBA, colour 0 of every palette being the backdrop.
+fn palette_rgb(nes : &Nes, index : u8) -> (u8,u8,u8,u8) {
+    let index = if index & 3 == 0 { 0 } else { index };
+    nes.ppu.colour_to_rgb(nes.ppu.palette.borrow()[index as usize] as u16)
+}
+
+// Draws one 8 pixel row of a tile from its two pattern planes.
+fn draw_tile_row(bitmap : &Bitmap, nes : &Nes, x : u32, y : u32, planes : (u8,u8), palette : u8, flip : bool) {
+    for column in 0..8 {
+        let bit = if flip { column } else { 7 - column };
+        let colour = (((planes.1 >> bit) & 1) << 1) | ((planes.0 >> bit) & 1);
+        bitmap.set_color(x + column as u32, y, palette_rgb(nes, (palette << 2) | colour));
+    }
+}
+
+fn tile_planes(nes : &Nes, address : u16) -> (u8,u8) {
+    (nes.ppu.read_memory(nes, address), nes.ppu.read_memory(nes, address + 8))
+}
+
+// Both pattern tables side by side (256x128), coloured with one of the 8 palettes in palette RAM.
+pub fn pattern_tables(nes : &Nes, palette : u8) -> Bitmap {
+    let bitmap = Bitmap::new(256, 128).expect("Failed to initialze bitmap!");
+    for tile in 0..512u16 {
+        let x = (((tile / 256) * 128) + ((tile % 16) * 8)) as u32;
+        let y = (((tile % 256) / 16) * 8) as u32;
+        for row in 0..8 {
+            draw_tile_row(&bitmap, nes, x, y + row as u32, tile_planes(nes, (tile * 16) + row), palette & 7, false);
+        }
+    }
+    bitmap
+}
+
+// All four nametables (512x480) with the area the scroll registers show outlined. The outline wraps around the
+// edges like the scroll does.
+pub fn nametables(nes : &Nes) -> Bitmap {
+    let bitmap = Bitmap::new(512, 480).expect("Failed to initialze bitmap!");
+    let pattern_table = if nes.ppu.ctrl.get() & CTRL_BACKGROUND_PATTERN_TABLE != 0 { 0x1000 } else { 0 };
+    for nametable in 0..4u16 {
+        let base = 0x2000 + (nametable * 0x400);
+        for tile in 0..960u16 {
+            let (column, row) = (tile % 32, tile / 32);
+            let tile_index = nes.ppu.read_memory(nes, base + tile) as u16;
+            let attribute = nes.ppu.read_memory(nes, base + 0x3c0 + ((row / 4) * 8) + (column / 4));
+            let palette = (attribute >> (((row & 2) << 1) | (column & 2))) & 3;
+            let x = (((nametable & 1) * 256) + (column * 8)) as u32;
+            let y = (((nametable >> 1) * 240) + (row * 8)) as u32;
+            for fine_y in 0..8 {
+                draw_tile_row(&bitmap, nes, x, y + fine_y as u32, tile_planes(nes, pattern_table + (tile_index * 16) + fine_y), palette, false);
+            }
+        }
+    }
+    // Top left of the screen from t and fine X, which is what gets copied into v for each frame.
+    let t = nes.ppu.t.get() as u32;
+    let scroll_x = (((t >> 10) & 1) * 256) + ((t & 0x1f) * 8) + nes.ppu.x.get() as u32;
+    let scroll_y = (((t >> 11) & 1) * 240) + (((t >> 5) & 0x1f) * 8) + ((t >> 12) & 7);
+    let (width, height) = (SCREEN_WIDTH as u32, SCREEN_HEIGHT as u32);
+    for offset in 0..width {
+        bitmap.set_color((scroll_x + offset) % 512, scroll_y % 480, SCROLL_OUTLINE);
+        bitmap.set_color((scroll_x + offset) % 512, (scroll_y + height - 1) % 480, SCROLL_OUTLINE);
+    }
+    for offset in 0..height {
+        bitmap.set_color(scroll_x % 512, (scroll_y + offset) % 480, SCROLL_OUTLINE);
+        bitmap.set_color((scroll_x + width - 1) % 512, (scroll_y + offset) % 480, SCROLL_OUTLINE);
+    }
+    bitmap
+}
+
+// The 32 palette RAM entries as 16x16 swatches, background palettes on the top row and sprite palettes below.
+pub fn palettes(nes : &Nes) -> Bitmap {
+    let bitmap = Bitmap::new(256, 32).expect("Failed to initialze bitmap!");
+    for index in 0..32u32 {
+        let rgb = nes.ppu.colour_to_rgb(nes.ppu.palette.borrow()[index as usize] as u16);
+        for y in 0..16 {
+            for x in 0..16 {
+                bitmap.set_color(((index % 16) * 16) + x, ((index / 16) * 16) + y, rgb);
+            }
+        }
+    }
+    bitmap
+}
+
+// Previews of the 64 sprites in OAM order, 8 to a row, drawn with their flips and palettes. Colour 0 shows the backdrop.
+pub fn oam(nes : &Nes) -> Bitmap {
+    let bitmap = Bitmap::new(256, 256).expect("Failed to initialze bitmap!");
+    let ctrl = nes.ppu.ctrl.get();
+    let height = if ctrl & CTRL_SPRITE_SIZE_16 != 0 { 16 } else { 8 };
+    let oam = nes.ppu.oam.borrow();
+    for (index, sprite) in oam.chunks(4).enumerate() {
+        let (tile, attributes) = (sprite[1] as u16, sprite[2]);
+        let x = ((index as u32 % 8) * OAM_CELL_SIZE) + 12;
+        let y = ((index as u32 / 8) * OAM_CELL_SIZE) + ((OAM_CELL_SIZE - height as u32) / 2);
+        for row in 0..height {
+            let flipped_row = if attributes & 0x80 != 0 { height - 1 - row } else { row };
+            let address = if height == 16 {
+                ((tile & 1) * 0x1000) + ((tile & 0xfe) * 16) + (if flipped_row >= 8 { 16 + flipped_row - 8 } else { flipped_row })
+            }
+            else {
+                (if ctrl & CTRL_SPRITE_PATTERN_TABLE != 0 { 0x1000 } else { 0 }) + (tile * 16) + flipped_row
+            };
+            draw_tile_row(&bitmap, nes, x, y + row as u32, tile_planes(nes, address), 4 | (attributes & 3), attributes & 0x40 != 0);
+        }
+    }
+    bitmap
+}
+
+// One line per sprite for printing next to the OAM view.
+pub fn oam_listing(nes : &Nes) -> Vec<String> {
+    nes.ppu.oam.borrow().chunks(4).enumerate().map(|(index, sprite)| {
+        format!("{:02}: X {:3} Y {:3} Tile ${:02X} Palette {} {}{}{}", index, sprite[3], sprite[0], sprite[1], sprite[2] & 3,
+                if sprite[2] & 0x20 != 0 { "Behind " } else { "" },
+                if sprite[2] & 0x40 != 0 { "H-Flip " } else { "" },
+                if sprite[2] & 0x80 != 0 { "V-Flip" } else { "" })
+    }).collect()
+}
+
+#[cfg(test)]
+fn viewer_nes() -> Nes {
+    let mut rom = crate::rom::test_rom(0, 1, 1);
+    for row in 0..8 {
+        rom.vrom_banks[0][16 + row] = 0xff; // Tile 1: solid colour 1
+        rom.vrom_banks[0][32 + row + 8] = 0xf0; // Tile 2: left half colour 2
+    }
+    let nes = Nes::new();
+    nes.insert_cartridge(crate::cartridge::Cartridge::new(rom).unwrap());
+    nes.ppu.rgb_palette.replace((0..64).map(|colour| (colour, colour, colour, 0xff)).collect());
+    {
+        let mut palette = nes.ppu.palette.borrow_mut();
+        palette[0] = 0x0f;
+        palette[1] = 0x01;
+        palette[6] = 0x06;
+        palette[0x12] = 0x22;
+        palette[0x15] = 0x25;
+    }
+    nes
+}
+
+#[test]
+fn test_pattern_tables_use_chosen_palette() {
+    let nes = viewer_nes();
+    let bitmap = pattern_tables(&nes, 1);
+    assert_eq!(bitmap.get_color(0, 0), (0x0f, 0x0f, 0x0f, 0xff));
+    assert_eq!(bitmap.get_color(16, 0), (0x06, 0x06, 0x06, 0xff));
+    assert_eq!(bitmap.get_color(20, 0), (0x0f, 0x0f, 0x0f, 0xff));
+    assert_eq!(pattern_tables(&nes, 0).get_color(8, 7), (0x01, 0x01, 0x01, 0xff));
+}
+
+#[test]
+fn test_nametables_follow_mirroring_and_outline_scroll() {
+    let nes = viewer_nes(); // Horizontal mirroring
+    nes.ppu.write_memory(&nes, 0x2021, 1); // Row 1, column 1
+    nes.write(0x2005, 16);
+    nes.write(0x2005, 0);
+    let bitmap = nametables(&nes);
+    assert_eq!(bitmap.get_color(8, 8), (0x01, 0x01, 0x01, 0xff));
+    assert_eq!(bitmap.get_color(256 + 8, 8), (0x01, 0x01, 0x01, 0xff)); // $2400 mirrors $2000
+    assert_eq!(bitmap.get_color(8, 240 + 8), (0x0f, 0x0f, 0x0f, 0xff));
+    assert_eq!(bitmap.get_color(16, 100), SCROLL_OUTLINE);
+    assert_eq!(bitmap.get_color(15, 100), (0x0f, 0x0f, 0x0f, 0xff));
+    assert_eq!(bitmap.get_color(16 + 255, 239), SCROLL_OUTLINE);
+}
+
+#[test]
+fn test_palette_view_shows_every_entry() {
+    let nes = viewer_nes();
+    let bitmap = palettes(&nes);
+    assert_eq!(bitmap.get_color(16, 0), (0x01, 0x01, 0x01, 0xff));
+    assert_eq!(bitmap.get_color((2 * 16) + 15, 16 + 15), (0x22, 0x22, 0x22, 0xff));
+}
+
+#[test]
+fn test_oam_view_draws_sprites_with_flips() {
+    let nes = viewer_nes();
+    {
+        let mut oam = nes.ppu.oam.borrow_mut();
+        oam[0..4].copy_from_slice(&[0, 2, 0x00, 0]);
+        oam[4..8].copy_from_slice(&[0, 2, 0x41, 0]);
+    }
+    let bitmap = oam(&nes);
+    assert_eq!(bitmap.get_color(12, 12), (0x22, 0x22, 0x22, 0xff));
+    assert_eq!(bitmap.get_color(12 + 7, 12), (0x0f, 0x0f, 0x0f, 0xff));
+    assert_eq!(bitmap.get_color(32 + 12, 12), (0x0f, 0x0f, 0x0f, 0xff));
+    assert_eq!(bitmap.get_color(32 + 12 + 7, 12), (0x00, 0x00, 0x00, 0xff)); // Sprite palette 1 colour 2 is unset
+    assert!(oam_listing(&nes)[1].starts_with("01: X   0 Y   0 Tile $02 Palette 1 H-Flip"));
+}
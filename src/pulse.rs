@@ -0,0 +1,208 @@
+use crate::apu::{Envelope,LengthCounter};
+use std::cell::Cell;
+
+// Square wave channels at $4000-$4003 and $4004-$4007.
+
+// Waveforms for the 4 duty cycles (12.5%, 25%, 50% and 25% negated), in the order the sequencer steps through them.
+const DUTY_TABLE : [[u8;8];4] = [
+    [0, 1, 0, 0, 0, 0, 0, 0],
+    [0, 1, 1, 0, 0, 0, 0, 0],
+    [0, 1, 1, 1, 1, 0, 0, 0],
+    [1, 0, 0, 1, 1, 1, 1, 1]
+];
+
+// Bends the channel's period up or down every few half frames.
+pub struct Sweep {
+    enabled : Cell<bool>,
+    period : Cell<u8>,
+    negate : Cell<bool>,
+    shift : Cell<u8>,
+    divider : Cell<u8>,
+    reload : Cell<bool>
+}
+
+impl Sweep {
+    pub fn new() -> Self {
+        Sweep { enabled: Cell::new(false), period: Cell::new(0), negate: Cell::new(false), shift: Cell::new(0), divider: Cell::new(0), reload: Cell::new(false) }
+    }
+
+    // EPPP NSSS
+    pub fn write(&self, value : u8) {
+        self.enabled.set(value & 0x80 != 0);
+        self.period.set((value >> 4) & 7);
+        self.negate.set(value & 0x08 != 0);
+        self.shift.set(value & 7);
+        self.reload.set(true);
+    }
+}
+
+pub struct Pulse {
+    pulse_one : bool, // Pulse 1 negates with ones' complement, so it sweeps down one further than pulse 2.
+    duty : Cell<u8>,
+    sequence_step : Cell<u8>,
+    timer_period : Cell<u16>,
+    timer : Cell<u16>,
+    pub envelope : Envelope,
+    pub sweep : Sweep,
+    pub length_counter : LengthCounter
+}
+
+impl Pulse {
+    pub fn new(pulse_one : bool) -> Self {
+        Pulse { pulse_one, duty: Cell::new(0), sequence_step: Cell::new(0), timer_period: Cell::new(0), timer: Cell::new(0),
+                envelope: Envelope::new(), sweep: Sweep::new(), length_counter: LengthCounter::new() }
+    }
+
+    // Register 0-3 of the channel.
+    pub fn write_register(&self, register : u16, value : u8) {
+        match register {
+            0 => {
+                self.duty.set(value >> 6);
+                self.length_counter.set_halted(value & 0x20 != 0);
+                self.envelope.write(value);
+            }
+            1 => self.sweep.write(value),
+            2 => self.timer_period.set((self.timer_period.get() & 0x700) | value as u16),
+            _ => {
+                self.timer_period.set((self.timer_period.get() & 0xff) | (((value & 7) as u16) << 8));
+                self.length_counter.load(value);
+                self.sequence_step.set(0);
+                self.envelope.restart();
+            }
+        }
+    }
+
+    // Clocked every APU cycle (two CPU cycles). The sequencer counts down through the duty waveform.
+    pub fn clock_timer(&self) {
+        if self.timer.get() == 0 {
+            self.timer.set(self.timer_period.get());
+            self.sequence_step.set((self.sequence_step.get() + 7) % 8);
+        }
+        else {
+            self.timer.set(self.timer.get() - 1);
+        }
+    }
+
+    // Period the sweep would move to.
+    pub fn sweep_target(&self) -> u16 {
+        let period = self.timer_period.get();
+        let change = period >> self.sweep.shift.get();
+        if self.sweep.negate.get() {
+            period.saturating_sub(change + if self.pulse_one { 1 } else { 0 })
+        }
+        else {
+            period + change
+        }
+    }
+
+    // Periods under 8 and sweep targets past $7FF silence the channel, even with the sweep disabled.
+    pub fn is_muted(&self) -> bool {
+        self.timer_period.get() < 8 || self.sweep_target() > 0x7ff
+    }
+
+    // Clocked by the frame counter every half frame.
+    pub fn clock_sweep(&self) {
+        let sweep = &self.sweep;
+        if sweep.divider.get() == 0 && sweep.enabled.get() && sweep.shift.get() > 0 && !self.is_muted() {
+            self.timer_period.set(self.sweep_target());
+        }
+        if sweep.divider.get() == 0 || sweep.reload.get() {
+            sweep.divider.set(sweep.period.get());
+            sweep.reload.set(false);
+        }
+        else {
+            sweep.divider.set(sweep.divider.get() - 1);
+        }
+    }
+
+    pub fn timer_period(&self) -> u16 {
+        self.timer_period.get()
+    }
+
+    // Current level, 0-15.
+    pub fn output(&self) -> u8 {
+        let high = DUTY_TABLE[self.duty.get() as usize][self.sequence_step.get() as usize] == 1;
+        if high && self.length_counter.is_active() && !self.is_muted() {
+            self.envelope.output()
+        }
+        else {
+            0
+        }
+    }
+}
+
+#[cfg(test)]
+fn playing_pulse(pulse_one : bool, period : u16) -> Pulse {
+    let pulse = Pulse::new(pulse_one);
+    pulse.length_counter.set_enabled(true);
+    pulse.write_register(0, 0x9f); // 50% duty, constant volume 15
+    pulse.write_register(2, (period & 0xff) as u8);
+    pulse.write_register(3, 0x08 | (period >> 8) as u8);
+    pulse
+}
+
+#[test]
+fn test_pulse_duty_sequence() {
+    let pulse = playing_pulse(false, 8);
+    let mut waveform = Vec::new();
+    for _ in 0..8 {
+        for _ in 0..9 {
+            pulse.clock_timer();
+        }
+        waveform.push(pulse.output());
+    }
+    // Stepping down from 0 goes 7, 6, ... 0 through the 50% waveform.
+    assert_eq!(waveform, vec![0, 0, 0, 15, 15, 15, 15, 0]);
+    pulse.write_register(0, 0x1f); // 12.5%
+    assert_eq!(pulse.output(), 0);
+    pulse.write_register(3, 0x08); // Restarts the sequence
+    for _ in 0..(9 * 7) {
+        pulse.clock_timer();
+    }
+    assert_eq!(pulse.output(), 15);
+}
+
+#[test]
+fn test_pulse_sweep_ones_complement_difference() {
+    let (one, two) = (playing_pulse(true, 0x100), playing_pulse(false, 0x100));
+    one.sweep.write(0x89); // Enabled, period 0, negate, shift 1
+    two.sweep.write(0x89);
+    assert_eq!(one.sweep_target(), 0x7f);
+    assert_eq!(two.sweep_target(), 0x80);
+    one.clock_sweep();
+    two.clock_sweep();
+    assert_eq!(one.timer_period(), 0x7f);
+    assert_eq!(two.timer_period(), 0x80);
+}
+
+#[test]
+fn test_pulse_sweep_divider_and_upward_sweep() {
+    let pulse = playing_pulse(false, 0x100);
+    pulse.sweep.write(0xa1); // Enabled, period 2, shift 1
+    pulse.clock_sweep(); // Divider was 0 so the period changes, then the divider reloads
+    assert_eq!(pulse.timer_period(), 0x180);
+    pulse.clock_sweep();
+    pulse.clock_sweep();
+    assert_eq!(pulse.timer_period(), 0x180);
+    pulse.clock_sweep();
+    assert_eq!(pulse.timer_period(), 0x240);
+}
+
+#[test]
+fn test_pulse_muting() {
+    let pulse = playing_pulse(false, 7);
+    pulse.clock_timer();
+    assert!(pulse.is_muted());
+    let pulse = playing_pulse(false, 0x600);
+    pulse.sweep.write(0x02); // Shift 2: target $780
+    assert!(!pulse.is_muted());
+    pulse.sweep.write(0x01); // Disabled, shift 1: target $900 still mutes
+    assert!(pulse.is_muted());
+    assert_eq!(pulse.output(), 0);
+    pulse.clock_sweep();
+    assert_eq!(pulse.timer_period(), 0x600);
+    pulse.sweep.write(0x09); // Negated targets never overflow
+    assert!(!pulse.is_muted());
+    pulse.sweep.write(0x00); // Shift 0 still compares period + period
+    assert!(pulse.is_muted());
+}
@@ -0,0 +1,156 @@
+use crate::rom::Rom;
+use std::str::FromStr;
+
+// Noise channel periods in CPU cycles. PAL's are shorter to keep the pitches close on its slower CPU.
+const NOISE_PERIODS_NTSC : [u16;16] = [4, 8, 16, 32, 64, 96, 128, 160, 202, 254, 380, 508, 762, 1016, 2034, 4068];
+const NOISE_PERIODS_PAL : [u16;16] = [4, 8, 14, 30, 60, 88, 118, 148, 188, 236, 354, 472, 708, 944, 1890, 3778];
+// DMC output rates in CPU cycles per bit.
+const DMC_RATES_NTSC : [u16;16] = [428, 380, 340, 320, 286, 254, 226, 214, 190, 160, 142, 128, 106, 84, 72, 54];
+const DMC_RATES_PAL : [u16;16] = [398, 354, 316, 298, 276, 236, 210, 198, 176, 148, 132, 118, 98, 78, 66, 50];
+// CPU cycles after a $4017 write at which the frame counter's five steps fall.
+const FRAME_COUNTER_STEPS_NTSC : [u32;5] = [7457, 14913, 22371, 29829, 37281];
+const FRAME_COUNTER_STEPS_PAL : [u32;5] = [8313, 16627, 24939, 33253, 41565];
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Region {
+    Ntsc,
+    Pal,
+    Dendy
+}
+
+impl Region {
+    // Region from the header: NES 2.0 timing field, or the iNES PAL flag.
+    pub fn from_rom(rom : &Rom) -> Region {
+        if rom.is_dendy {
+            Region::Dendy
+        }
+        else if rom.is_pal {
+            Region::Pal
+        }
+        else {
+            Region::Ntsc
+        }
+    }
+
+    pub fn master_clock_hz(&self) -> f64 {
+        match *self {
+            Region::Ntsc => 236_250_000.0 / 11.0,
+            Region::Pal | Region::Dendy => 26_601_712.0
+        }
+    }
+
+    pub fn cpu_clock_hz(&self) -> f64 {
+        self.master_clock_hz() / self.cpu_divider() as f64
+    }
+
+    // Master clock ticks per CPU cycle. NTSC runs off a 21.477272 MHz crystal, PAL and Dendy off 26.601712 MHz.
+    pub fn cpu_divider(&self) -> u64 {
+        match *self {
+            Region::Ntsc => 12,
+            Region::Pal => 16,
+            Region::Dendy => 15
+        }
+    }
+
+    // Master clock ticks per PPU dot, giving 3 dots per CPU cycle on NTSC and Dendy and 3.2 on PAL.
+    pub fn ppu_divider(&self) -> u64 {
+        match *self {
+            Region::Ntsc => 4,
+            Region::Pal | Region::Dendy => 5
+        }
+    }
+
+    // Dendy keeps the NTSC length of vblank by putting its extra 50 lines before it instead of after.
+    pub fn vblank_scanline(&self) -> u16 {
+        match *self {
+            Region::Ntsc | Region::Pal => 241,
+            Region::Dendy => 291
+        }
+    }
+
+    // Frames per second: 60.0988 on NTSC, where every other frame is a dot short, and 50.007 on PAL and Dendy.
+    pub fn frame_rate(&self) -> f64 {
+        let mut dots_per_frame = (self.scanlines_per_frame() as f64) * (crate::ppu::DOTS_PER_SCANLINE as f64);
+        if self.skips_dot_on_odd_frames() {
+            dots_per_frame -= 0.5;
+        }
+        self.master_clock_hz() / ((self.ppu_divider() as f64) * dots_per_frame)
+    }
+
+    pub fn scanlines_per_frame(&self) -> u16 {
+        match *self {
+            Region::Ntsc => 262,
+            Region::Pal | Region::Dendy => 312
+        }
+    }
+
+    // Dendy clones copy the NTSC 2A03's APU, tables and frame counter included, only the clock driving it is slower.
+    pub fn noise_periods(&self) -> &'static [u16;16] {
+        match *self {
+            Region::Ntsc | Region::Dendy => &NOISE_PERIODS_NTSC,
+            Region::Pal => &NOISE_PERIODS_PAL
+        }
+    }
+
+    pub fn dmc_rates(&self) -> &'static [u16;16] {
+        match *self {
+            Region::Ntsc | Region::Dendy => &DMC_RATES_NTSC,
+            Region::Pal => &DMC_RATES_PAL
+        }
+    }
+
+    pub fn frame_counter_steps(&self) -> &'static [u32;5] {
+        match *self {
+            Region::Ntsc | Region::Dendy => &FRAME_COUNTER_STEPS_NTSC,
+            Region::Pal => &FRAME_COUNTER_STEPS_PAL
+        }
+    }
+
+    // NTSC shortens every other frame by one dot while rendering is enabled. PAL and Dendy frames are always full length.
+    pub fn skips_dot_on_odd_frames(&self) -> bool {
+        *self == Region::Ntsc
+    }
+}
+
+impl FromStr for Region {
+    type Err = String;
+
+    fn from_str(name : &str) -> Result<Region,String> {
+        match name.to_lowercase().as_str() {
+            "ntsc" => Ok(Region::Ntsc),
+            "pal" => Ok(Region::Pal),
+            "dendy" => Ok(Region::Dendy),
+            _ => Err(format!("Unknown region {}, expected ntsc, pal or dendy!", name))
+        }
+    }
+}
+
+#[test]
+fn test_region_frame_rates() {
+    assert!((Region::Ntsc.frame_rate() - 60.0988).abs() < 0.0001);
+    assert!((Region::Pal.frame_rate() - 50.007).abs() < 0.001);
+    assert!((Region::Dendy.frame_rate() - 50.007).abs() < 0.001);
+}
+
+#[test]
+fn test_region_from_header_and_name() {
+    assert_eq!(Region::from_rom(&crate::rom::test_rom(0, 1, 1)), Region::Ntsc);
+    let mut buffer = vec![0x4e, 0x45, 0x53, 0x1a, 1, 1, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0];
+    buffer.extend(vec![0;16384 + 8192]);
+    assert_eq!(Region::from_rom(&Rom::from_bytes(&buffer, &[(0,0,0,0xff);64]).unwrap()), Region::Pal);
+    buffer[7] = 0x08; // NES 2.0, timing in byte 12
+    buffer[9] = 0;
+    buffer[12] = 3;
+    assert_eq!(Region::from_rom(&Rom::from_bytes(&buffer, &[(0,0,0,0xff);64]).unwrap()), Region::Dendy);
+    assert_eq!("PAL".parse::<Region>(), Ok(Region::Pal));
+    assert!("secam".parse::<Region>().is_err());
+}
+
+#[test]
+fn test_region_dendy_uses_ntsc_apu_tables() {
+    assert_eq!(Region::Dendy.noise_periods(), Region::Ntsc.noise_periods());
+    assert_ne!(Region::Pal.noise_periods(), Region::Ntsc.noise_periods());
+    assert_eq!(Region::Dendy.dmc_rates(), Region::Ntsc.dmc_rates());
+    assert_ne!(Region::Pal.dmc_rates(), Region::Ntsc.dmc_rates());
+    assert_eq!(Region::Dendy.frame_counter_steps(), Region::Ntsc.frame_counter_steps());
+}
@@ -7,21 +7,58 @@ use std::fmt;
 use std::vec::Vec;
 
 
+#[derive(Clone, Copy, PartialEq, Debug)]
 pub enum Mirroring {
     Horizontal = 0,
-    Vertical = 1
+    Vertical = 1,
+    SingleScreenA = 2, // Every nametable is the first 1KB of CIRAM
+    SingleScreenB = 3, // Every nametable is the second 1KB of CIRAM
+    FourScreen = 4 // Cartridge provides 2KB more VRAM so all four nametables are distinct
+}
+
+impl Mirroring {
+    // Where a nametable address ($2000-$2FFF and its mirror up to $3EFF) lands in the 2KB of CIRAM, or None
+    // when it's in the cartridge's own VRAM.
+    pub fn ciram_index(&self, address : u16) -> Option<usize> {
+        let offset = (address & 0x3ff) as usize;
+        let nametable = (address >> 10) & 3;
+        match *self {
+            Mirroring::Horizontal => Some((((nametable >> 1) as usize) << 10) | offset),
+            Mirroring::Vertical => Some((((nametable & 1) as usize) << 10) | offset),
+            Mirroring::SingleScreenA => Some(offset),
+            Mirroring::SingleScreenB => Some(0x400 | offset),
+            Mirroring::FourScreen if nametable < 2 => Some(((nametable as usize) << 10) | offset),
+            Mirroring::FourScreen => None
+        }
+    }
 }
 
 impl fmt::Display for Mirroring {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", match *self {
            Mirroring::Horizontal => "Horizontal Mirroing",
-           Mirroring::Vertical => "Vertical Mirroing"
+           Mirroring::Vertical => "Vertical Mirroing",
+           Mirroring::SingleScreenA => "Single Screen Mirroring (lower bank)",
+           Mirroring::SingleScreenB => "Single Screen Mirroring (upper bank)",
+           Mirroring::FourScreen => "Four Screen VRAM"
         })
        
     }
 }
 
+#[test]
+fn test_mirroring_ciram_index() {
+    assert_eq!(Mirroring::Horizontal.ciram_index(0x2400), Some(0x000));
+    assert_eq!(Mirroring::Horizontal.ciram_index(0x2c05), Some(0x405));
+    assert_eq!(Mirroring::Vertical.ciram_index(0x2800), Some(0x000));
+    assert_eq!(Mirroring::Vertical.ciram_index(0x2405), Some(0x405));
+    assert_eq!(Mirroring::SingleScreenA.ciram_index(0x2c05), Some(0x005));
+    assert_eq!(Mirroring::SingleScreenB.ciram_index(0x2005), Some(0x405));
+    assert_eq!(Mirroring::FourScreen.ciram_index(0x2405), Some(0x405));
+    assert_eq!(Mirroring::FourScreen.ciram_index(0x2800), None);
+    assert_eq!(Mirroring::Vertical.ciram_index(0x3405), Some(0x405)); // $3000-$3EFF mirrors $2000-$2EFF
+}
+
 pub struct Rom {
     pub number_of_rom_banks : u8,
     pub number_of_vrom_banks : u8,
@@ -33,16 +70,19 @@ pub struct Rom {
     pub is_vs_system_cartidge : bool,
     pub number_of_8k_ram_banks : u8,
     pub is_pal : bool,
+    pub is_dendy : bool,
     pub trainer : RefCell<Option<[u8;512]>>,
     pub rom_banks : Vec<[u8;16384]>, // PRG ROM banks
     pub vrom_banks : Vec<[u8;8192]>, // CHR ROM banks
-    pub vrom_bmps : Vec<Bitmap> // CHR ROM represented as a bitmap image
+    pub chr_ram_size : usize, // Bytes of CHR RAM on the cartridge, only when there are no CHR ROM banks
+    pub vrom_bmps : Vec<Bitmap>, // CHR ROM represented as a bitmap image
+    pub palette : Vec<(u8,u8,u8,u8)> // RGBA colours the ROM was loaded with, indexed by NES colour
 
 
 }
 
 impl Rom {
-    pub fn load(file_name : String, palette_file : String) -> Result<Rom,String> {
+    pub fn load(file_name : String, palette : &[(u8,u8,u8,u8)]) -> Result<Rom,String> {
         println!("Reading {}", file_name);
         if let Ok(file_handle) = &mut File::open(file_name) {
             let mut buffer : Vec<u8> = Vec::new();
@@ -50,122 +90,118 @@ impl Rom {
                 Err(e.description().to_string())
             }
             else {
-                let magic_string_valid = buffer[0] == 0x4e && buffer[1] == 0x45 && buffer[2] == 0x53 && buffer[3] == 0x1a;
-                if magic_string_valid {
-                        let number_of_rom_banks = buffer[4];
-                        let number_of_vrom_banks = buffer[5];
-                        let six_flag = buffer[6];
-                        let mirroring = if six_flag & 0x1 == 0x1 {
-                            Mirroring::Vertical
-                        }
-                        else {
-                            Mirroring::Horizontal
-                        };
-                        let has_battery_packed_ram = six_flag & 0x2 == 0x2;
-                        let has_trainer = six_flag & 0x4 == 0x4;
-                        let has_four_screen_vram_layout = six_flag & 0x8 == 0x8;
-                        let low_nibble = (six_flag & 0xf0) >> 4;
-                        let seven_flag = buffer[7];
-                        let is_vs_system_cartidge = seven_flag & 0x1 == 0x1;
-                        let high_nibble = (seven_flag & 0xf0) >> 4;
-                        let mapper_number = (high_nibble << 4) | low_nibble;
-                        let number_of_8k_ram_banks = if buffer[8] == 0 {
-                            1
-                        }
-                        else {
-                            buffer[8]
-                        };
-                        let is_pal = buffer[9] == 1;
-                        let trainer = if has_trainer {
-                            let mut trainer_data : [u8;512] = [0;512];
-                            trainer_data.copy_from_slice(&buffer[16..(16+512)]);
-                            RefCell::from(Some(trainer_data))
-                        }
-                        else {
-                            RefCell::new(None)
-                        };
-                        let mut rom_banks : Vec<[u8;16384]> = Vec::new();
-                        for i in 0..(number_of_rom_banks as usize) {
-                            let mut rom_bank : [u8;16384] = [0;16384];
-                            if has_trainer {
-                                let start = (i*16384)+16+512;
-                                let end = (i*16384)+16+16384+512;
-                                rom_bank.copy_from_slice(&buffer[start..end]);
-                            }
-                            else {
-                                let start = ((i*16384)+16) as usize;
-                                let end = ((i*16384)+16+16384) as usize;
-                                rom_bank.copy_from_slice(&buffer[start..end]);
-                            }
-                            rom_banks.push(rom_bank);
-                        }
-                        let mut vrom_banks : Vec<[u8;8192]> = Vec::new();
-                        let mut vrom_bmps : Vec<Bitmap> = Vec::new();
-                        let offset = (number_of_rom_banks as usize) * 16384;
-                        for i in 0..(number_of_vrom_banks as usize) {
-                            let mut vrom_bank : [u8;8192] = [0;8192];
-                            if has_trainer {
-                                let start = (i*8192)+16+512+offset;
-                                let end = (i*8192)+16+8192+512+offset;
-                                vrom_bank.copy_from_slice(&buffer[start..end]);
-                            }
-                            else {
-                                let start = ((i*8192)+16+offset) as usize;
-                                let end = ((i*8192)+16+8192+offset) as usize;
-                                vrom_bank.copy_from_slice(&buffer[start..end]);
-                            }
-                            vrom_bmps.push(chr_to_bitmap(vrom_bank, [0xc,0x17,0x28,0x39], palette_file.clone()));
-                            vrom_banks.push(vrom_bank);
-                        }
-
-                        println!("Successfully Loaded!\nROM Debug Info:\nNumber of PRG ROM Pages (16K each): {}\nNumber of CHR ROM Pages (8K each): {}\nMapper Type: {}\nPAL: {}\nHas Trainer: {}\nMirroring: {}", 
-                        number_of_rom_banks, 
-                        number_of_vrom_banks, 
-                        mapper_number, is_pal,
-                        has_trainer,
-                        mirroring);
-
-                        Ok(Rom {
-                            number_of_rom_banks: number_of_rom_banks, number_of_vrom_banks: number_of_vrom_banks, 
-                            mirroring: mirroring, has_battery_packed_ram: has_battery_packed_ram, has_trainer: has_trainer, 
-                            has_four_screen_vram_layout: has_four_screen_vram_layout, rom_mapper_type: mapper_number,
-                            is_vs_system_cartidge: is_vs_system_cartidge, number_of_8k_ram_banks: number_of_8k_ram_banks, is_pal: is_pal,
-                            trainer: trainer, rom_banks: rom_banks, vrom_banks: vrom_banks, vrom_bmps: vrom_bmps
-                        })    
-                }
-                else {
-                    Err(String::from("NES file magic 4 byte string is missing!"))
-                }
+                Rom::from_bytes(&buffer, palette)
             }
          }
         else {
             Err(String::from("Failed to open NES Rom!"))
         }
     }
-}
 
-fn load_palette(file_name : String) -> Result<Vec<(u8,u8,u8,u8)>, String> { // Vector of 4-tuple or RGBA
-    let mut palette : Vec<(u8,u8,u8,u8)> = Vec::new();
-    if let Ok(file_handle) = &mut File::open(file_name) {
-            let mut buffer : Vec<u8> = Vec::new();
-            if let Err(e) = file_handle.read_to_end(&mut buffer) {
-                Err(e.description().to_string())
+    pub fn from_bytes(buffer : &[u8], palette : &[(u8,u8,u8,u8)]) -> Result<Rom,String> {
+        let magic_string_valid = buffer.len() >= 16 && buffer[0] == 0x4e && buffer[1] == 0x45 && buffer[2] == 0x53 && buffer[3] == 0x1a;
+        if magic_string_valid {
+            let number_of_rom_banks = buffer[4];
+            let number_of_vrom_banks = buffer[5];
+            let six_flag = buffer[6];
+            let mirroring = if six_flag & 0x1 == 0x1 {
+                Mirroring::Vertical
+            }
+            else {
+                Mirroring::Horizontal
+            };
+            let has_battery_packed_ram = six_flag & 0x2 == 0x2;
+            let has_trainer = six_flag & 0x4 == 0x4;
+            let has_four_screen_vram_layout = six_flag & 0x8 == 0x8;
+            let low_nibble = (six_flag & 0xf0) >> 4;
+            let seven_flag = buffer[7];
+            let is_vs_system_cartidge = seven_flag & 0x1 == 0x1;
+            let high_nibble = (seven_flag & 0xf0) >> 4;
+            let mapper_number = (high_nibble << 4) | low_nibble;
+            let number_of_8k_ram_banks = if buffer[8] == 0 {
+                1
+            }
+            else {
+                buffer[8]
+            };
+            let is_nes2 = seven_flag & 0x0c == 0x08;
+            let is_pal = if is_nes2 { buffer[12] & 0x03 == 1 } else { buffer[9] == 1 };
+            let is_dendy = is_nes2 && buffer[12] & 0x03 == 3;
+            let chr_ram_size = if number_of_vrom_banks != 0 {
+                0
+            }
+            else if is_nes2 && buffer[11] & 0x0f != 0 {
+                64 << (buffer[11] & 0x0f) // NES 2.0 gives the size as a shift count
             }
             else {
-                for i in (0..(buffer.len()-3)).filter(|x| x % 3 == 0) {
-                    palette.push((buffer[i], buffer[i+1], buffer[i+2], 0xff));
+                8192
+            };
+            let trainer = if has_trainer {
+                let mut trainer_data : [u8;512] = [0;512];
+                trainer_data.copy_from_slice(&buffer[16..(16+512)]);
+                RefCell::from(Some(trainer_data))
+            }
+            else {
+                RefCell::new(None)
+            };
+            let mut rom_banks : Vec<[u8;16384]> = Vec::new();
+            for i in 0..(number_of_rom_banks as usize) {
+                let mut rom_bank : [u8;16384] = [0;16384];
+                if has_trainer {
+                    let start = (i*16384)+16+512;
+                    let end = (i*16384)+16+16384+512;
+                    rom_bank.copy_from_slice(&buffer[start..end]);
+                }
+                else {
+                    let start = ((i*16384)+16) as usize;
+                    let end = ((i*16384)+16+16384) as usize;
+                    rom_bank.copy_from_slice(&buffer[start..end]);
+                }
+                rom_banks.push(rom_bank);
+            }
+            let mut vrom_banks : Vec<[u8;8192]> = Vec::new();
+            let mut vrom_bmps : Vec<Bitmap> = Vec::new();
+            let offset = (number_of_rom_banks as usize) * 16384;
+            for i in 0..(number_of_vrom_banks as usize) {
+                let mut vrom_bank : [u8;8192] = [0;8192];
+                if has_trainer {
+                    let start = (i*8192)+16+512+offset;
+                    let end = (i*8192)+16+8192+512+offset;
+                    vrom_bank.copy_from_slice(&buffer[start..end]);
+                }
+                else {
+                    let start = ((i*8192)+16+offset) as usize;
+                    let end = ((i*8192)+16+8192+offset) as usize;
+                    vrom_bank.copy_from_slice(&buffer[start..end]);
                 }
-                Ok(palette)
+                vrom_bmps.push(chr_to_bitmap(vrom_bank, [0xc,0x17,0x28,0x39], palette));
+                vrom_banks.push(vrom_bank);
             }
-     }
-     else {
-         Err(String::from("Failed to open NES Palette!"))
-     }
-    
+
+            println!("Successfully Loaded!\nROM Debug Info:\nNumber of PRG ROM Pages (16K each): {}\nNumber of CHR ROM Pages (8K each): {}\nCHR RAM: {} bytes\nMapper Type: {}\nPAL: {}\nHas Trainer: {}\nMirroring: {}", 
+            number_of_rom_banks, 
+            number_of_vrom_banks, 
+            chr_ram_size,
+            mapper_number, is_pal,
+            has_trainer,
+            mirroring);
+
+            Ok(Rom {
+                number_of_rom_banks: number_of_rom_banks, number_of_vrom_banks: number_of_vrom_banks, 
+                mirroring: mirroring, has_battery_packed_ram: has_battery_packed_ram, has_trainer: has_trainer, 
+                has_four_screen_vram_layout: has_four_screen_vram_layout, rom_mapper_type: mapper_number,
+                is_vs_system_cartidge: is_vs_system_cartidge, number_of_8k_ram_banks: number_of_8k_ram_banks, is_pal: is_pal, is_dendy: is_dendy,
+                trainer: trainer, rom_banks: rom_banks, vrom_banks: vrom_banks, chr_ram_size: chr_ram_size, vrom_bmps: vrom_bmps,
+                palette: palette.to_vec()
+            })    
+        }
+        else {
+            Err(String::from("NES file magic 4 byte string is missing!"))
+        }
+    }
 }
 
-fn chr_to_bitmap(vrom_bank : [u8;8192], selected_palette_indicies : [usize;4],  palette_path : String) -> Bitmap {
-    let palette = load_palette(palette_path).expect("Can't find palette file!");
+fn chr_to_bitmap(vrom_bank : [u8;8192], selected_palette_indicies : [usize;4],  palette : &[(u8,u8,u8,u8)]) -> Bitmap {
     let bmp = Bitmap::new(256, 240).expect("Failed to initialze bitmap!");
     let mut x = 0;
     let mut y = 0;
@@ -200,4 +236,23 @@ fn chr_to_bitmap(vrom_bank : [u8;8192], selected_palette_indicies : [usize;4],
        }
     }
     bmp
-}
\ No newline at end of file
+}
+// Builds an iNES image in memory with every PRG ROM bank filled with its own bank number.
+#[cfg(test)]
+pub fn test_rom(mapper : u8, number_of_rom_banks : u8, number_of_vrom_banks : u8) -> Rom {
+    let mut buffer = vec![0x4e, 0x45, 0x53, 0x1a, number_of_rom_banks, number_of_vrom_banks, (mapper & 0xf) << 4, mapper & 0xf0, 0, 0, 0, 0, 0, 0, 0, 0];
+    for bank in 0..number_of_rom_banks {
+        buffer.extend(vec![bank;16384]);
+    }
+    buffer.extend(vec![0;(number_of_vrom_banks as usize) * 8192]);
+    Rom::from_bytes(&buffer, &[(0,0,0,0xff);64]).expect("Failed to build test ROM!")
+}
+
+#[test]
+fn test_chr_ram_size_from_header() {
+    assert_eq!(test_rom(0, 1, 1).chr_ram_size, 0);
+    assert_eq!(test_rom(2, 2, 0).chr_ram_size, 8192);
+    let mut buffer = vec![0x4e, 0x45, 0x53, 0x1a, 1, 0, 0, 0x08, 0, 0, 0, 9, 0, 0, 0, 0]; // NES 2.0 with 32KB of CHR RAM
+    buffer.extend(vec![0;16384]);
+    assert_eq!(Rom::from_bytes(&buffer, &[]).unwrap().chr_ram_size, 32768);
+}
@@ -0,0 +1,405 @@
+use crate::cpu::{self,CPU};
+use crate::nes::Nes;
+use crate::hooks::Access;
+use std::cell::Cell;
+
+const OAM_DMA_CYCLES : u64 = 513;
+const DMC_DMA_CYCLES : u64 = 4;
+const DMC_DMA_CYCLES_AFTER_WRITE : u64 = 3; // The CPU only halts on a read, so a write cycle gets one fewer.
+const DMC_DMA_CYCLES_DURING_OAM_DMA : u64 = 2; // OAM DMA has already halted the CPU, only alignment and the read are left.
+
+// Drives the CPU, PPU and APU from the master clock. The CPU runs whole instructions and the PPU and APU
+// are then caught up cycle by cycle, so interrupts raised by them are seen at the next instruction boundary.
+pub struct Scheduler {
+    cpu : CPU,
+    master_clock : Cell<u64>, // Master clock ticks elapsed.
+    ppu_clock : Cell<u64>, // Master clock tick the PPU has been run up to.
+    cpu_cycles : Cell<u64>,
+    jammed : Cell<bool>, // Set when the CPU hits an opcode it can't execute, it stays put while the rest of the console runs.
+    late_nmi : Cell<bool>, // The NMI or IRQ line went active on the last cycle of the step just run, after the CPU polled it.
+    late_irq : Cell<bool>
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        Scheduler { cpu: CPU::new(), master_clock: Cell::new(0), ppu_clock: Cell::new(0), cpu_cycles: Cell::new(0), jammed: Cell::new(false),
+                    late_nmi: Cell::new(false), late_irq: Cell::new(false) }
+    }
+
+    pub fn cpu_cycles(&self) -> u64 {
+        self.cpu_cycles.get()
+    }
+
+    pub fn is_jammed(&self) -> bool {
+        self.jammed.get()
+    }
+
+    // Called alongside Nes::reset, a reset gets a jammed CPU running again.
+    pub fn reset(&self) {
+        self.jammed.set(false);
+    }
+
+    // Runs whole instructions until at least the given number of CPU cycles have elapsed.
+    pub fn run_cycles(&self, nes : &Nes, cycles : u64) {
+        let target = self.cpu_cycles.get() + cycles;
+        while self.cpu_cycles.get() < target {
+            self.step(nes);
+        }
+    }
+
+    // Runs until the PPU finishes the current frame.
+    pub fn run_frame(&self, nes : &Nes) {
+        let frame = nes.ppu.frame.get();
+        while nes.ppu.frame.get() == frame {
+            self.step(nes);
+        }
+    }
+
+    // Runs one instruction or interrupt sequence and clocks the other components for its duration.
+    fn step(&self, nes : &Nes) {
+        // The CPU polls for interrupts at the end of an instruction's second-to-last cycle, so one raised on the last
+        // cycle only gets taken after the next instruction. The I flag is still read after the instruction, which isn't
+        // right for CLI, SEI and PLP: the poll happens before they change it, so an IRQ should get in right after SEI
+        // or PLP setting it and wait an instruction after CLI or PLP clearing it.
+        let late_nmi = self.late_nmi.replace(false);
+        let late_irq = self.late_irq.replace(false);
+        let cycles = if nes.nmi_pending.get() && !late_nmi {
+            nes.nmi_pending.set(false);
+            self.jammed.set(false);
+            cpu::interrupt(nes, cpu::NMI_VECTOR);
+            cpu::INTERRUPT_CYCLES
+        }
+        else if nes.irq_sources.get() != 0 && !late_irq && nes.processor_status_flag.get() & 0b100 == 0 {
+            self.jammed.set(false);
+            cpu::interrupt(nes, cpu::IRQ_VECTOR);
+            cpu::INTERRUPT_CYCLES
+        }
+        else if self.jammed.get() {
+            1
+        }
+        else if let Some(cycles) = self.cpu.step(nes) {
+            cycles
+        }
+        else {
+            self.jammed.set(true);
+            1
+        };
+        self.clock(nes, cycles as u64, false);
+        if let Some(page) = nes.oam_dma_page.take() {
+            // The CPU is halted for 513 cycles, plus one more to line up with a read cycle when the DMA starts on an odd cycle.
+            nes.oam_dma(page);
+            self.clock(nes, OAM_DMA_CYCLES + (self.cpu_cycles.get() & 1), true);
+        }
+    }
+
+    fn clock(&self, nes : &Nes, cycles : u64, during_oam_dma : bool) {
+        let region = nes.region.get();
+        let mut remaining = cycles;
+        while remaining > 0 {
+            remaining -= 1;
+            let nmi = nes.nmi_pending.get();
+            let irq = nes.irq_sources.get() != 0;
+            self.master_clock.set(self.master_clock.get() + region.cpu_divider());
+            self.cpu_cycles.set(self.cpu_cycles.get() + 1);
+            nes.apu.step(nes);
+            if let Some(address) = nes.apu.dmc.dma_address() {
+                remaining += self.dmc_dma(nes, address, during_oam_dma, remaining == 0);
+            }
+            while self.ppu_clock.get() + region.ppu_divider() <= self.master_clock.get() {
+                self.ppu_clock.set(self.ppu_clock.get() + region.ppu_divider());
+                nes.ppu.step(nes);
+            }
+            if remaining == 0 {
+                self.late_nmi.set(nes.nmi_pending.get() && !nmi);
+                self.late_irq.set(nes.irq_sources.get() != 0 && !irq);
+            }
+        }
+    }
+
+    // Fetches the DMC's next sample byte, returning how many cycles the CPU is stalled for. Instructions run whole,
+    // so the DMA is taken to interrupt the instruction's last bus cycle when it falls on its final cycle. A halted
+    // read is repeated while the CPU waits, which clocks the controllers again and loses a bit from them.
+    fn dmc_dma(&self, nes : &Nes, address : u16, during_oam_dma : bool, last_cycle : bool) -> u64 {
+        let (access, last_address) = nes.last_access.get();
+        let stall = if during_oam_dma {
+            DMC_DMA_CYCLES_DURING_OAM_DMA
+        }
+        else if last_cycle && access == Access::Write {
+            DMC_DMA_CYCLES_AFTER_WRITE
+        }
+        else {
+            DMC_DMA_CYCLES
+        };
+        if !during_oam_dma && last_cycle && access == Access::Read && (last_address == 0x4016 || last_address == 0x4017) {
+            nes.read(last_address);
+        }
+        let value = nes.read(address);
+        nes.apu.dmc.fill_sample_buffer(nes, value);
+        stall
+    }
+}
+
+#[cfg(test)]
+const TEST_ROM_MAX_FRAMES : u32 = 60 * 60;
+#[cfg(test)]
+const TEST_ROM_RESET_DELAY : u32 = 6; // Frames to wait before pressing reset when asked, the ROMs want at least 100 ms.
+
+// Runs one of blargg's test ROMs that report through PRG RAM: once $6001-$6003 hold DE B0 61, $6000 is $80 while the
+// test runs, $81 when it wants reset pressed, then the result code with 0 for a pass and a message from $6004.
+// Returns None when the ROM file isn't there, the test ROMs aren't shipped with the repository.
+#[cfg(test)]
+pub fn run_test_rom(path : &str) -> Option<Result<(), String>> {
+    if !std::path::Path::new(path).exists() {
+        return None;
+    }
+    let rom = crate::rom::Rom::load(path.to_string(), &crate::palette::Preset::Ntsc2C02.palette()).expect("Failed to load test ROM");
+    let nes = Nes::new();
+    nes.insert_cartridge(crate::cartridge::Cartridge::new(rom).expect("Unsupported test ROM"));
+    let scheduler = Scheduler::new();
+    // Read straight from the cartridge so checking on the ROM doesn't disturb the open bus or the hooks.
+    let prg_ram = |address : u16| nes.cartridge.borrow().as_ref().and_then(|cartridge| cartridge.cpu_read(address)).unwrap_or(0);
+    let mut reset_frames = 0;
+    for _ in 0..TEST_ROM_MAX_FRAMES {
+        scheduler.run_frame(&nes);
+        if (prg_ram(0x6001), prg_ram(0x6002), prg_ram(0x6003)) != (0xde, 0xb0, 0x61) {
+            continue;
+        }
+        match prg_ram(0x6000) {
+            0x80 => {}
+            0x81 => {
+                reset_frames += 1;
+                if reset_frames == TEST_ROM_RESET_DELAY {
+                    reset_frames = 0;
+                    nes.reset();
+                    scheduler.reset();
+                }
+            }
+            result => {
+                let message : String = (0x6004..0x7000).map(prg_ram).take_while(|&byte| byte != 0).map(|byte| byte as char).collect();
+                return Some(if result == 0 { Ok(()) } else { Err(format!("{} failed with code {}: {}", path, result, message.trim())) });
+            }
+        }
+    }
+    Some(Err(format!("{} didn't finish within {} frames", path, TEST_ROM_MAX_FRAMES)))
+}
+
+#[cfg(test)]
+fn nop_filled_nes() -> Nes {
+    let nes = Nes::new();
+    {
+        let mut memory = nes.memory.borrow_mut();
+        for byte in memory[0x8000..0xfffa].iter_mut() {
+            *byte = 0xea;
+        }
+    }
+    nes.program_counter.set(0x8000);
+    nes
+}
+
+#[cfg(test)]
+fn ppu_dots(nes : &Nes) -> u64 {
+    let lines = nes.region.get().scanlines_per_frame() as u64;
+    ((nes.ppu.frame.get() * lines) + nes.ppu.scanline.get() as u64) * (crate::ppu::DOTS_PER_SCANLINE as u64) + nes.ppu.dot.get() as u64
+}
+
+#[test]
+fn test_scheduler_runs_three_dots_per_cycle_on_ntsc() {
+    let nes = nop_filled_nes();
+    let scheduler = Scheduler::new();
+    scheduler.run_cycles(&nes, 10);
+    assert_eq!(scheduler.cpu_cycles(), 10);
+    assert_eq!(nes.apu.cycles.get(), 10);
+    assert_eq!(ppu_dots(&nes), 30);
+    assert_eq!(nes.program_counter.get(), 0x8005);
+}
+
+#[test]
+fn test_scheduler_runs_three_point_two_dots_per_cycle_on_pal() {
+    let nes = nop_filled_nes();
+    nes.region.set(crate::region::Region::Pal);
+    let scheduler = Scheduler::new();
+    scheduler.run_cycles(&nes, 10);
+    assert_eq!(ppu_dots(&nes), 32);
+}
+
+#[test]
+fn test_scheduler_runs_three_dots_per_cycle_on_dendy() {
+    let nes = nop_filled_nes();
+    nes.region.set(crate::region::Region::Dendy);
+    let scheduler = Scheduler::new();
+    scheduler.run_cycles(&nes, 10);
+    assert_eq!(ppu_dots(&nes), 30);
+}
+
+#[test]
+fn test_scheduler_run_frame_stops_at_frame_boundary() {
+    let nes = nop_filled_nes();
+    let scheduler = Scheduler::new();
+    scheduler.run_frame(&nes);
+    assert_eq!(nes.ppu.frame.get(), 1);
+    assert!(nes.ppu.scanline.get() == 0 && nes.ppu.dot.get() < 6); // Overshoots by at most one 2 cycle instruction.
+}
+
+#[test]
+fn test_scheduler_delivers_nmi_at_instruction_boundary() {
+    let nes = nop_filled_nes();
+    {
+        let mut memory = nes.memory.borrow_mut();
+        memory[0xfffa] = 0x00;
+        memory[0xfffb] = 0x90;
+    }
+    let scheduler = Scheduler::new();
+    scheduler.run_cycles(&nes, 2);
+    nes.nmi_pending.set(true);
+    scheduler.run_cycles(&nes, 1);
+    assert_eq!(nes.program_counter.get(), 0x9000);
+    assert_eq!(scheduler.cpu_cycles(), 2 + 7);
+    assert!(!nes.nmi_pending.get());
+}
+
+#[test]
+fn test_scheduler_irq_respects_interrupt_disable() {
+    let nes = nop_filled_nes();
+    {
+        let mut memory = nes.memory.borrow_mut();
+        memory[0xfffe] = 0x00;
+        memory[0xffff] = 0xa0;
+    }
+    let scheduler = Scheduler::new();
+    nes.irq_sources.set(1);
+    nes.processor_status_flag.set(0b100);
+    scheduler.run_cycles(&nes, 2);
+    assert_eq!(nes.program_counter.get(), 0x8001);
+    nes.processor_status_flag.set(0);
+    scheduler.run_cycles(&nes, 1);
+    assert_eq!(nes.program_counter.get(), 0xa000);
+}
+
+#[test]
+fn test_scheduler_nmi_on_last_cycle_waits_an_instruction() {
+    // Each NOP's two cycles cover three dots each. Vblank starting on the first cycle is polled in time, on the second
+    // it's too late and the next NOP runs first.
+    for &(scanline, dot, nmi_step) in [(241, 0, 2), (240, 339, 3)].iter() {
+        let nes = nop_filled_nes();
+        {
+            let mut memory = nes.memory.borrow_mut();
+            memory[0xfffa] = 0x00;
+            memory[0xfffb] = 0x90;
+        }
+        nes.write(0x2000, 0x80);
+        nes.ppu.scanline.set(scanline);
+        nes.ppu.dot.set(dot);
+        let scheduler = Scheduler::new();
+        for _ in 1..nmi_step {
+            scheduler.step(&nes);
+        }
+        assert_eq!(nes.program_counter.get(), 0x8000 + nmi_step - 1);
+        scheduler.step(&nes);
+        assert_eq!(nes.program_counter.get(), 0x9000);
+    }
+}
+
+#[test]
+fn test_scheduler_oam_dma_copies_page_and_stalls_cpu() {
+    let nes = nop_filled_nes();
+    {
+        let mut memory = nes.memory.borrow_mut();
+        for (index, byte) in memory[0x0200..0x0300].iter_mut().enumerate() {
+            *byte = index as u8;
+        }
+        memory[0x8000] = 0xa9; // LDA #$02
+        memory[0x8001] = 0x02;
+        memory[0x8002] = 0x8d; // STA $4014
+        memory[0x8003] = 0x14;
+        memory[0x8004] = 0x40;
+    }
+    nes.ppu.write_register(&nes, 0x2003, 0x10);
+    let scheduler = Scheduler::new();
+    scheduler.run_cycles(&nes, 2 + 4);
+    assert_eq!(scheduler.cpu_cycles(), 2 + 4 + 513);
+    assert_eq!(nes.ppu.oam.borrow()[0x10], 0x00);
+    assert_eq!(nes.ppu.oam.borrow()[0x0f], 0xff);
+    assert_eq!(nes.ppu.oam_address.get(), 0x10);
+    nes.program_counter.set(0x8002);
+    scheduler.run_cycles(&nes, 1);
+    assert_eq!(scheduler.cpu_cycles(), 2 + 4 + 513 + 4 + 514); // Started on an odd cycle
+}
+
+#[test]
+fn test_scheduler_dmc_dma_fetches_sample_and_stalls_cpu() {
+    let nes = nop_filled_nes();
+    {
+        let mut memory = nes.memory.borrow_mut();
+        memory[0xc000] = 0x5a;
+    }
+    let scheduler = Scheduler::new();
+    nes.write(0x4013, 0x00); // 1 byte sample at $C000
+    nes.write(0x4015, 0x10);
+    scheduler.run_cycles(&nes, 2);
+    assert_eq!(scheduler.cpu_cycles(), 2 + DMC_DMA_CYCLES);
+    assert_eq!(nes.program_counter.get(), 0x8001);
+    assert!(!nes.apu.dmc.is_active());
+    assert_eq!(nes.last_access.get(), (Access::Read, 0xc000));
+}
+
+#[test]
+fn test_scheduler_dmc_dma_stall_depends_on_halted_cycle() {
+    let nes = nop_filled_nes();
+    let scheduler = Scheduler::new();
+    nes.write(0x4013, 0xff);
+    nes.write(0x4015, 0x10);
+    nes.write(0x0200, 0);
+    assert_eq!(scheduler.dmc_dma(&nes, 0xc000, false, true), DMC_DMA_CYCLES_AFTER_WRITE);
+    nes.write(0x0200, 0);
+    assert_eq!(scheduler.dmc_dma(&nes, 0xc000, false, false), DMC_DMA_CYCLES);
+    assert_eq!(scheduler.dmc_dma(&nes, 0xc000, true, true), DMC_DMA_CYCLES_DURING_OAM_DMA);
+}
+
+#[test]
+fn test_scheduler_dmc_dma_glitches_controller_reads() {
+    use crate::controller::Button;
+    let nes = nop_filled_nes();
+    let scheduler = Scheduler::new();
+    nes.write(0x4013, 0xff);
+    nes.write(0x4015, 0x10);
+    nes.controllers[0].set_button(Button::B, true);
+    nes.write(0x4016, 1);
+    nes.write(0x4016, 0);
+    assert_eq!(nes.read(0x4016) & 1, 0); // A
+    scheduler.dmc_dma(&nes, 0xc000, false, true); // Repeats the read, so B is lost
+    assert_eq!(nes.read(0x4016) & 1, 0); // Select
+    nes.write(0x4016, 1);
+    nes.write(0x4016, 0);
+    nes.read(0x4016);
+    scheduler.dmc_dma(&nes, 0xc000, false, false);
+    assert_eq!(nes.read(0x4016) & 1, 1); // B survives when the DMA misses the read
+}
+
+// A ROM that reports DE B0 61, a message and the given result code the way blargg's ROMs do, then loops.
+#[cfg(test)]
+fn reporting_test_rom(result : u8) -> Vec<u8> {
+    let mut prg = vec![0;16384];
+    let program = [0xa9, 0xde, 0x8d, 0x01, 0x60, 0xa9, 0xb0, 0x8d, 0x02, 0x60, 0xa9, 0x61, 0x8d, 0x03, 0x60, // Signature
+                   0xa9, b'H', 0x8d, 0x04, 0x60, 0xa9, b'i', 0x8d, 0x05, 0x60, // Message
+                   0xa9, result, 0x8d, 0x00, 0x60,
+                   0x4c, 0x1e, 0xc0]; // JMP to itself
+    prg[..program.len()].copy_from_slice(&program);
+    prg[0x3ffc] = 0x00; // Reset vector, $C000
+    prg[0x3ffd] = 0xc0;
+    let mut bytes = vec![0x4e, 0x45, 0x53, 0x1a, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+    bytes.extend(prg);
+    bytes.extend(vec![0;8192]);
+    bytes
+}
+
+#[test]
+fn test_run_test_rom_reads_result() {
+    assert_eq!(run_test_rom("roms/missing.nes"), None);
+    let path = std::env::temp_dir().join("rustynes_test_rom.nes");
+    let path = path.to_str().unwrap();
+    std::fs::write(path, reporting_test_rom(0)).unwrap();
+    assert_eq!(run_test_rom(path), Some(Ok(())));
+    std::fs::write(path, reporting_test_rom(3)).unwrap();
+    assert_eq!(run_test_rom(path), Some(Err(format!("{} failed with code 3: Hi", path))));
+    std::fs::remove_file(path).unwrap();
+}
@@ -0,0 +1,156 @@
+use crate::apu::LengthCounter;
+use std::cell::Cell;
+
+// Triangle channel at $4008-$400B. It has no volume control, only a linear counter that gates it at a finer
+// resolution than the length counter.
+
+const SEQUENCE : [u8;32] = [
+    15, 14, 13, 12, 11, 10, 9, 8, 7, 6, 5, 4, 3, 2, 1, 0,
+    0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15
+];
+// Periods this short step the sequencer above 27 kHz. Real hardware plays them, but what comes out after the
+// TV's filtering is the waveform's average, so the channel is held there instead of aliasing.
+const ULTRASONIC_PERIOD : u16 = 2;
+const ULTRASONIC_OUTPUT : u8 = 7;
+
+pub struct Triangle {
+    sequence_step : Cell<u8>,
+    timer_period : Cell<u16>,
+    timer : Cell<u16>,
+    linear_counter : Cell<u8>,
+    linear_reload_value : Cell<u8>,
+    linear_reload : Cell<bool>,
+    control : Cell<bool>, // Halts the length counter and keeps the linear counter reloading.
+    pub length_counter : LengthCounter
+}
+
+impl Triangle {
+    pub fn new() -> Self {
+        Triangle { sequence_step: Cell::new(0), timer_period: Cell::new(0), timer: Cell::new(0), linear_counter: Cell::new(0),
+                   linear_reload_value: Cell::new(0), linear_reload: Cell::new(false), control: Cell::new(false), length_counter: LengthCounter::new() }
+    }
+
+    // Register 0-3 of the channel, register 1 being unused.
+    pub fn write_register(&self, register : u16, value : u8) {
+        match register {
+            0 => {
+                self.control.set(value & 0x80 != 0);
+                self.length_counter.set_halted(value & 0x80 != 0);
+                self.linear_reload_value.set(value & 0x7f);
+            }
+            1 => {}
+            2 => self.timer_period.set((self.timer_period.get() & 0x700) | value as u16),
+            _ => {
+                self.timer_period.set((self.timer_period.get() & 0xff) | (((value & 7) as u16) << 8));
+                self.length_counter.load(value);
+                self.linear_reload.set(true);
+            }
+        }
+    }
+
+    // Clocked every CPU cycle. The sequencer only moves while both counters are running.
+    pub fn clock_timer(&self) {
+        if self.timer.get() == 0 {
+            self.timer.set(self.timer_period.get());
+            if self.linear_counter.get() > 0 && self.length_counter.is_active() && !self.is_ultrasonic() {
+                self.sequence_step.set((self.sequence_step.get() + 1) % 32);
+            }
+        }
+        else {
+            self.timer.set(self.timer.get() - 1);
+        }
+    }
+
+    // Clocked by the frame counter every quarter frame.
+    pub fn clock_linear_counter(&self) {
+        if self.linear_reload.get() {
+            self.linear_counter.set(self.linear_reload_value.get());
+        }
+        else if self.linear_counter.get() > 0 {
+            self.linear_counter.set(self.linear_counter.get() - 1);
+        }
+        if !self.control.get() {
+            self.linear_reload.set(false);
+        }
+    }
+
+    fn is_ultrasonic(&self) -> bool {
+        self.timer_period.get() < ULTRASONIC_PERIOD
+    }
+
+    // Current level, 0-15. Silencing the channel freezes the sequencer rather than dropping to 0, so it keeps its level.
+    pub fn output(&self) -> u8 {
+        if self.is_ultrasonic() && self.length_counter.is_active() && self.linear_counter.get() > 0 {
+            ULTRASONIC_OUTPUT
+        }
+        else {
+            SEQUENCE[self.sequence_step.get() as usize]
+        }
+    }
+}
+
+#[cfg(test)]
+fn playing_triangle(period : u16, linear : u8) -> Triangle {
+    let triangle = Triangle::new();
+    triangle.length_counter.set_enabled(true);
+    triangle.write_register(0, linear);
+    triangle.write_register(2, (period & 0xff) as u8);
+    triangle.write_register(3, 0x08 | (period >> 8) as u8);
+    triangle.clock_linear_counter();
+    triangle
+}
+
+#[test]
+fn test_triangle_sequence() {
+    let triangle = playing_triangle(3, 0x7f);
+    let mut waveform = Vec::new();
+    for _ in 0..32 {
+        waveform.push(triangle.output());
+        for _ in 0..4 {
+            triangle.clock_timer();
+        }
+    }
+    assert_eq!(waveform[0..4], [15, 14, 13, 12]);
+    assert_eq!(waveform[14..19], [1, 0, 0, 1, 2]);
+    assert_eq!(triangle.output(), 15);
+}
+
+#[test]
+fn test_triangle_linear_counter() {
+    let triangle = playing_triangle(0, 0x02);
+    assert_eq!(triangle.linear_counter.get(), 2);
+    triangle.clock_linear_counter();
+    triangle.clock_linear_counter();
+    assert_eq!(triangle.linear_counter.get(), 0);
+    triangle.clock_linear_counter();
+    assert_eq!(triangle.linear_counter.get(), 0); // Reload flag was cleared without the control bit
+    triangle.write_register(0, 0x82);
+    triangle.write_register(3, 0x08);
+    triangle.clock_linear_counter();
+    triangle.clock_linear_counter();
+    assert_eq!(triangle.linear_counter.get(), 2); // Control keeps it reloading
+    triangle.write_register(0, 0x02);
+    triangle.clock_linear_counter();
+    triangle.clock_linear_counter();
+    assert_eq!(triangle.linear_counter.get(), 1);
+}
+
+#[test]
+fn test_triangle_freezes_when_silenced_or_ultrasonic() {
+    let triangle = playing_triangle(3, 0x01);
+    for _ in 0..8 {
+        triangle.clock_timer();
+    }
+    let level = triangle.output();
+    triangle.clock_linear_counter();
+    for _ in 0..40 {
+        triangle.clock_timer();
+    }
+    assert_eq!(triangle.output(), level);
+    let ultrasonic = playing_triangle(1, 0x7f);
+    for _ in 0..10 {
+        ultrasonic.clock_timer();
+    }
+    assert_eq!(ultrasonic.output(), ULTRASONIC_OUTPUT);
+    assert_eq!(ultrasonic.sequence_step.get(), 0);
+}